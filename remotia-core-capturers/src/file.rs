@@ -0,0 +1,586 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::debug;
+use remotia_core::{
+    error::ProcessorError,
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+use tokio::time::Instant;
+
+/// Buffers in which the planes of Y4M sequences are written
+const Y4M_BUFFERS: [&str; 3] = ["y_channel_buffer", "cb_channel_buffer", "cr_channel_buffer"];
+
+enum Source {
+    /// Frames dumped one per file, sorted by the timestamp in their name
+    Dumps {
+        files: Vec<(u128, PathBuf)>,
+        position: usize,
+    },
+
+    /// Frames stored back to back, each made of the given planes
+    Raw {
+        path: PathBuf,
+        reader: BufReader<File>,
+        planes: Vec<(String, usize)>,
+        frame: Vec<u8>,
+    },
+
+    Y4M {
+        path: PathBuf,
+        decoder: y4m::Decoder<BufReader<File>>,
+    },
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn open_y4m(path: &Path) -> io::Result<y4m::Decoder<BufReader<File>>> {
+    y4m::decode(BufReader::new(File::open(path)?)).map_err(|error| match error {
+        y4m::Error::IoError(error) => error,
+        error => invalid_data(error),
+    })
+}
+
+/// Fills the buffer as much as possible, returning the number of bytes read
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(count) => read += count,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(read)
+}
+
+fn write_buffer(frame_data: &mut FrameData, key: &str, data: &[u8]) -> Result<(), ProcessorError> {
+    let buffer = frame_data
+        .get_writable_buffer_ref(key)
+        .ok_or_else(|| ProcessorError::MissingBuffer(key.to_string()))?;
+
+    if buffer.len() < data.len() {
+        return Err(ProcessorError::BufferTooSmall {
+            key: key.to_string(),
+            required: data.len(),
+            found: buffer.len(),
+        });
+    }
+
+    buffer[..data.len()].copy_from_slice(data);
+    Ok(())
+}
+
+impl Source {
+    /// Reads the next frame into the buffers of the frame data, returning `None` at the end
+    /// of the source, or the original timestamp of the frame if known
+    fn read(
+        &mut self,
+        frame_data: &mut FrameData,
+        buffer_id: &str,
+    ) -> Result<Option<Option<u128>>, ProcessorError> {
+        match self {
+            Source::Dumps { files, position } => {
+                let (timestamp, path) = match files.get(*position) {
+                    Some(file) => file,
+                    None => return Ok(None),
+                };
+
+                // Move past the dump even if it cannot be read, so that it is skipped when
+                // the error is dropped rather than failing again on every frame
+                *position += 1;
+                write_buffer(frame_data, buffer_id, &fs::read(path)?)?;
+
+                Ok(Some(Some(*timestamp)))
+            }
+            Source::Raw {
+                path,
+                reader,
+                planes,
+                frame,
+            } => {
+                let read = read_full(reader, frame)?;
+                if read < frame.len() {
+                    if read > 0 {
+                        debug!("Ignoring the truncated last frame of {:?}", path);
+                    }
+
+                    return Ok(None);
+                }
+
+                let mut offset = 0;
+                for (buffer_id, size) in planes.iter() {
+                    write_buffer(frame_data, buffer_id, &frame[offset..offset + size])?;
+                    offset += size;
+                }
+
+                Ok(Some(None))
+            }
+            Source::Y4M { decoder, .. } => {
+                let frame = match decoder.read_frame() {
+                    Ok(frame) => frame,
+                    Err(y4m::Error::EOF) => return Ok(None),
+                    Err(y4m::Error::IoError(error)) => return Err(error.into()),
+                    Err(error) => return Err(ProcessorError::Other(error.to_string())),
+                };
+
+                let planes = [
+                    frame.get_y_plane(),
+                    frame.get_u_plane(),
+                    frame.get_v_plane(),
+                ];
+                for (buffer_id, plane) in Y4M_BUFFERS.iter().zip(planes) {
+                    if !plane.is_empty() {
+                        write_buffer(frame_data, buffer_id, plane)?;
+                    }
+                }
+
+                Ok(Some(None))
+            }
+        }
+    }
+
+    /// Restarts the source from its first frame
+    fn rewind(&mut self) -> io::Result<()> {
+        match self {
+            Source::Dumps { position, .. } => *position = 0,
+            Source::Raw { reader, .. } => {
+                reader.rewind()?;
+            }
+            Source::Y4M { path, decoder } => *decoder = open_y4m(path)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Replays recorded frames into a pipeline, in place of a live capturer.
+///
+/// Frames can be read from a folder of raw dumps such as the ones written by the
+/// `RawFrameDumper`, from a file of raw frames stored back to back, or from a Y4M sequence.
+/// They are written into buffers which must already be allocated, and released at the
+/// original frame rate unless another one is configured. The index of each frame is stored
+/// in the "frame_index" stat and, for dumps, the timestamp in its file name in the
+/// "source_timestamp" stat.
+///
+/// At the end of the source the capturer either loops or ends the stream, stopping its
+/// component.
+pub struct FileFrameCapturer {
+    source: Source,
+
+    /// Interval between frames, overriding the timestamps of the source when set
+    frame_interval: Option<Duration>,
+    paced: bool,
+    looping: bool,
+
+    buffer_id: String,
+
+    frame_index: u64,
+    last_timestamp: Option<u128>,
+    last_interval: Duration,
+    deadline: Option<Instant>,
+}
+
+impl FileFrameCapturer {
+    fn from_source(source: Source) -> Self {
+        Self {
+            source,
+            frame_interval: None,
+            paced: true,
+            looping: false,
+            buffer_id: "raw_frame_buffer".to_string(),
+            frame_index: 0,
+            last_timestamp: None,
+            last_interval: Duration::ZERO,
+            deadline: None,
+        }
+    }
+
+    /// Replays the `.bgra` dumps in a folder, named after their capture timestamp in
+    /// milliseconds. Other files, and dumps whose name is not a timestamp, are ignored.
+    pub fn dumps(folder: &Path) -> io::Result<Self> {
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(folder)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("bgra") {
+                continue;
+            }
+
+            let timestamp = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u128>().ok());
+
+            if let (Some(timestamp), true) = (timestamp, path.is_file()) {
+                files.push((timestamp, path));
+            }
+        }
+
+        files.sort();
+        debug!("Found {} dumps in {:?}", files.len(), folder);
+
+        Ok(Self::from_source(Source::Dumps { files, position: 0 }))
+    }
+
+    /// Replays a file of raw frames, such as YUV or BGRA ones, made of planes of the given
+    /// sizes which are written in the buffers with the given keys. As the file carries no
+    /// timing, frames are not paced unless a frame rate is configured.
+    pub fn raw(path: &Path, planes: &[(&str, usize)]) -> io::Result<Self> {
+        let planes: Vec<(String, usize)> = planes
+            .iter()
+            .map(|(buffer_id, size)| (buffer_id.to_string(), *size))
+            .collect();
+        let frame_size = planes.iter().map(|(_, size)| size).sum();
+
+        Ok(Self::from_source(Source::Raw {
+            path: path.to_path_buf(),
+            reader: BufReader::new(File::open(path)?),
+            planes,
+            frame: vec![0; frame_size],
+        })
+        .paced(false))
+    }
+
+    /// Replays a Y4M sequence at the frame rate in its header, writing its planes in the
+    /// "y_channel_buffer", "cb_channel_buffer" and "cr_channel_buffer"
+    pub fn y4m(path: &Path) -> io::Result<Self> {
+        let decoder = open_y4m(path)?;
+        let frame_rate = decoder.get_framerate();
+
+        debug!(
+            "Opened a {}x{} {:?} Y4M sequence at {} FPS",
+            decoder.get_width(),
+            decoder.get_height(),
+            decoder.get_colorspace(),
+            frame_rate
+        );
+
+        let capturer = Self::from_source(Source::Y4M {
+            path: path.to_path_buf(),
+            decoder,
+        });
+
+        Ok(match (frame_rate.num, frame_rate.den) {
+            (0, _) | (_, 0) => capturer,
+            (num, den) => capturer.frame_rate(num as f64 / den as f64),
+        })
+    }
+
+    /// Frames per second at which frames are released, overriding the original timing
+    pub fn frame_rate(mut self, frame_rate: f64) -> Self {
+        assert!(frame_rate > 0.0, "The frame rate must be positive");
+        self.frame_interval = Some(Duration::from_secs_f64(1.0 / frame_rate));
+        self.paced = true;
+        self
+    }
+
+    /// Whether frames are released at their frame rate or as fast as the pipeline pulls them
+    pub fn paced(mut self, paced: bool) -> Self {
+        self.paced = paced;
+        self
+    }
+
+    /// Whether the source restarts from its first frame once over
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Buffer in which dumps are written
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Interval since the previous frame, given the timestamp of the current one
+    fn interval(&mut self, timestamp: Option<u128>) -> Duration {
+        if let Some(interval) = self.frame_interval {
+            return interval;
+        }
+
+        // When looping, the first frame follows the last one as the previous frames did
+        if let (Some(timestamp), Some(last_timestamp)) = (timestamp, self.last_timestamp) {
+            if timestamp >= last_timestamp {
+                self.last_interval = Duration::from_millis((timestamp - last_timestamp) as u64);
+            }
+        }
+
+        self.last_timestamp = timestamp;
+        self.last_interval
+    }
+
+    async fn pace(&mut self, timestamp: Option<u128>) {
+        let interval = self.interval(timestamp);
+
+        if !self.paced {
+            // Reading files never suspends, let the other components run in between frames
+            tokio::task::yield_now().await;
+            return;
+        }
+
+        let deadline = match self.deadline {
+            Some(deadline) => deadline + interval,
+            None => Instant::now(),
+        };
+
+        // Restart the schedule if the pipeline fell behind, rather than bursting to catch up
+        let now = Instant::now();
+        let deadline = if deadline < now {
+            now
+        } else {
+            tokio::time::sleep_until(deadline).await;
+            deadline
+        };
+
+        self.deadline = Some(deadline);
+    }
+
+    async fn capture_on_frame_data(
+        &mut self,
+        frame_data: &mut FrameData,
+    ) -> Result<(), ProcessorError> {
+        debug!("Replaying frame {}...", self.frame_index);
+
+        let timestamp = match self.source.read(frame_data, &self.buffer_id)? {
+            Some(timestamp) => timestamp,
+            None if self.looping && self.frame_index > 0 => {
+                debug!("End of the source reached, looping");
+                self.source.rewind()?;
+
+                self.source
+                    .read(frame_data, &self.buffer_id)?
+                    .ok_or(ProcessorError::EndOfStream)?
+            }
+            None => {
+                debug!("End of the source reached");
+                return Err(ProcessorError::EndOfStream);
+            }
+        };
+
+        self.pace(timestamp).await;
+
+        frame_data.set("frame_index", self.frame_index as u128);
+        if let Some(timestamp) = timestamp {
+            frame_data.set("source_timestamp", timestamp);
+        }
+
+        self.frame_index += 1;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for FileFrameCapturer {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        match self.capture_on_frame_data(&mut frame_data).await {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use bytes::BytesMut;
+    use remotia_core::{error::ProcessorError, types::FrameData};
+
+    use super::FileFrameCapturer;
+
+    const FRAME_SIZE: usize = 16;
+
+    /// Empty folder, unique to the test
+    fn folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!(
+            "remotia_file_capturer_{}_{}",
+            name,
+            std::process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn capture(
+        capturer: &mut FileFrameCapturer,
+        buffers: &[(&str, usize)],
+    ) -> Result<FrameData, ProcessorError> {
+        let mut frame_data = FrameData::default();
+        for (buffer_id, size) in buffers {
+            frame_data.insert_writable_buffer(buffer_id, BytesMut::zeroed(*size));
+        }
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(capturer.capture_on_frame_data(&mut frame_data))?;
+
+        Ok(frame_data)
+    }
+
+    fn buffer(frame_data: &mut FrameData, buffer_id: &str) -> Vec<u8> {
+        frame_data
+            .get_writable_buffer_ref(buffer_id)
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn dumps_are_replayed_in_timestamp_order() {
+        let folder = folder("dumps");
+        for timestamp in [30u8, 10, 20] {
+            fs::write(
+                folder.join(format!("{}.bgra", timestamp)),
+                [timestamp; FRAME_SIZE],
+            )
+            .unwrap();
+        }
+        fs::write(folder.join("40.txt"), [40; FRAME_SIZE]).unwrap();
+        fs::write(folder.join("notes.bgra"), [50; FRAME_SIZE]).unwrap();
+
+        let mut capturer = FileFrameCapturer::dumps(&folder).unwrap().paced(false);
+        let buffers = [("raw_frame_buffer", FRAME_SIZE)];
+
+        for (index, timestamp) in [10u8, 20, 30].into_iter().enumerate() {
+            let mut frame_data = capture(&mut capturer, &buffers).unwrap();
+
+            assert_eq!(frame_data.get("frame_index"), index as u128);
+            assert_eq!(frame_data.get("source_timestamp"), timestamp as u128);
+            assert_eq!(
+                buffer(&mut frame_data, "raw_frame_buffer"),
+                [timestamp; FRAME_SIZE]
+            );
+        }
+
+        assert!(matches!(
+            capture(&mut capturer, &buffers),
+            Err(ProcessorError::EndOfStream)
+        ));
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn failing_dumps_are_skipped() {
+        let folder = folder("failing_dumps");
+        fs::write(folder.join("10.bgra"), [10; FRAME_SIZE * 2]).unwrap();
+        fs::write(folder.join("20.bgra"), [20; FRAME_SIZE]).unwrap();
+
+        let mut capturer = FileFrameCapturer::dumps(&folder).unwrap().paced(false);
+        let buffers = [("raw_frame_buffer", FRAME_SIZE)];
+
+        assert!(matches!(
+            capture(&mut capturer, &buffers),
+            Err(ProcessorError::BufferTooSmall { .. })
+        ));
+
+        let mut frame_data = capture(&mut capturer, &buffers).unwrap();
+        assert_eq!(frame_data.get("source_timestamp"), 20);
+        assert_eq!(
+            buffer(&mut frame_data, "raw_frame_buffer"),
+            [20; FRAME_SIZE]
+        );
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn raw_frames_are_split_into_planes() {
+        let folder = folder("raw");
+        let path = folder.join("frames.yuv");
+
+        // Two frames of 4 bytes of luma and 2 of chroma, then a truncated one
+        fs::write(&path, [1, 1, 1, 1, 2, 2, 3, 3, 3, 3, 4, 4, 5, 5]).unwrap();
+
+        let buffers = [("y_channel_buffer", 4), ("cb_channel_buffer", 2)];
+        let mut capturer = FileFrameCapturer::raw(&path, &buffers).unwrap();
+
+        for (luma, chroma) in [(1, 2), (3, 4)] {
+            let mut frame_data = capture(&mut capturer, &buffers).unwrap();
+
+            assert_eq!(buffer(&mut frame_data, "y_channel_buffer"), [luma; 4]);
+            assert_eq!(buffer(&mut frame_data, "cb_channel_buffer"), [chroma; 2]);
+        }
+
+        assert!(matches!(
+            capture(&mut capturer, &buffers),
+            Err(ProcessorError::EndOfStream)
+        ));
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn y4m_planes_are_written_in_their_buffers() {
+        let folder = folder("y4m");
+        let path = folder.join("sequence.y4m");
+
+        let (width, height) = (4, 2);
+        let planes = [vec![16; width * height], vec![64; 2], vec![192; 2]];
+
+        let mut file = fs::File::create(&path).unwrap();
+        let mut encoder = y4m::encode(width, height, y4m::Ratio::new(30, 1))
+            .with_colorspace(y4m::Colorspace::C420jpeg)
+            .write_header(&mut file)
+            .unwrap();
+        encoder
+            .write_frame(&y4m::Frame::new([&planes[0], &planes[1], &planes[2]], None))
+            .unwrap();
+
+        let buffers = [
+            ("y_channel_buffer", width * height),
+            ("cb_channel_buffer", 2),
+            ("cr_channel_buffer", 2),
+        ];
+        let mut capturer = FileFrameCapturer::y4m(&path).unwrap().paced(false);
+        let mut frame_data = capture(&mut capturer, &buffers).unwrap();
+
+        for ((buffer_id, _), plane) in buffers.iter().zip(&planes) {
+            assert_eq!(&buffer(&mut frame_data, buffer_id), plane);
+        }
+
+        assert!(matches!(
+            capture(&mut capturer, &buffers),
+            Err(ProcessorError::EndOfStream)
+        ));
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn looping_sources_restart_from_the_first_frame() {
+        let folder = folder("looping");
+        let path = folder.join("frames.bgra");
+        fs::write(&path, [1, 1, 2, 2]).unwrap();
+
+        let buffers = [("raw_frame_buffer", 2)];
+        let mut capturer = FileFrameCapturer::raw(&path, &buffers)
+            .unwrap()
+            .looping(true);
+
+        for (index, value) in [1, 2, 1, 2, 1].into_iter().enumerate() {
+            let mut frame_data = capture(&mut capturer, &buffers).unwrap();
+
+            assert_eq!(frame_data.get("frame_index"), index as u128);
+            assert_eq!(buffer(&mut frame_data, "raw_frame_buffer"), [value; 2]);
+        }
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1 +1,8 @@
-pub mod scrap;
\ No newline at end of file
+pub mod file;
+pub mod test_pattern;
+
+#[cfg(feature = "scrap")]
+pub mod scrap;
+
+#[cfg(feature = "x11")]
+pub mod x11;
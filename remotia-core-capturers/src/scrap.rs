@@ -1,12 +1,84 @@
+use std::{io, time::Duration};
+
 use async_trait::async_trait;
 use log::debug;
-use remotia_core::{traits::FrameProcessor, types::FrameData};
+use rayon::{iter::IndexedParallelIterator, iter::ParallelIterator, slice::ParallelSliceMut};
+use remotia_core::{
+    error::{DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
 use scrap::{Capturer, Display};
+use tokio::time::Instant;
+
+pub const FRAME_WIDTH_STAT: &str = "frame_width";
+pub const FRAME_HEIGHT_STAT: &str = "frame_height";
+pub const FRAME_STRIDE_STAT: &str = "frame_stride";
+
+/// Area of the display to capture, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Copies a region of a BGRA frame into tightly packed rows, averaging blocks of
+/// `downscale`x`downscale` pixels. Rows are copied in parallel, as the capturer cannot be
+/// moved to a blocking task while it lends the frame.
+fn copy_region(frame: &[u8], stride: usize, region: Region, downscale: usize, output: &mut [u8]) {
+    let row_size = region.width / downscale * 4;
+
+    output
+        .par_chunks_exact_mut(row_size)
+        .take(region.height / downscale)
+        .enumerate()
+        .for_each(|(output_y, output_row)| {
+            let y = region.y + output_y * downscale;
+
+            if downscale == 1 {
+                let offset = y * stride + region.x * 4;
+                output_row.copy_from_slice(&frame[offset..offset + row_size]);
+                return;
+            }
+
+            for (output_x, pixel) in output_row.chunks_exact_mut(4).enumerate() {
+                let x = region.x + output_x * downscale;
+                let mut sums = [0usize; 4];
 
-use core::slice;
+                for row in frame[y * stride..].chunks(stride).take(downscale) {
+                    for source in row[x * 4..(x + downscale) * 4].chunks_exact(4) {
+                        for (sum, value) in sums.iter_mut().zip(source) {
+                            *sum += *value as usize;
+                        }
+                    }
+                }
 
+                for (value, sum) in pixel.iter_mut().zip(sums) {
+                    *value = (sum / (downscale * downscale)) as u8;
+                }
+            }
+        });
+}
+
+/// Captures the screen in the "raw_frame_buffer" through scrap.
+///
+/// The whole display is captured by default, but a region of it can be selected and
+/// downscaled. Frames are written as tightly packed BGRA rows, their size being stored in the
+/// "frame_width", "frame_height" and "frame_stride" stats.
+///
+/// When no new frame is ready, the capture is retried until a deadline, after which the frame
+/// is marked with [`DropReason::CaptureTimeout`]. The time spent waiting for a frame is stored
+/// in the "capture_latency" stat.
 pub struct ScrapFrameCapturer {
     capturer: Capturer,
+
+    region: Region,
+    downscale: usize,
+
+    timeout: Duration,
+    retry_interval: Duration,
 }
 
 // TODO: Evaluate a safer way to move the capturer to another thread
@@ -15,45 +87,265 @@ unsafe impl Send for ScrapFrameCapturer {}
 
 impl ScrapFrameCapturer {
     pub fn new(capturer: Capturer) -> Self {
-        Self { capturer }
+        let region = Region {
+            x: 0,
+            y: 0,
+            width: capturer.width(),
+            height: capturer.height(),
+        };
+
+        Self {
+            capturer,
+            region,
+            downscale: 1,
+            timeout: Duration::from_millis(100),
+            retry_interval: Duration::from_millis(1),
+        }
     }
 
     pub fn new_from_primary() -> Self {
         let display = Display::primary().expect("Couldn't find primary display.");
         let capturer = Capturer::new(display).expect("Couldn't begin capture.");
-        Self { capturer }
+        Self::new(capturer)
     }
 
+    /// Captures the display with the given index, in the order listed by the system
+    pub fn new_from_display(index: usize) -> io::Result<Self> {
+        let display = Display::all()?.into_iter().nth(index).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No display with index {}", index),
+            )
+        })?;
+
+        Ok(Self::new(Capturer::new(display)?))
+    }
+
+    /// Captures only the given area of the display, such as the one of a single window
+    pub fn region(mut self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "The region cannot be empty");
+        assert!(
+            x + width <= self.capturer.width() && y + height <= self.capturer.height(),
+            "The region exceeds the {}x{} display",
+            self.capturer.width(),
+            self.capturer.height()
+        );
+
+        self.region = Region {
+            x,
+            y,
+            width,
+            height,
+        };
+        self
+    }
+
+    /// Divides the size of the captured frames by an integer factor, averaging the pixels
+    pub fn downscale(mut self, factor: usize) -> Self {
+        assert!(
+            factor > 0 && factor <= self.region.width.min(self.region.height),
+            "Invalid downscaling factor {}",
+            factor
+        );
+
+        self.downscale = factor;
+        self
+    }
+
+    /// Maximum time to wait for a new frame
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Time to wait before trying again when no new frame is ready
+    pub fn retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Width of the captured frames, after the region selection and downscaling
     pub fn width(&self) -> usize {
-        self.capturer.width()
+        self.region.width / self.downscale
     }
 
+    /// Height of the captured frames, after the region selection and downscaling
     pub fn height(&self) -> usize {
-        self.capturer.height()
+        self.region.height / self.downscale
+    }
+
+    /// Size of the rows of the captured frames, in bytes
+    pub fn stride(&self) -> usize {
+        self.width() * 4
     }
 
-    fn capture_on_frame_data(&mut self, frame_data: &mut FrameData) {
+    /// Size of the captured frames, in bytes
+    pub fn frame_size(&self) -> usize {
+        self.stride() * self.height()
+    }
+
+    /// Copies the last frame into the buffer, returning `false` if no new frame is ready
+    fn try_capture(&mut self, raw_frame_buffer: &mut [u8]) -> Result<bool, ProcessorError> {
+        let required = self.frame_size();
+        if raw_frame_buffer.len() < required {
+            return Err(ProcessorError::BufferTooSmall {
+                key: "raw_frame_buffer".to_string(),
+                required,
+                found: raw_frame_buffer.len(),
+            });
+        }
+
+        let (region, downscale) = (self.region, self.downscale);
+        let display_height = self.capturer.height();
+
+        let frame = match self.capturer.frame() {
+            Ok(buffer) => buffer,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+            Err(error) => return Err(ProcessorError::Io(error)),
+        };
+
+        // Rows may be padded, depending on the platform
+        let stride = frame.len() / display_height;
+        copy_region(
+            &frame,
+            stride,
+            region,
+            downscale,
+            &mut raw_frame_buffer[..required],
+        );
+
+        Ok(true)
+    }
+
+    async fn capture_on_frame_data(
+        &mut self,
+        frame_data: &mut FrameData,
+    ) -> Result<(), ProcessorError> {
         debug!("Capturing...");
 
-        match self.capturer.frame() {
-            Ok(buffer) => {
-                let frame_slice = unsafe { slice::from_raw_parts(buffer.as_ptr(), buffer.len()) };
-                frame_data
-                    .get_writable_buffer_ref("raw_frame_buffer")
-                    .unwrap()
-                    .copy_from_slice(frame_slice);
-            }
-            Err(error) => {
-                panic!("Scrap capture error: {}", error);
+        let capture_start = Instant::now();
+        let deadline = capture_start + self.timeout;
+
+        let mut raw_frame_buffer = frame_data
+            .extract_writable_buffer("raw_frame_buffer")
+            .ok_or_else(|| ProcessorError::MissingBuffer("raw_frame_buffer".to_string()))?;
+
+        let result = loop {
+            match self.try_capture(&mut raw_frame_buffer) {
+                Ok(false) if Instant::now() < deadline => {
+                    tokio::time::sleep(self.retry_interval).await
+                }
+                result => break result,
             }
+        };
+
+        frame_data.insert_writable_buffer("raw_frame_buffer", raw_frame_buffer);
+
+        if !result? {
+            debug!("No new frame captured in {:?}", self.timeout);
+            frame_data.set_drop_reason(Some(DropReason::CaptureTimeout));
         }
+
+        frame_data.set_value("capture_latency", capture_start.elapsed());
+        frame_data.set(FRAME_WIDTH_STAT, self.width() as u128);
+        frame_data.set(FRAME_HEIGHT_STAT, self.height() as u128);
+        frame_data.set(FRAME_STRIDE_STAT, self.stride() as u128);
+
+        Ok(())
     }
 }
 
 #[async_trait]
-impl FrameProcessor for ScrapFrameCapturer {
-    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
-        self.capture_on_frame_data(&mut frame_data);
-        Some(frame_data)
+impl FallibleFrameProcessor for ScrapFrameCapturer {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        match self.capture_on_frame_data(&mut frame_data).await {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy_region, Region};
+
+    const WIDTH: usize = 6;
+    const HEIGHT: usize = 5;
+
+    /// Rows are padded with a marker which must never be copied
+    const STRIDE: usize = WIDTH * 4 + 8;
+    const PADDING: u8 = 0xEE;
+
+    /// BGRA frame whose pixels hold their own coordinates
+    fn frame() -> Vec<u8> {
+        let mut frame = vec![PADDING; STRIDE * HEIGHT];
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let offset = y * STRIDE + x * 4;
+                frame[offset..offset + 4].copy_from_slice(&[
+                    (x * 10) as u8,
+                    (y * 10) as u8,
+                    7,
+                    255,
+                ]);
+            }
+        }
+
+        frame
+    }
+
+    fn copy(region: Region, downscale: usize) -> Vec<u8> {
+        let (width, height) = (region.width / downscale, region.height / downscale);
+        let mut output = vec![0; width * height * 4];
+        copy_region(&frame(), STRIDE, region, downscale, &mut output);
+        output
+    }
+
+    #[test]
+    fn regions_are_copied_from_their_offset() {
+        let region = Region {
+            x: 2,
+            y: 1,
+            width: 3,
+            height: 2,
+        };
+
+        let expected: Vec<u8> = (1..3)
+            .flat_map(|y| (2..5).flat_map(move |x| [x * 10, y * 10, 7, 255]))
+            .collect();
+        assert_eq!(copy(region, 1), expected);
+    }
+
+    #[test]
+    fn whole_padded_frames_are_tightly_packed() {
+        let region = Region {
+            x: 0,
+            y: 0,
+            width: WIDTH,
+            height: HEIGHT,
+        };
+
+        let output = copy(region, 1);
+        assert_eq!(output.len(), WIDTH * HEIGHT * 4);
+        assert!(!output.contains(&PADDING));
+        assert_eq!(&output[output.len() - 4..], &[50, 40, 7, 255]);
+    }
+
+    #[test]
+    fn downscaling_averages_the_blocks() {
+        // The last column and row do not fill a block and are left out
+        let region = Region {
+            x: 1,
+            y: 0,
+            width: 5,
+            height: 5,
+        };
+
+        let expected: Vec<u8> = [(15, 5), (35, 5), (15, 25), (35, 25)]
+            .iter()
+            .flat_map(|&(b, g)| [b, g, 7, 255])
+            .collect();
+        assert_eq!(copy(region, 2), expected);
     }
 }
@@ -0,0 +1,373 @@
+use async_trait::async_trait;
+use log::debug;
+use remotia_core::{
+    error::ProcessorError,
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+/// Width and height of the glyph cells of [`TestPattern::ScrollingText`], in pixels
+const GLYPH_WIDTH: usize = 8;
+const GLYPH_HEIGHT: usize = 12;
+
+/// 75% color bars, as RGB
+const COLOR_BARS: [[u8; 3]; 8] = [
+    [191, 191, 191],
+    [191, 191, 0],
+    [0, 191, 191],
+    [0, 191, 0],
+    [191, 0, 191],
+    [191, 0, 0],
+    [0, 0, 191],
+    [0, 0, 0],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestPattern {
+    /// Vertical color bars, moving horizontally
+    ColorBars,
+
+    /// Smooth horizontal and vertical gradients, moving horizontally
+    Gradient,
+
+    /// Lines of glyphs imitating text on a plain background, scrolling upwards
+    ScrollingText,
+
+    /// Uniform noise, different on each frame
+    Noise,
+}
+
+/// SplitMix64 step, used to derive deterministic values from the frame coordinates
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9E3779B97F4A7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+    value ^ (value >> 31)
+}
+
+/// Generates deterministic BGRA frames in the "raw_frame_buffer", without any display. The
+/// index of each frame is stored in the "frame_index" stat and the frames starting a new
+/// scene are flagged with the "scene_cut" stat.
+///
+/// The content depends only on the configuration and the frame index, so that pipelines and
+/// codecs can be tested and benchmarked reproducibly. Scene cuts change the colors, the order
+/// of the bars or the seed of the pattern.
+pub struct TestPatternCapturer {
+    width: usize,
+    height: usize,
+
+    pattern: TestPattern,
+    motion: usize,
+    scene_cut_interval: Option<u64>,
+    seed: u64,
+
+    buffer_id: String,
+    frame_index: u64,
+}
+
+impl TestPatternCapturer {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "Frames cannot be empty");
+
+        Self {
+            width,
+            height,
+            pattern: TestPattern::ColorBars,
+            motion: 4,
+            scene_cut_interval: None,
+            seed: 0,
+            buffer_id: "raw_frame_buffer".to_string(),
+            frame_index: 0,
+        }
+    }
+
+    pub fn pattern(mut self, pattern: TestPattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Pixels by which the pattern moves on each frame, 0 producing a static pattern
+    pub fn motion(mut self, motion: usize) -> Self {
+        self.motion = motion;
+        self
+    }
+
+    /// Number of frames after which the scene changes
+    pub fn scene_cut_interval(mut self, interval: u64) -> Self {
+        self.scene_cut_interval = Some(interval);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn scene(&self) -> u64 {
+        match self.scene_cut_interval {
+            Some(interval) if interval > 0 => self.frame_index / interval,
+            _ => 0,
+        }
+    }
+
+    fn is_scene_cut(&self) -> bool {
+        match self.scene_cut_interval {
+            Some(interval) if interval > 0 => self.frame_index.is_multiple_of(interval),
+            _ => self.frame_index == 0,
+        }
+    }
+
+    /// Offset of the pattern due to the motion
+    fn offset(&self) -> usize {
+        (self.frame_index as usize).wrapping_mul(self.motion)
+    }
+
+    fn render(&self, frame: &mut [u8]) {
+        let scene = self.scene();
+        let offset = self.offset();
+        let scene_seed = mix(self.seed ^ mix(scene));
+
+        let rows = frame.chunks_exact_mut(self.width * 4).take(self.height);
+
+        match self.pattern {
+            TestPattern::ColorBars => {
+                let bar_width = self.width.div_ceil(COLOR_BARS.len()).max(1);
+
+                for row in rows {
+                    for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                        let bar = ((x + offset % self.width) % self.width) / bar_width;
+                        let [r, g, b] = COLOR_BARS[(bar + scene as usize) % COLOR_BARS.len()];
+                        pixel.copy_from_slice(&[b, g, r, 255]);
+                    }
+                }
+            }
+            TestPattern::Gradient => {
+                let base = (scene_seed & 0xFF) as u8;
+
+                for (y, row) in rows.enumerate() {
+                    let g = (y * 255 / self.height.max(1)) as u8;
+
+                    for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                        let r = ((x + offset % self.width) % self.width * 255 / self.width) as u8;
+                        pixel.copy_from_slice(&[base.wrapping_sub(r / 2), g, r, 255]);
+                    }
+                }
+            }
+            TestPattern::ScrollingText => {
+                let background = 160 + (scene_seed & 0x3F) as u8;
+                let foreground = ((scene_seed >> 8) & 0x3F) as u8;
+
+                for (y, row) in rows.enumerate() {
+                    // The text wraps around once the offset overflows
+                    let position = y.wrapping_add(offset);
+                    let line = position / GLYPH_HEIGHT;
+                    let glyph_row = position % GLYPH_HEIGHT;
+
+                    for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                        let glyph =
+                            mix(scene_seed ^ ((line as u64) << 32) ^ (x / GLYPH_WIDTH) as u64);
+                        let glyph_column = x % GLYPH_WIDTH;
+
+                        // Glyphs are 5x7 bitmaps in a cell with margins, one in six is a space
+                        let inked = !glyph.is_multiple_of(6)
+                            && (1..6).contains(&glyph_column)
+                            && (2..9).contains(&glyph_row)
+                            && (glyph >> (8 + (glyph_row - 2) * 5 + glyph_column - 1)) & 1 == 1;
+
+                        let value = if inked { foreground } else { background };
+                        pixel.copy_from_slice(&[value, value, value, 255]);
+                    }
+                }
+            }
+            TestPattern::Noise => {
+                let mut state = mix(scene_seed ^ self.frame_index);
+
+                for row in rows {
+                    for pixel in row.chunks_exact_mut(4) {
+                        state = mix(state);
+                        let [b, g, r, ..] = state.to_le_bytes();
+                        pixel.copy_from_slice(&[b, g, r, 255]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn capture_on_frame_data(&mut self, frame_data: &mut FrameData) -> Result<(), ProcessorError> {
+        debug!("Generating frame {}...", self.frame_index);
+
+        let required = self.width * self.height * 4;
+
+        let raw_frame_buffer = frame_data
+            .get_writable_buffer_ref(&self.buffer_id)
+            .ok_or_else(|| ProcessorError::MissingBuffer(self.buffer_id.clone()))?;
+
+        if raw_frame_buffer.len() < required {
+            return Err(ProcessorError::BufferTooSmall {
+                key: self.buffer_id.clone(),
+                required,
+                found: raw_frame_buffer.len(),
+            });
+        }
+
+        self.render(&mut raw_frame_buffer[..required]);
+
+        frame_data.set("frame_index", self.frame_index as u128);
+        frame_data.set_value("scene_cut", self.is_scene_cut());
+
+        self.frame_index += 1;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for TestPatternCapturer {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        match self.capture_on_frame_data(&mut frame_data) {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use remotia_core::types::FrameData;
+
+    use super::{TestPattern, TestPatternCapturer};
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 36;
+
+    const PATTERNS: [TestPattern; 4] = [
+        TestPattern::ColorBars,
+        TestPattern::Gradient,
+        TestPattern::ScrollingText,
+        TestPattern::Noise,
+    ];
+
+    fn capture(capturer: &mut TestPatternCapturer) -> FrameData {
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::zeroed(WIDTH * HEIGHT * 4));
+
+        capturer.capture_on_frame_data(&mut frame_data).unwrap();
+        frame_data
+    }
+
+    fn frame(frame_data: &mut FrameData) -> Vec<u8> {
+        frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn same_seed_and_index_produce_identical_frames() {
+        for pattern in PATTERNS {
+            let capturer = || {
+                TestPatternCapturer::new(WIDTH, HEIGHT)
+                    .pattern(pattern)
+                    .seed(42)
+                    .scene_cut_interval(2)
+            };
+            let (mut first, mut second) = (capturer(), capturer());
+
+            for index in 0..4 {
+                let mut first_frame = capture(&mut first);
+                let mut second_frame = capture(&mut second);
+
+                assert_eq!(first_frame.get("frame_index"), index);
+                assert_eq!(
+                    frame(&mut first_frame),
+                    frame(&mut second_frame),
+                    "{:?}, frame {}",
+                    pattern,
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn different_seeds_produce_different_frames() {
+        let capturer = |seed| {
+            TestPatternCapturer::new(WIDTH, HEIGHT)
+                .pattern(TestPattern::Noise)
+                .seed(seed)
+        };
+
+        let first = frame(&mut capture(&mut capturer(1)));
+        let second = frame(&mut capture(&mut capturer(2)));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn scene_cut_is_set_at_interval_boundaries() {
+        let mut capturer = TestPatternCapturer::new(WIDTH, HEIGHT).scene_cut_interval(5);
+
+        let scene_cuts: Vec<bool> = (0..12)
+            .map(|_| capture(&mut capturer).get_bool("scene_cut"))
+            .collect();
+        let expected: Vec<bool> = (0..12).map(|index| index % 5 == 0).collect();
+        assert_eq!(scene_cuts, expected);
+
+        let mut capturer = TestPatternCapturer::new(WIDTH, HEIGHT);
+        let scene_cuts: Vec<bool> = (0..3)
+            .map(|_| capture(&mut capturer).get_bool("scene_cut"))
+            .collect();
+        assert_eq!(scene_cuts, [true, false, false]);
+    }
+
+    #[test]
+    fn large_offsets_wrap_around() {
+        let capturer = |pattern, frame_index| {
+            let mut capturer = TestPatternCapturer::new(WIDTH, HEIGHT)
+                .pattern(pattern)
+                .motion(1);
+            capturer.frame_index = frame_index;
+            capturer
+        };
+
+        let last_index = u64::MAX - 2;
+
+        for pattern in PATTERNS {
+            let mut large = capturer(pattern, last_index);
+            let mut first_frame = capture(&mut large);
+            let mut second_frame = capture(&mut large);
+            assert_eq!(first_frame.get("frame_index"), last_index as u128);
+            assert_eq!(second_frame.get("frame_index"), last_index as u128 + 1);
+
+            let (first, second) = (frame(&mut first_frame), frame(&mut second_frame));
+            let row_size = WIDTH * 4;
+
+            match pattern {
+                // Horizontal motion repeats every frame width
+                TestPattern::ColorBars | TestPattern::Gradient => {
+                    let equivalent_index = (last_index as usize % WIDTH) as u64;
+                    let equivalent = frame(&mut capture(&mut capturer(pattern, equivalent_index)));
+                    assert_eq!(first, equivalent, "{:?}", pattern);
+                }
+                // Each row takes the place of the previous one, across the wrap as well
+                TestPattern::ScrollingText => {
+                    assert_eq!(first[row_size..], second[..second.len() - row_size]);
+                }
+                TestPattern::Noise => assert_ne!(first, second),
+            }
+        }
+    }
+}
@@ -0,0 +1,549 @@
+use std::{fmt::Display, io, ptr, slice};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use log::debug;
+use remotia_core::{
+    error::{DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        damage::{self, ConnectionExt as _},
+        shm::ConnectionExt as _,
+        xfixes::{self, ConnectionExt as _},
+        xproto::{ImageFormat, Rectangle, Window},
+    },
+    rust_connection::RustConnection,
+};
+
+/// Damaged rectangles of each frame, as four little endian u16 per rectangle: x, y, width and
+/// height
+pub const DAMAGE_BUFFER: &str = "damage_buffer";
+
+/// Cursor image as premultiplied BGRA pixels, only attached when the cursor shape changes, as
+/// flagged by the "cursor_shape_changed" stat. Consumers must keep the last image they received
+/// and reuse it for the frames in which the flag is false.
+pub const CURSOR_BUFFER: &str = "cursor_buffer";
+
+fn x11_error<E: Display>(error: E) -> io::Error {
+    io::Error::other(format!("X11 error: {}", error))
+}
+
+fn processor_error<E: Display>(error: E) -> ProcessorError {
+    ProcessorError::Other(format!("X11 error: {}", error))
+}
+
+/// System V shared memory segment attached to the X server, in which frames are captured
+struct SharedMemory {
+    segment: u32,
+    address: *mut u8,
+    size: usize,
+}
+
+impl SharedMemory {
+    fn attach(connection: &RustConnection, size: usize) -> io::Result<Self> {
+        let id = unsafe { libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600) };
+        if id < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let address = unsafe { libc::shmat(id, ptr::null(), 0) };
+
+        // The segment is destroyed as soon as both the server and the capturer detach from it
+        let attached = match address as isize {
+            -1 => Err(io::Error::last_os_error()),
+            _ => connection
+                .generate_id()
+                .map_err(x11_error)
+                .and_then(|segment| {
+                    connection
+                        .shm_attach(segment, id as u32, false)
+                        .map_err(x11_error)?
+                        .check()
+                        .map_err(x11_error)?;
+
+                    Ok(segment)
+                }),
+        };
+
+        unsafe { libc::shmctl(id, libc::IPC_RMID, ptr::null_mut()) };
+
+        match attached {
+            Ok(segment) => Ok(Self {
+                segment,
+                address: address as *mut u8,
+                size,
+            }),
+            Err(error) => {
+                if address as isize != -1 {
+                    unsafe { libc::shmdt(address) };
+                }
+
+                Err(error)
+            }
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.address, self.size) }
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        unsafe { libc::shmdt(self.address as *const libc::c_void) };
+    }
+}
+
+/// Captures the root window of an X11 display, such as a Xvfb one, through the MIT-SHM
+/// extension, writing BGRA frames in the "raw_frame_buffer".
+///
+/// Changes of the screen are tracked with the DAMAGE extension: the rectangles changed since
+/// the previous frame are written in the "damage_buffer" and counted in the "damaged_regions"
+/// stat, along with the "damaged_area_ratio" one. Frames without changes are flagged with the
+/// "static_frame" stat and can be marked with [`DropReason::StaticFrame`].
+///
+/// The cursor position is stored in the "cursor_x" and "cursor_y" stats, its hotspot in the
+/// "cursor_hot_x" and "cursor_hot_y" ones. Its image is only attached in the "cursor_buffer",
+/// along with the "cursor_width" and "cursor_height" stats, on the frames whose
+/// "cursor_shape_changed" stat is true: consumers must check it before looking for the buffer.
+pub struct X11FrameCapturer {
+    connection: RustConnection,
+    root: Window,
+    width: u16,
+    height: u16,
+
+    memory: SharedMemory,
+    damage: damage::Damage,
+    damaged_region: xfixes::Region,
+
+    drop_static_frames: bool,
+    capture_cursor: bool,
+
+    first_frame: bool,
+    cursor_serial: Option<u32>,
+}
+
+// The shared memory is only accessed through the capturer
+unsafe impl Send for X11FrameCapturer {}
+
+impl X11FrameCapturer {
+    /// Connects to the given display, or to the one in the DISPLAY environment variable
+    pub fn connect(display_name: Option<&str>) -> io::Result<Self> {
+        let (connection, screen_number) =
+            RustConnection::connect(display_name).map_err(x11_error)?;
+
+        let setup = connection.setup();
+        let screen = &setup.roots[screen_number];
+        let (root, width, height) = (screen.root, screen.width_in_pixels, screen.height_in_pixels);
+
+        let bits_per_pixel = setup
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == screen.root_depth)
+            .map(|format| format.bits_per_pixel);
+
+        if bits_per_pixel != Some(32) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Unsupported root depth {}", screen.root_depth),
+            ));
+        }
+
+        connection
+            .shm_query_version()
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        connection
+            .xfixes_query_version(5, 0)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        connection
+            .damage_query_version(1, 1)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+
+        let memory = SharedMemory::attach(&connection, width as usize * height as usize * 4)?;
+
+        let damage = connection.generate_id().map_err(x11_error)?;
+        connection
+            .damage_create(damage, root, damage::ReportLevel::NON_EMPTY)
+            .map_err(x11_error)?;
+
+        let damaged_region = connection.generate_id().map_err(x11_error)?;
+        connection
+            .xfixes_create_region(damaged_region, &[])
+            .map_err(x11_error)?
+            .check()
+            .map_err(x11_error)?;
+
+        debug!("Connected to a {}x{} X11 screen", width, height);
+
+        Ok(Self {
+            connection,
+            root,
+            width,
+            height,
+            memory,
+            damage,
+            damaged_region,
+            drop_static_frames: false,
+            capture_cursor: true,
+            first_frame: true,
+            cursor_serial: None,
+        })
+    }
+
+    /// Marks the frames without any damage with [`DropReason::StaticFrame`]
+    pub fn drop_static_frames(mut self, drop: bool) -> Self {
+        self.drop_static_frames = drop;
+        self
+    }
+
+    /// Whether the cursor position and image are reported
+    pub fn capture_cursor(mut self, capture: bool) -> Self {
+        self.capture_cursor = capture;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.height as usize
+    }
+
+    /// Rectangles damaged since the previous call, the whole screen on the first frame
+    fn damaged_rectangles(&mut self) -> Result<Vec<Rectangle>, ProcessorError> {
+        // Damage notifications are not needed, as the damaged region is fetched on each frame
+        while self
+            .connection
+            .poll_for_event()
+            .map_err(processor_error)?
+            .is_some()
+        {}
+
+        self.connection
+            .damage_subtract(self.damage, x11rb::NONE, self.damaged_region)
+            .map_err(processor_error)?;
+
+        let rectangles = self
+            .connection
+            .xfixes_fetch_region(self.damaged_region)
+            .map_err(processor_error)?
+            .reply()
+            .map_err(processor_error)?
+            .rectangles;
+
+        if self.first_frame {
+            self.first_frame = false;
+
+            return Ok(vec![Rectangle {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+            }]);
+        }
+
+        Ok(rectangles)
+    }
+
+    fn capture_image(&mut self) -> Result<(), ProcessorError> {
+        self.connection
+            .shm_get_image(
+                self.root,
+                0,
+                0,
+                self.width,
+                self.height,
+                !0,
+                ImageFormat::Z_PIXMAP.into(),
+                self.memory.segment,
+                0,
+            )
+            .map_err(processor_error)?
+            .reply()
+            .map_err(processor_error)?;
+
+        Ok(())
+    }
+
+    fn capture_cursor_on_frame_data(
+        &mut self,
+        frame_data: &mut FrameData,
+    ) -> Result<(), ProcessorError> {
+        let cursor = self
+            .connection
+            .xfixes_get_cursor_image()
+            .map_err(processor_error)?
+            .reply()
+            .map_err(processor_error)?;
+
+        frame_data.set_value("cursor_x", cursor.x as i128);
+        frame_data.set_value("cursor_y", cursor.y as i128);
+        frame_data.set("cursor_hot_x", cursor.xhot as u128);
+        frame_data.set("cursor_hot_y", cursor.yhot as u128);
+
+        let shape_changed = self.cursor_serial != Some(cursor.cursor_serial);
+        frame_data.set_value("cursor_shape_changed", shape_changed);
+
+        if shape_changed {
+            self.cursor_serial = Some(cursor.cursor_serial);
+
+            // ARGB pixels, stored as BGRA bytes
+            let image: Vec<u8> = cursor
+                .cursor_image
+                .iter()
+                .flat_map(|pixel| pixel.to_le_bytes())
+                .collect();
+
+            frame_data.set("cursor_width", cursor.width as u128);
+            frame_data.set("cursor_height", cursor.height as u128);
+            frame_data.insert_readonly_buffer(CURSOR_BUFFER, Bytes::from(image));
+        }
+
+        Ok(())
+    }
+
+    fn capture_on_frame_data(&mut self, frame_data: &mut FrameData) -> Result<(), ProcessorError> {
+        debug!("Capturing...");
+
+        let rectangles = self.damaged_rectangles()?;
+
+        let damaged_area: usize = rectangles
+            .iter()
+            .map(|rectangle| rectangle.width as usize * rectangle.height as usize)
+            .sum();
+        let damage: Vec<u8> = rectangles
+            .iter()
+            .flat_map(|rectangle| {
+                [
+                    rectangle.x as u16,
+                    rectangle.y as u16,
+                    rectangle.width,
+                    rectangle.height,
+                ]
+            })
+            .flat_map(u16::to_le_bytes)
+            .collect();
+
+        let static_frame = rectangles.is_empty();
+
+        frame_data.set("damaged_regions", rectangles.len() as u128);
+        let screen_area = self.width as usize * self.height as usize;
+        frame_data.set_value(
+            "damaged_area_ratio",
+            damaged_area as f64 / screen_area as f64,
+        );
+        frame_data.set_value("static_frame", static_frame);
+        frame_data.insert_readonly_buffer(DAMAGE_BUFFER, Bytes::from(damage));
+
+        if self.capture_cursor {
+            self.capture_cursor_on_frame_data(frame_data)?;
+        }
+
+        if static_frame && self.drop_static_frames {
+            debug!("Static frame");
+            frame_data.set_drop_reason(Some(DropReason::StaticFrame));
+            return Ok(());
+        }
+
+        // The shared memory keeps the previous frame, which is still valid without damage
+        if !static_frame {
+            self.capture_image()?;
+        }
+
+        let raw_frame_buffer = frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .ok_or_else(|| ProcessorError::MissingBuffer("raw_frame_buffer".to_string()))?;
+
+        if raw_frame_buffer.len() < self.memory.size {
+            return Err(ProcessorError::BufferTooSmall {
+                key: "raw_frame_buffer".to_string(),
+                required: self.memory.size,
+                found: raw_frame_buffer.len(),
+            });
+        }
+
+        raw_frame_buffer[..self.memory.size].copy_from_slice(self.memory.as_slice());
+
+        Ok(())
+    }
+}
+
+impl Drop for X11FrameCapturer {
+    fn drop(&mut self) {
+        let _ = self.connection.damage_destroy(self.damage);
+        let _ = self.connection.xfixes_destroy_region(self.damaged_region);
+        let _ = self.connection.shm_detach(self.memory.segment);
+        let _ = self.connection.flush();
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for X11FrameCapturer {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        match self.capture_on_frame_data(&mut frame_data) {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        process::{Child, Command},
+        thread,
+        time::Duration,
+    };
+
+    use bytes::BytesMut;
+    use remotia_core::types::FrameData;
+    use x11rb::{
+        connection::Connection,
+        protocol::xproto::{ConnectionExt as _, CreateGCAux, Rectangle},
+        rust_connection::RustConnection,
+        wrapper::ConnectionExt as _,
+    };
+
+    use super::{X11FrameCapturer, CURSOR_BUFFER, DAMAGE_BUFFER};
+
+    const DISPLAY: &str = ":97";
+    const WIDTH: u16 = 64;
+    const HEIGHT: u16 = 48;
+
+    /// Xvfb server, killed when dropped
+    struct Xvfb(Child);
+
+    impl Xvfb {
+        fn start() -> Self {
+            let xvfb = Self(
+                Command::new("Xvfb")
+                    .args([DISPLAY, "-screen", "0", &format!("{}x{}x24", WIDTH, HEIGHT)])
+                    .spawn()
+                    .expect("Xvfb must be installed"),
+            );
+
+            // Wait for the server to accept connections
+            for _ in 0..50 {
+                if RustConnection::connect(Some(DISPLAY)).is_ok() {
+                    return xvfb;
+                }
+
+                thread::sleep(Duration::from_millis(100));
+            }
+
+            panic!("Xvfb did not start");
+        }
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn capture(capturer: &mut X11FrameCapturer) -> FrameData {
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer(
+            "raw_frame_buffer",
+            BytesMut::zeroed(WIDTH as usize * HEIGHT as usize * 4),
+        );
+
+        capturer.capture_on_frame_data(&mut frame_data).unwrap();
+        frame_data
+    }
+
+    fn damaged_rectangles(frame_data: &mut FrameData) -> Vec<[u16; 4]> {
+        frame_data
+            .get_readonly_buffer_ref(DAMAGE_BUFFER)
+            .chunks_exact(8)
+            .map(|rectangle| {
+                let value = |i: usize| u16::from_le_bytes([rectangle[i], rectangle[i + 1]]);
+                [value(0), value(2), value(4), value(6)]
+            })
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "requires Xvfb"]
+    fn damage_and_cursor_are_reported() {
+        let _xvfb = Xvfb::start();
+        let mut capturer = X11FrameCapturer::connect(Some(DISPLAY)).unwrap();
+
+        // The whole screen is damaged on the first frame, which carries the cursor image
+        let mut frame_data = capture(&mut capturer);
+        assert_eq!(damaged_rectangles(&mut frame_data), [[0, 0, WIDTH, HEIGHT]]);
+        assert!(!frame_data.get_bool("static_frame"));
+        assert!(frame_data.get_bool("cursor_shape_changed"));
+        assert!(frame_data.has_readonly_buffer(CURSOR_BUFFER));
+
+        let (cursor_width, cursor_height) = (
+            frame_data.get("cursor_width"),
+            frame_data.get("cursor_height"),
+        );
+        assert_eq!(
+            frame_data.get_readonly_buffer_ref(CURSOR_BUFFER).len() as u128,
+            cursor_width * cursor_height * 4
+        );
+
+        // Nothing changed, and the cursor image is not attached again
+        let mut frame_data = capture(&mut capturer);
+        assert!(damaged_rectangles(&mut frame_data).is_empty());
+        assert!(frame_data.get_bool("static_frame"));
+        assert!(!frame_data.get_bool("cursor_shape_changed"));
+        assert!(!frame_data.has_readonly_buffer(CURSOR_BUFFER));
+
+        // Draw a white rectangle on the root window and move the pointer
+        let (connection, screen_number) = RustConnection::connect(Some(DISPLAY)).unwrap();
+        let screen = &connection.setup().roots[screen_number];
+        let gc = connection.generate_id().unwrap();
+        connection
+            .create_gc(
+                gc,
+                screen.root,
+                &CreateGCAux::new().foreground(screen.white_pixel),
+            )
+            .unwrap();
+        let rectangle = Rectangle {
+            x: 8,
+            y: 4,
+            width: 16,
+            height: 12,
+        };
+        connection
+            .poly_fill_rectangle(screen.root, gc, &[rectangle])
+            .unwrap();
+        connection
+            .warp_pointer(x11rb::NONE, screen.root, 0, 0, 0, 0, 20, 30)
+            .unwrap();
+        connection.sync().unwrap();
+
+        let mut frame_data = capture(&mut capturer);
+        // The software cursor of Xvfb may damage other areas when the pointer moves
+        let rectangles = damaged_rectangles(&mut frame_data);
+        assert!(rectangles.iter().any(|&[x, y, width, height]| {
+            x <= 8 && y <= 4 && x + width >= 24 && y + height >= 16
+        }));
+        assert_eq!(frame_data.get("damaged_regions"), rectangles.len() as u128);
+        assert!(!frame_data.get_bool("static_frame"));
+        assert_eq!(frame_data.get_signed("cursor_x"), 20);
+        assert_eq!(frame_data.get_signed("cursor_y"), 30);
+
+        let frame = frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap();
+        let pixel = (4 * WIDTH as usize + 8) * 4;
+        assert_eq!(frame[pixel..pixel + 3], [0xFF; 3]);
+    }
+}
@@ -0,0 +1,119 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::prelude::*;
+use remotia_core_codecs::{
+    color::ColorSpace,
+    pixel_format::{FrameFormat, PixelFormat},
+    yuv420p::{decoder::yuv_separate_to_bgra, encoder::bgra_to_yuv_separate},
+};
+
+const RESOLUTIONS: [(usize, usize); 2] = [(1280, 720), (1920, 1080)];
+
+/// Previous encoder, converting each pixel in parallel and accumulating the chroma afterwards
+fn rayon_bgra_to_yuv(bgra: &[u8], y: &mut [u8], u: &mut [u8], v: &mut [u8]) {
+    let yuv_pixels = (0..bgra.len() / 4)
+        .into_par_iter()
+        .map(|i| {
+            let (b, g, r) = (
+                bgra[i * 4] as f32,
+                bgra[i * 4 + 1] as f32,
+                bgra[i * 4 + 2] as f32,
+            );
+
+            let luma = r * 0.29900 + g * 0.58700 + b * 0.11400;
+            let cb = (r * -0.16874 + g * -0.33126 + b * 0.50000) + 128.0;
+            let cr = (r * 0.50000 + g * -0.41869 + b * -0.08131) + 128.0;
+
+            (i, (luma, cb, cr))
+        })
+        .collect::<Vec<(usize, (f32, f32, f32))>>();
+
+    // The chroma planes used to be freshly allocated for each frame
+    u.fill(0);
+    v.fill(0);
+
+    yuv_pixels.into_iter().for_each(|(i, (luma, cb, cr))| {
+        y[i] = luma as u8;
+        u[i / 4] += (cb * 0.25) as u8;
+        v[i / 4] += (cr * 0.25) as u8;
+    });
+}
+
+/// Previous decoder, converting each pixel in parallel
+fn rayon_yuv_to_bgra(y: &[u8], u: &[u8], v: &[u8], bgra: &mut [u8]) {
+    bgra.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+        let luma = y[i] as f32;
+        let cb = u[i / 4] as f32 - 128.0;
+        let cr = v[i / 4] as f32 - 128.0;
+
+        pixel[0] = (luma + cb * 1.77200).round().clamp(0.0, 255.0) as u8;
+        pixel[1] = (luma + cb * -0.34414 + cr * -0.71414)
+            .round()
+            .clamp(0.0, 255.0) as u8;
+        pixel[2] = (luma + cr * 1.40200).round().clamp(0.0, 255.0) as u8;
+        pixel[3] = 255;
+    });
+}
+
+/// BGRA frame, then the Y, Cb and Cr planes
+fn frame_buffers(bgra: &FrameFormat, i420: &FrameFormat) -> Vec<Vec<u8>> {
+    let packed_size = bgra.planes()[0].size();
+    let mut buffers = vec![(0..packed_size).map(|i| (i * 7 % 251) as u8).collect()];
+    buffers.extend(i420.planes().iter().map(|plane| vec![0; plane.size()]));
+    buffers
+}
+
+fn bgra_to_yuv(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bgra_to_yuv420p");
+    let color_space = ColorSpace::default();
+
+    for (width, height) in RESOLUTIONS {
+        let bgra_format = FrameFormat::new(PixelFormat::BGRA, width, height);
+        let i420_format = FrameFormat::new(PixelFormat::I420, width, height);
+        let [bgra, y, u, v] = &mut frame_buffers(&bgra_format, &i420_format)[..] else {
+            unreachable!()
+        };
+
+        let resolution = format!("{}x{}", width, height);
+        group.throughput(Throughput::Elements((width * height) as u64));
+
+        group.bench_function(BenchmarkId::new("fixed_point", &resolution), |b| {
+            b.iter(|| bgra_to_yuv_separate(&bgra_format, &i420_format, &color_space, bgra, y, u, v))
+        });
+
+        group.bench_function(BenchmarkId::new("rayon", &resolution), |b| {
+            b.iter(|| rayon_bgra_to_yuv(bgra, y, u, v))
+        });
+    }
+
+    group.finish();
+}
+
+fn yuv_to_bgra(c: &mut Criterion) {
+    let mut group = c.benchmark_group("yuv420p_to_bgra");
+    let color_space = ColorSpace::default();
+
+    for (width, height) in RESOLUTIONS {
+        let bgra_format = FrameFormat::new(PixelFormat::BGRA, width, height);
+        let i420_format = FrameFormat::new(PixelFormat::I420, width, height);
+        let [bgra, y, u, v] = &mut frame_buffers(&bgra_format, &i420_format)[..] else {
+            unreachable!()
+        };
+        bgra_to_yuv_separate(&bgra_format, &i420_format, &color_space, bgra, y, u, v);
+
+        let resolution = format!("{}x{}", width, height);
+        group.throughput(Throughput::Elements((width * height) as u64));
+
+        group.bench_function(BenchmarkId::new("fixed_point", &resolution), |b| {
+            b.iter(|| yuv_separate_to_bgra(&i420_format, &bgra_format, &color_space, y, u, v, bgra))
+        });
+
+        group.bench_function(BenchmarkId::new("rayon", &resolution), |b| {
+            b.iter(|| rayon_yuv_to_bgra(y, u, v, bgra))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bgra_to_yuv, yuv_to_bgra);
+criterion_main!(benches);
@@ -0,0 +1,784 @@
+use std::{
+    collections::VecDeque,
+    ffi::{c_int, OsStr},
+    ptr,
+};
+
+use libloading::Library;
+use rav1e::{
+    color::{
+        ChromaSampling, ColorDescription, ColorPrimaries, MatrixCoefficients, PixelRange,
+        TransferCharacteristics,
+    },
+    prelude::{
+        Config, Context, EncoderConfig, EncoderStatus, FrameParameters, FrameType,
+        FrameTypeOverride, Packet, Rational,
+    },
+};
+use remotia_core::error::CodecError;
+
+use crate::{
+    color::{ColorMatrix, ColorRange, ColorSpace},
+    pixel_format::{FrameFormat, PixelFormat},
+};
+
+use super::{library, Decoder, EncodedFrame, Encoder};
+
+/// Relative bitrate changes below this one are ignored, not to restart the encoder for them
+const BITRATE_TOLERANCE: f64 = 0.1;
+
+/// Settings of an [`AV1Encoder`], tuned for real-time streaming by default: low latency mode
+/// without frame reordering or lookahead, and the fastest speed preset
+pub struct AV1EncoderConfig {
+    format: FrameFormat,
+    color_space: ColorSpace,
+
+    speed: u8,
+    bitrate: Option<i32>,
+    quantizer: Option<usize>,
+    frame_rate: u64,
+    key_frame_interval: u64,
+    tiles: usize,
+    tile_grid: (usize, usize),
+    threads: usize,
+}
+
+impl AV1EncoderConfig {
+    pub fn new(format: FrameFormat) -> Self {
+        Self {
+            format,
+            color_space: ColorSpace::default(),
+            speed: 10,
+            bitrate: None,
+            quantizer: None,
+            frame_rate: 60,
+            key_frame_interval: 240,
+            tiles: 0,
+            tile_grid: (0, 0),
+            threads: 0,
+        }
+    }
+
+    /// Color space of the input frames, signalled in the bitstream
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Speed preset, from 0 (best quality) to 10 (fastest)
+    pub fn speed(mut self, speed: u8) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Target bitrate in bits per second, enabling rate control
+    pub fn bitrate(mut self, bits_per_second: i32) -> Self {
+        self.bitrate = Some(bits_per_second);
+        self
+    }
+
+    /// Base quantizer (0-255), or the maximum one when a bitrate is set
+    pub fn quantizer(mut self, quantizer: usize) -> Self {
+        self.quantizer = Some(quantizer);
+        self
+    }
+
+    pub fn frame_rate(mut self, frame_rate: u64) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    /// Maximum number of frames between two keyframes
+    pub fn key_frame_interval(mut self, interval: u64) -> Self {
+        self.key_frame_interval = interval;
+        self
+    }
+
+    /// Number of tiles in which each frame is split to be encoded in parallel, 0 letting the
+    /// encoder choose
+    pub fn tiles(mut self, tiles: usize) -> Self {
+        self.tiles = tiles;
+        self
+    }
+
+    /// Explicit tile grid, as the log2 of the number of tile columns and rows
+    pub fn tile_grid(mut self, log2_columns: usize, log2_rows: usize) -> Self {
+        self.tile_grid = (log2_columns, log2_rows);
+        self
+    }
+
+    /// Size of the encoder thread pool, 0 using the global rayon pool
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    fn chroma_sampling(&self) -> Result<ChromaSampling, CodecError> {
+        match self.format.get_pixel_format() {
+            PixelFormat::I420 => Ok(ChromaSampling::Cs420),
+            PixelFormat::I444 => Ok(ChromaSampling::Cs444),
+            pixel_format => Err(CodecError::InvalidConfiguration(format!(
+                "rav1e encodes I420 or I444 frames, not {:?}",
+                pixel_format
+            ))),
+        }
+    }
+
+    fn color_description(&self) -> ColorDescription {
+        let (color_primaries, transfer_characteristics, matrix_coefficients) =
+            match self.color_space.matrix {
+                ColorMatrix::BT601 => (
+                    ColorPrimaries::BT601,
+                    TransferCharacteristics::BT601,
+                    MatrixCoefficients::BT601,
+                ),
+                ColorMatrix::BT709 => (
+                    ColorPrimaries::BT709,
+                    TransferCharacteristics::BT709,
+                    MatrixCoefficients::BT709,
+                ),
+                ColorMatrix::BT2020 => (
+                    ColorPrimaries::BT2020,
+                    TransferCharacteristics::BT2020_10Bit,
+                    MatrixCoefficients::BT2020NCL,
+                ),
+            };
+
+        ColorDescription {
+            color_primaries,
+            transfer_characteristics,
+            matrix_coefficients,
+        }
+    }
+
+    pub fn build(self) -> Result<AV1Encoder, CodecError> {
+        let mut encoder_config = EncoderConfig::with_speed_preset(self.speed);
+
+        encoder_config.width = self.format.get_width();
+        encoder_config.height = self.format.get_height();
+        encoder_config.time_base = Rational::new(1, self.frame_rate);
+        encoder_config.chroma_sampling = self.chroma_sampling()?;
+        encoder_config.pixel_range = match self.color_space.range {
+            ColorRange::Full => PixelRange::Full,
+            ColorRange::Limited => PixelRange::Limited,
+        };
+        encoder_config.color_description = Some(self.color_description());
+
+        encoder_config.low_latency = true;
+        encoder_config.speed_settings.rdo_lookahead_frames = 1;
+        encoder_config.max_key_frame_interval = self.key_frame_interval;
+        encoder_config.min_key_frame_interval = self.key_frame_interval.min(12);
+        encoder_config.tiles = self.tiles;
+        (encoder_config.tile_cols, encoder_config.tile_rows) = self.tile_grid;
+
+        if let Some(bitrate) = self.bitrate {
+            encoder_config.bitrate = bitrate;
+        }
+
+        if let Some(quantizer) = self.quantizer {
+            encoder_config.quantizer = quantizer;
+        }
+
+        Ok(AV1Encoder {
+            context: new_context(&encoder_config, self.threads)?,
+            format: self.format,
+            encoder_config,
+            threads: self.threads,
+            encoded_frames: VecDeque::new(),
+            force_keyframe: false,
+            pending_bitrate: None,
+            frames_since_keyframe: 0,
+        })
+    }
+}
+
+fn new_context(encoder_config: &EncoderConfig, threads: usize) -> Result<Context<u8>, CodecError> {
+    Config::new()
+        .with_encoder_config(encoder_config.clone())
+        .with_threads(threads)
+        .new_context()
+        .map_err(|error| CodecError::InvalidConfiguration(error.to_string()))
+}
+
+/// AV1 software encoder based on rav1e.
+///
+/// The bitrate of rav1e cannot change while encoding, so the encoder is restarted with the new
+/// one at the next keyframe, either forced or due to the keyframe interval, which keeps the
+/// stream from getting additional keyframes. Changes smaller than 10% are ignored.
+pub struct AV1Encoder {
+    format: FrameFormat,
+
+    encoder_config: EncoderConfig,
+    threads: usize,
+    context: Context<u8>,
+
+    encoded_frames: VecDeque<EncodedFrame>,
+    force_keyframe: bool,
+
+    pending_bitrate: Option<i32>,
+    frames_since_keyframe: u64,
+}
+
+impl AV1Encoder {
+    fn encoded_frame(packet: Packet<u8>) -> EncodedFrame {
+        EncodedFrame::new(packet.data, packet.frame_type == FrameType::KEY).qp(packet.qp as u32)
+    }
+
+    /// Queues the packets produced so far by the encoder
+    fn receive_packets(&mut self) -> Result<(), CodecError> {
+        loop {
+            match self.context.receive_packet() {
+                Ok(packet) => self.encoded_frames.push_back(Self::encoded_frame(packet)),
+                Err(EncoderStatus::Encoded) => continue,
+                Err(EncoderStatus::NeedMoreData | EncoderStatus::LimitReached) => return Ok(()),
+                Err(status) => return Err(CodecError::EncodingFailed(status.to_string())),
+            }
+        }
+    }
+
+    /// Replaces the encoder with one using the given bitrate, releasing the frames still in
+    /// the previous one
+    fn restart(&mut self, bitrate: i32) -> Result<(), CodecError> {
+        let mut encoder_config = self.encoder_config.clone();
+        encoder_config.bitrate = bitrate;
+
+        let context = new_context(&encoder_config, self.threads)?;
+
+        self.context.flush();
+        self.receive_packets()?;
+
+        self.context = context;
+        self.encoder_config = encoder_config;
+
+        Ok(())
+    }
+}
+
+impl Encoder for AV1Encoder {
+    fn input_format(&self) -> &FrameFormat {
+        &self.format
+    }
+
+    fn encode(&mut self, planes: &[&[u8]]) -> Result<Option<EncodedFrame>, CodecError> {
+        if self.force_keyframe
+            || self.frames_since_keyframe >= self.encoder_config.max_key_frame_interval
+        {
+            // A restarted encoder begins with a keyframe on its own
+            if let Some(bitrate) = self.pending_bitrate.take() {
+                self.restart(bitrate)?;
+                self.force_keyframe = false;
+            }
+
+            self.frames_since_keyframe = 0;
+        }
+        self.frames_since_keyframe += 1;
+
+        let mut frame = self.context.new_frame();
+
+        for ((plane, source), layout) in frame
+            .planes
+            .iter_mut()
+            .zip(planes)
+            .zip(self.format.planes())
+        {
+            plane.copy_from_raw_u8(source, layout.stride, 1);
+        }
+
+        let parameters = FrameParameters {
+            frame_type_override: match self.force_keyframe {
+                true => FrameTypeOverride::Key,
+                false => FrameTypeOverride::No,
+            },
+            ..Default::default()
+        };
+        self.force_keyframe = false;
+
+        self.context
+            .send_frame((frame, parameters))
+            .map_err(|status| CodecError::EncodingFailed(status.to_string()))?;
+
+        self.receive_packets()?;
+        Ok(self.encoded_frames.pop_front())
+    }
+
+    fn flush(&mut self) -> Result<Vec<EncodedFrame>, CodecError> {
+        self.context.flush();
+        self.receive_packets()?;
+
+        Ok(self.encoded_frames.drain(..).collect())
+    }
+
+    fn force_keyframe(&mut self) {
+        self.force_keyframe = true;
+    }
+
+    fn set_bitrate(&mut self, bits_per_second: u32) -> Result<(), CodecError> {
+        let bitrate = bits_per_second.min(i32::MAX as u32) as i32;
+        let current = self.encoder_config.bitrate;
+
+        let change = (bitrate as f64 - current as f64).abs() / current as f64;
+        if current > 0 && change < BITRATE_TOLERANCE {
+            self.pending_bitrate = None;
+            return Ok(());
+        }
+
+        // Validate the configuration now, to report errors to the caller
+        let mut encoder_config = self.encoder_config.clone();
+        encoder_config.bitrate = bitrate;
+        Config::new()
+            .with_encoder_config(encoder_config)
+            .validate()
+            .map_err(|error| CodecError::InvalidConfiguration(error.to_string()))?;
+
+        self.pending_bitrate = Some(bitrate);
+
+        Ok(())
+    }
+}
+
+/// Subset of the dav1d 1.x C API, as declared in dav1d.h, data.h and picture.h. The trailing
+/// fields of the structs, which vary between versions, are only reserved.
+mod dav1d {
+    use std::ffi::{c_int, c_void};
+
+    pub const PIXEL_LAYOUT_I420: c_int = 1;
+    pub const PIXEL_LAYOUT_I444: c_int = 3;
+
+    /// Negated errno code asking to retrieve pictures before sending more data
+    pub const EAGAIN: c_int = if cfg!(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    )) {
+        -35
+    } else {
+        -11
+    };
+
+    #[repr(C)]
+    pub struct Settings {
+        pub n_threads: c_int,
+        pub max_frame_delay: c_int,
+        pub reserved: [u64; 32],
+    }
+
+    #[repr(C)]
+    pub struct UserData {
+        pub data: *const u8,
+        pub reference: *mut c_void,
+    }
+
+    #[repr(C)]
+    pub struct DataProps {
+        pub timestamp: i64,
+        pub duration: i64,
+        pub offset: i64,
+        pub size: usize,
+        pub user_data: UserData,
+    }
+
+    #[repr(C)]
+    pub struct Data {
+        pub data: *const u8,
+        pub size: usize,
+        pub reference: *mut c_void,
+        pub props: DataProps,
+    }
+
+    #[repr(C)]
+    pub struct PictureParameters {
+        pub width: c_int,
+        pub height: c_int,
+        pub layout: c_int,
+        pub bits_per_component: c_int,
+    }
+
+    #[repr(C)]
+    pub struct Picture {
+        pub sequence_header: *mut c_void,
+        pub frame_header: *mut c_void,
+        pub data: [*mut u8; 3],
+        pub stride: [isize; 2],
+        pub parameters: PictureParameters,
+        pub reserved: [u64; 64],
+    }
+
+    pub type Context = c_void;
+
+    pub type DefaultSettings = unsafe extern "C" fn(*mut Settings);
+    pub type Open = unsafe extern "C" fn(*mut *mut Context, *const Settings) -> c_int;
+    pub type Close = unsafe extern "C" fn(*mut *mut Context);
+    pub type SendData = unsafe extern "C" fn(*mut Context, *mut Data) -> c_int;
+    pub type GetPicture = unsafe extern "C" fn(*mut Context, *mut Picture) -> c_int;
+    pub type PictureUnref = unsafe extern "C" fn(*mut Picture);
+    pub type DataCreate = unsafe extern "C" fn(*mut Data, usize) -> *mut u8;
+    pub type DataUnref = unsafe extern "C" fn(*mut Data);
+}
+
+/// Names under which the dav1d library is looked up when no path is configured
+const DAV1D_LIBRARY_NAMES: &[&str] = if cfg!(target_os = "windows") {
+    &["dav1d.dll"]
+} else if cfg!(target_os = "macos") {
+    &["libdav1d.dylib", "libdav1d.7.dylib", "libdav1d.6.dylib"]
+} else {
+    &["libdav1d.so", "libdav1d.so.7", "libdav1d.so.6"]
+};
+
+/// Picture returned by dav1d, released when dropped
+struct DecodedPicture {
+    picture: dav1d::Picture,
+    unref: dav1d::PictureUnref,
+}
+
+impl Drop for DecodedPicture {
+    fn drop(&mut self) {
+        // SAFETY: the picture was returned by the decoder and is not used afterwards
+        unsafe { (self.unref)(&mut self.picture) };
+    }
+}
+
+/// AV1 software decoder based on a runtime-loaded dav1d library, configured for low latency:
+/// each frame is output as soon as its data is received.
+pub struct AV1Decoder {
+    format: FrameFormat,
+    layout: c_int,
+
+    _library: Library,
+    close: dav1d::Close,
+    send_data: dav1d::SendData,
+    get_picture: dav1d::GetPicture,
+    picture_unref: dav1d::PictureUnref,
+    data_create: dav1d::DataCreate,
+    data_unref: dav1d::DataUnref,
+    context: *mut dav1d::Context,
+}
+
+// SAFETY: the decoder is only accessed through exclusive references
+unsafe impl Send for AV1Decoder {}
+
+impl AV1Decoder {
+    /// Loads the library from the default locations
+    pub fn new(format: FrameFormat) -> Result<Self, CodecError> {
+        Self::build(format, None)
+    }
+
+    /// Loads the library from the given path
+    pub fn with_library(format: FrameFormat, path: impl AsRef<OsStr>) -> Result<Self, CodecError> {
+        Self::build(format, Some(path.as_ref()))
+    }
+
+    fn build(format: FrameFormat, path: Option<&OsStr>) -> Result<Self, CodecError> {
+        let layout = match format.get_pixel_format() {
+            PixelFormat::I420 => dav1d::PIXEL_LAYOUT_I420,
+            PixelFormat::I444 => dav1d::PIXEL_LAYOUT_I444,
+            pixel_format => {
+                return Err(CodecError::InvalidConfiguration(format!(
+                    "dav1d decodes I420 or I444 frames, not {:?}",
+                    pixel_format
+                )))
+            }
+        };
+
+        let library = library::load("dav1d", DAV1D_LIBRARY_NAMES, path)?;
+        let default_settings: dav1d::DefaultSettings =
+            library::symbol(&library, "dav1d_default_settings")?;
+        let open: dav1d::Open = library::symbol(&library, "dav1d_open")?;
+
+        // SAFETY: the settings are plain C data, valid when zeroed, then filled with defaults
+        let mut settings: dav1d::Settings = unsafe { std::mem::zeroed() };
+        unsafe { default_settings(&mut settings) };
+        settings.max_frame_delay = 1;
+
+        let mut context = ptr::null_mut();
+
+        // SAFETY: the function stores a new decoder in the pointer
+        let status = unsafe { open(&mut context, &settings) };
+        if status != 0 || context.is_null() {
+            return Err(CodecError::InvalidConfiguration(format!(
+                "Unable to create the dav1d decoder (error {})",
+                status
+            )));
+        }
+
+        Ok(Self {
+            format,
+            layout,
+            close: library::symbol(&library, "dav1d_close")?,
+            send_data: library::symbol(&library, "dav1d_send_data")?,
+            get_picture: library::symbol(&library, "dav1d_get_picture")?,
+            picture_unref: library::symbol(&library, "dav1d_picture_unref")?,
+            data_create: library::symbol(&library, "dav1d_data_create")?,
+            data_unref: library::symbol(&library, "dav1d_data_unref")?,
+            _library: library,
+            context,
+        })
+    }
+
+    fn picture(&mut self) -> Result<Option<DecodedPicture>, CodecError> {
+        // SAFETY: the picture is plain C data, valid when zeroed
+        let mut picture: dav1d::Picture = unsafe { std::mem::zeroed() };
+
+        // SAFETY: the context is a valid decoder
+        match unsafe { (self.get_picture)(self.context, &mut picture) } {
+            0 => Ok(Some(DecodedPicture {
+                picture,
+                unref: self.picture_unref,
+            })),
+            dav1d::EAGAIN => Ok(None),
+            status => Err(CodecError::DecodingFailed(format!(
+                "dav1d error {}",
+                status
+            ))),
+        }
+    }
+
+    /// Sends the data to the decoder, returning the last picture it output meanwhile
+    fn send(&mut self, input: &mut dav1d::Data) -> Result<Option<DecodedPicture>, CodecError> {
+        let mut picture = None;
+
+        while input.size > 0 {
+            // SAFETY: the context is a valid decoder and the data was created by it
+            match unsafe { (self.send_data)(self.context, input) } {
+                0 => {}
+                dav1d::EAGAIN => match self.picture()? {
+                    Some(decoded) => picture = Some(decoded),
+                    None => {
+                        return Err(CodecError::DecodingFailed(
+                            "dav1d accepts no data and outputs no pictures".to_string(),
+                        ))
+                    }
+                },
+                status => {
+                    return Err(CodecError::DecodingFailed(format!(
+                        "dav1d error {}",
+                        status
+                    )))
+                }
+            }
+        }
+
+        Ok(picture)
+    }
+}
+
+impl Decoder for AV1Decoder {
+    fn output_format(&self) -> &FrameFormat {
+        &self.format
+    }
+
+    fn decode(&mut self, data: &[u8], planes: &mut [&mut [u8]]) -> Result<bool, CodecError> {
+        let layouts = self.format.planes();
+        if planes.len() != layouts.len()
+            || planes
+                .iter()
+                .zip(&layouts)
+                .any(|(plane, layout)| plane.len() < layout.size())
+        {
+            return Err(CodecError::DecodingFailed(
+                "The planes do not match the output format".to_string(),
+            ));
+        }
+
+        if data.is_empty() {
+            return Err(CodecError::DecodingFailed(
+                "Empty encoded frame".to_string(),
+            ));
+        }
+
+        // SAFETY: the data is plain C data, valid when zeroed, then allocated by the decoder
+        let mut input: dav1d::Data = unsafe { std::mem::zeroed() };
+        let buffer = unsafe { (self.data_create)(&mut input, data.len()) };
+        if buffer.is_null() {
+            return Err(CodecError::DecodingFailed(
+                "Unable to allocate the dav1d input".to_string(),
+            ));
+        }
+
+        // SAFETY: the buffer has just been allocated with the size of the data
+        unsafe { std::slice::from_raw_parts_mut(buffer, data.len()) }.copy_from_slice(data);
+
+        let sent = self.send(&mut input);
+
+        // SAFETY: the data was created by the decoder, releasing it is a no-op once consumed
+        unsafe { (self.data_unref)(&mut input) };
+
+        let picture = match sent? {
+            Some(picture) => Some(picture),
+            None => self.picture()?,
+        };
+
+        let picture = match picture {
+            Some(picture) => picture,
+            None => return Ok(false),
+        };
+
+        let parameters = &picture.picture.parameters;
+        let (width, height) = (self.format.get_width(), self.format.get_height());
+        if (parameters.width as usize, parameters.height as usize) != (width, height)
+            || parameters.layout != self.layout
+            || parameters.bits_per_component != 8
+        {
+            return Err(CodecError::DecodingFailed(format!(
+                "Decoded a {}x{} frame with layout {} and {} bits per component, expected a \
+                 {}x{} {:?} frame",
+                parameters.width,
+                parameters.height,
+                parameters.layout,
+                parameters.bits_per_component,
+                width,
+                height,
+                self.format.get_pixel_format()
+            )));
+        }
+
+        let strides = [
+            picture.picture.stride[0],
+            picture.picture.stride[1],
+            picture.picture.stride[1],
+        ];
+
+        for (((plane, layout), source), stride) in planes
+            .iter_mut()
+            .zip(layouts)
+            .zip(picture.picture.data)
+            .zip(strides)
+        {
+            // SAFETY: the decoded frame has the size of the output format
+            unsafe { library::copy_plane(source, stride as usize, layout, plane) };
+        }
+
+        Ok(true)
+    }
+}
+
+impl Drop for AV1Decoder {
+    fn drop(&mut self) {
+        // SAFETY: the context is a valid decoder, not used afterwards
+        unsafe { (self.close)(&mut self.context) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codec::{Decoder, EncodedFrame, Encoder},
+        pixel_format::{FrameFormat, PixelFormat},
+    };
+
+    use super::{AV1Decoder, AV1Encoder, AV1EncoderConfig};
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 48;
+
+    fn format() -> FrameFormat {
+        FrameFormat::new(PixelFormat::I420, WIDTH, HEIGHT)
+    }
+
+    fn frame(index: usize) -> Vec<Vec<u8>> {
+        format()
+            .planes()
+            .iter()
+            .enumerate()
+            .map(|(plane, layout)| {
+                (0..layout.size())
+                    .map(|i| ((i % layout.stride) * 2 + plane * 40 + index) as u8)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn encode(encoder: &mut AV1Encoder, frame: &[Vec<u8>]) -> Option<EncodedFrame> {
+        let planes: Vec<&[u8]> = frame.iter().map(Vec::as_slice).collect();
+        encoder.encode(&planes).unwrap()
+    }
+
+    fn encoder() -> AV1Encoder {
+        AV1EncoderConfig::new(format())
+            .bitrate(1_000_000)
+            .key_frame_interval(10)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn small_bitrate_changes_are_ignored() {
+        let mut encoder = encoder();
+
+        encoder.set_bitrate(2_000_000).unwrap();
+        assert_eq!(encoder.pending_bitrate, Some(2_000_000));
+
+        encoder.set_bitrate(1_050_000).unwrap();
+        assert_eq!(encoder.pending_bitrate, None);
+    }
+
+    #[test]
+    fn bitrate_changes_wait_for_the_next_keyframe() {
+        let mut encoder = encoder();
+        let mut encoded_frames = Vec::new();
+
+        for index in 0..25 {
+            if index == 3 {
+                encoder.set_bitrate(2_000_000).unwrap();
+            }
+
+            encoded_frames.extend(encode(&mut encoder, &frame(0)));
+
+            let expected = if index < 10 { 1_000_000 } else { 2_000_000 };
+            assert_eq!(encoder.encoder_config.bitrate, expected);
+        }
+        encoded_frames.extend(encoder.flush().unwrap());
+
+        let keyframes: Vec<usize> = encoded_frames
+            .iter()
+            .enumerate()
+            .filter(|(_, encoded_frame)| encoded_frame.keyframe)
+            .map(|(index, _)| index)
+            .collect();
+
+        assert_eq!(encoded_frames.len(), 25);
+        assert_eq!(keyframes, [0, 10, 20]);
+    }
+
+    #[test]
+    #[ignore = "requires the dav1d library"]
+    fn encoded_frames_are_decoded() {
+        let mut encoder = encoder();
+        let mut decoder = AV1Decoder::new(format()).unwrap();
+
+        let mut encoded_frames = Vec::new();
+        for index in 0..5 {
+            encoded_frames.extend(encode(&mut encoder, &frame(index)));
+        }
+        encoded_frames.extend(encoder.flush().unwrap());
+        assert_eq!(encoded_frames.len(), 5);
+
+        let mut decoded: Vec<Vec<u8>> = format()
+            .planes()
+            .iter()
+            .map(|layout| vec![0; layout.size()])
+            .collect();
+
+        for (index, encoded_frame) in encoded_frames.iter().enumerate() {
+            let mut planes: Vec<&mut [u8]> = decoded.iter_mut().map(Vec::as_mut_slice).collect();
+            assert!(decoder.decode(&encoded_frame.data, &mut planes).unwrap());
+
+            for (source, decoded) in frame(index).iter().zip(&decoded) {
+                let max_error = source
+                    .iter()
+                    .zip(decoded)
+                    .map(|(source, decoded)| source.abs_diff(*decoded))
+                    .max()
+                    .unwrap();
+
+                assert!(max_error <= 16, "Error of {} in frame {}", max_error, index);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_library_is_reported() {
+        assert!(AV1Decoder::with_library(format(), "/nonexistent/libdav1d.so").is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::Instant;
+
+use async_trait::async_trait;
+use log::debug;
+use remotia_core::{
+    error::{CodecError, DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use super::{Decoder, DECODE_TIME_STAT, ENCODED_FRAME_BUFFER, ENCODED_SIZE_STAT};
+
+/// Decodes the content of the "encoded_frame_buffer", whose size is read from the
+/// "encoded_size" stat, into the buffers of the output format of the backend. The time spent
+/// is stored in the "decode_time" stat.
+///
+/// Frames which could not be decoded are marked with [`DropReason::NoDecodedFrames`] or
+/// [`DropReason::CodecError`].
+pub struct FrameDecoder {
+    decoder: Box<dyn Decoder>,
+
+    buffer_id: String,
+    size_stat_id: String,
+}
+
+impl FrameDecoder {
+    pub fn new<D: Decoder + 'static>(decoder: D) -> Self {
+        Self {
+            decoder: Box::new(decoder),
+            buffer_id: ENCODED_FRAME_BUFFER.to_string(),
+            size_stat_id: ENCODED_SIZE_STAT.to_string(),
+        }
+    }
+
+    /// Buffer from which the encoded frame is read
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat holding the size of the encoded frame
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = stat_id.to_string();
+        self
+    }
+
+    fn encoded_size(&self, frame_data: &mut FrameData) -> Result<usize, ProcessorError> {
+        let size = frame_data.try_get(&self.size_stat_id)? as usize;
+
+        let found = frame_data
+            .get_writable_buffer_ref(&self.buffer_id)
+            .ok_or_else(|| ProcessorError::MissingBuffer(self.buffer_id.clone()))?
+            .len();
+
+        if found < size {
+            return Err(ProcessorError::BufferTooSmall {
+                key: self.buffer_id.clone(),
+                required: size,
+                found,
+            });
+        }
+
+        Ok(size)
+    }
+
+    /// Decodes into the planes of the output format, after checking them
+    fn decode(
+        &mut self,
+        frame_data: &mut FrameData,
+        size: usize,
+    ) -> Result<Result<bool, CodecError>, ProcessorError> {
+        let format = self.decoder.output_format().clone();
+        format.check_buffers(frame_data)?;
+
+        let mut keys = vec![self.buffer_id.as_str()];
+        keys.extend(format.get_buffers().iter().map(String::as_str));
+
+        frame_data.with_writable_buffers(&keys, |buffers| {
+            let (encoded_buffer, buffers) = buffers.split_first_mut().unwrap();
+
+            let mut planes: Vec<&mut [u8]> = buffers
+                .iter_mut()
+                .zip(format.planes())
+                .map(|(buffer, plane)| &mut buffer[..plane.size()])
+                .collect();
+
+            self.decoder.decode(&encoded_buffer[..size], &mut planes)
+        })
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for FrameDecoder {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let size = match self.encoded_size(&mut frame_data) {
+            Ok(size) => size,
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        let decode_start = Instant::now();
+        let result = self.decode(&mut frame_data, size);
+        let decode_time = decode_start.elapsed();
+
+        match result {
+            Ok(Ok(true)) => frame_data.set_value(DECODE_TIME_STAT, decode_time),
+            Ok(Ok(false)) => {
+                debug!("No decoded frame available");
+                frame_data.set_drop_reason(Some(DropReason::NoDecodedFrames));
+            }
+            Ok(Err(error)) => {
+                debug!("Unable to decode frame: {}", error);
+                frame_data.set_drop_reason(Some(DropReason::CodecError));
+            }
+            Err(error) => return Err((frame_data, error)),
+        }
+
+        Ok(Some(frame_data))
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use log::debug;
+use remotia_core::{
+    common::feedback::TARGET_BITRATE_STAT,
+    error::{CodecError, DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use crate::pixel_format::FrameFormat;
+
+use super::{
+    EncodedFrame, Encoder, ENCODED_FRAME_BUFFER, ENCODED_SIZE_STAT, ENCODE_TIME_STAT,
+    KEYFRAME_STAT, QP_STAT,
+};
+
+/// Encodes the raw frames with the given backend, writing the output in the
+/// "encoded_frame_buffer" along with the "encoded_size", "keyframe", "qp" and "encode_time"
+/// stats.
+///
+/// Frames buffered by the encoder are held until their encoded counterpart is available, then
+/// released in order. Encoding failures mark the frame with [`DropReason::CodecError`].
+///
+/// When a frame carries the "target_bitrate" stat, e.g. set by an adaptive controller, the
+/// bitrate of the encoder is changed accordingly.
+pub struct FrameEncoder {
+    encoder: Box<dyn Encoder>,
+
+    buffer_id: String,
+    size_stat_id: String,
+
+    bitrate: Option<u128>,
+
+    pending_frames: VecDeque<FrameData>,
+}
+
+impl FrameEncoder {
+    pub fn new<E: Encoder + 'static>(encoder: E) -> Self {
+        Self {
+            encoder: Box::new(encoder),
+            buffer_id: ENCODED_FRAME_BUFFER.to_string(),
+            size_stat_id: ENCODED_SIZE_STAT.to_string(),
+            bitrate: None,
+            pending_frames: VecDeque::new(),
+        }
+    }
+
+    /// Buffer in which the encoded frame is written. The buffer is allocated if not already
+    /// present in the frame DTO, e.g. by a buffers pool.
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat in which the size of the encoded frame is stored
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = stat_id.to_string();
+        self
+    }
+
+    fn update_bitrate(&mut self, frame_data: &FrameData) {
+        let bitrate = match frame_data.try_get(TARGET_BITRATE_STAT) {
+            Ok(bitrate) if self.bitrate != Some(bitrate) => bitrate,
+            _ => return,
+        };
+
+        // The bitrate is recorded even on failure, not to retry on each frame
+        self.bitrate = Some(bitrate);
+
+        match self.encoder.set_bitrate(bitrate.min(u32::MAX as u128) as u32) {
+            Ok(()) => debug!("Encoder bitrate set to {} bps", bitrate),
+            Err(error) => debug!("Unable to set the encoder bitrate: {}", error),
+        }
+    }
+
+    /// Encodes the planes of the input format, after checking them
+    fn encode(
+        &mut self,
+        frame_data: &mut FrameData,
+    ) -> Result<Result<Option<EncodedFrame>, CodecError>, ProcessorError> {
+        let format = self.encoder.input_format().clone();
+
+        FrameFormat::with_buffers(&[&format], frame_data, |buffers| {
+            let planes: Vec<&[u8]> = buffers
+                .iter()
+                .zip(format.planes())
+                .map(|(buffer, plane)| &buffer[..plane.size()])
+                .collect();
+
+            self.encoder.encode(&planes)
+        })
+    }
+
+    fn write_encoded_frame(
+        &self,
+        frame_data: &mut FrameData,
+        encoded_frame: EncodedFrame,
+        encode_time: Duration,
+    ) -> Result<(), ProcessorError> {
+        let size = encoded_frame.data.len();
+
+        match frame_data.get_writable_buffer_ref(&self.buffer_id) {
+            Some(buffer) if buffer.len() >= size => {
+                buffer[..size].copy_from_slice(&encoded_frame.data);
+            }
+            Some(buffer) => {
+                return Err(ProcessorError::BufferTooSmall {
+                    key: self.buffer_id.clone(),
+                    required: size,
+                    found: buffer.len(),
+                });
+            }
+            None => {
+                frame_data.insert_writable_buffer(
+                    &self.buffer_id,
+                    BytesMut::from(&encoded_frame.data[..]),
+                );
+            }
+        }
+
+        frame_data.set(&self.size_stat_id, size as u128);
+        frame_data.set_value(KEYFRAME_STAT, encoded_frame.keyframe);
+        if let Some(qp) = encoded_frame.qp {
+            frame_data.set(QP_STAT, qp as u128);
+        }
+        frame_data.set_value(ENCODE_TIME_STAT, encode_time);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for FrameEncoder {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        self.update_bitrate(&frame_data);
+
+        let encode_start = Instant::now();
+        let result = self.encode(&mut frame_data);
+        let encode_time = encode_start.elapsed();
+
+        let encoded_frame = match result {
+            Ok(Ok(Some(encoded_frame))) => encoded_frame,
+            Ok(Ok(None)) => {
+                debug!(
+                    "Encoder is buffering ({} pending frames)",
+                    self.pending_frames.len() + 1
+                );
+                self.pending_frames.push_back(frame_data);
+                return Ok(None);
+            }
+            Ok(Err(error)) => {
+                debug!("Unable to encode frame: {}", error);
+                frame_data.set_drop_reason(Some(DropReason::CodecError));
+                return Ok(Some(frame_data));
+            }
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        self.pending_frames.push_back(frame_data);
+        let mut frame_data = self.pending_frames.pop_front().unwrap();
+
+        match self.write_encoded_frame(&mut frame_data, encoded_frame, encode_time) {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
+    }
+
+    async fn flush(&mut self) -> Vec<FrameData> {
+        let flush_start = Instant::now();
+        let encoded_frames = match self.encoder.flush() {
+            Ok(encoded_frames) => encoded_frames,
+            Err(error) => {
+                debug!("Unable to flush encoder: {}", error);
+                Vec::new()
+            }
+        };
+        let flush_time = flush_start.elapsed();
+
+        debug!(
+            "Releasing {} held frames, {} flushed by the encoder",
+            self.pending_frames.len(),
+            encoded_frames.len()
+        );
+
+        let mut encoded_frames = encoded_frames.into_iter();
+        let pending_frames: Vec<FrameData> = self.pending_frames.drain(..).collect();
+
+        pending_frames
+            .into_iter()
+            .map(|mut frame_data| {
+                match encoded_frames.next() {
+                    Some(encoded_frame) => {
+                        let written =
+                            self.write_encoded_frame(&mut frame_data, encoded_frame, flush_time);
+
+                        if let Err(error) = written {
+                            debug!("Unable to write flushed frame: {}", error);
+                            frame_data.set_drop_reason(Some(DropReason::ProcessorError));
+                        }
+                    }
+                    None => frame_data.set_drop_reason(Some(DropReason::NoEncodedFrames)),
+                }
+
+                frame_data
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use bytes::BytesMut;
+    use remotia_core::{
+        common::feedback::TARGET_BITRATE_STAT,
+        error::{CodecError, DropReason},
+        traits::FrameProcessor,
+        types::FrameData,
+    };
+
+    use crate::{
+        codec::{EncodedFrame, Encoder, ENCODED_FRAME_BUFFER, ENCODED_SIZE_STAT, KEYFRAME_STAT},
+        pixel_format::{FrameFormat, PixelFormat},
+    };
+
+    use super::FrameEncoder;
+
+    /// Holds `delay` frames before returning them, each encoded as a copy of its single BGRA
+    /// pixel. Pixels set to 255 fail to encode.
+    struct DelayedEncoder {
+        format: FrameFormat,
+        delay: usize,
+        buffered: VecDeque<Vec<u8>>,
+        flushable: bool,
+        bitrates: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl DelayedEncoder {
+        fn new(delay: usize) -> Self {
+            Self {
+                format: FrameFormat::new(PixelFormat::BGRA, 1, 1),
+                delay,
+                buffered: VecDeque::new(),
+                flushable: true,
+                bitrates: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+
+        fn encoded_frame(data: Vec<u8>) -> EncodedFrame {
+            let keyframe = data[0] == 0;
+            EncodedFrame::new(data, keyframe)
+        }
+    }
+
+    impl Encoder for DelayedEncoder {
+        fn input_format(&self) -> &FrameFormat {
+            &self.format
+        }
+
+        fn encode(&mut self, planes: &[&[u8]]) -> Result<Option<EncodedFrame>, CodecError> {
+            if planes[0][0] == 255 {
+                return Err(CodecError::EncodingFailed("Unsupported pixel".to_string()));
+            }
+
+            self.buffered.push_back(planes[0].to_vec());
+            if self.buffered.len() <= self.delay {
+                return Ok(None);
+            }
+
+            Ok(self.buffered.pop_front().map(Self::encoded_frame))
+        }
+
+        fn flush(&mut self) -> Result<Vec<EncodedFrame>, CodecError> {
+            if !self.flushable {
+                return Err(CodecError::EncodingFailed("Unable to flush".to_string()));
+            }
+
+            Ok(self.buffered.drain(..).map(Self::encoded_frame).collect())
+        }
+
+        fn set_bitrate(&mut self, bits_per_second: u32) -> Result<(), CodecError> {
+            self.bitrates.lock().unwrap().push(bits_per_second);
+            Ok(())
+        }
+    }
+
+    fn frame(id: u8) -> FrameData {
+        let mut frame_data = FrameData::default();
+        frame_data.set("frame_id", id as u128);
+        frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::from(&[id; 4][..]));
+        frame_data
+    }
+
+    /// Checks that the frame carries its own encoded pixel
+    fn check_encoded(frame_data: &mut FrameData) {
+        let id = frame_data.get("frame_id") as u8;
+
+        assert_eq!(frame_data.get_drop_reason(), None);
+        assert_eq!(frame_data.get(ENCODED_SIZE_STAT), 4);
+        assert_eq!(frame_data.get_bool(KEYFRAME_STAT), id == 0);
+        assert_eq!(
+            &frame_data
+                .get_writable_buffer_ref(ENCODED_FRAME_BUFFER)
+                .unwrap()[..],
+            &[id; 4]
+        );
+    }
+
+    #[tokio::test]
+    async fn buffered_frames_are_released_in_order() {
+        let mut encoder = FrameEncoder::new(DelayedEncoder::new(2));
+
+        let mut released = Vec::new();
+        for id in 0..5 {
+            let output = encoder.try_process(frame(id)).await.unwrap();
+            assert_eq!(output.is_none(), id < 2);
+            released.extend(output);
+        }
+
+        released.extend(encoder.flush().await);
+
+        let ids: Vec<u128> = released.iter().map(|frame| frame.get("frame_id")).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+
+        for frame_data in released.iter_mut() {
+            check_encoded(frame_data);
+        }
+    }
+
+    #[tokio::test]
+    async fn frames_left_without_output_on_flush_are_dropped() {
+        let mut delayed_encoder = DelayedEncoder::new(2);
+        delayed_encoder.flushable = false;
+        let mut encoder = FrameEncoder::new(delayed_encoder);
+
+        for id in 0..3 {
+            encoder.try_process(frame(id)).await.unwrap();
+        }
+
+        let flushed = encoder.flush().await;
+        let ids: Vec<u128> = flushed.iter().map(|frame| frame.get("frame_id")).collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        for frame_data in flushed {
+            assert_eq!(
+                frame_data.get_drop_reason(),
+                Some(DropReason::NoEncodedFrames)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn encoding_failures_drop_the_frame() {
+        let mut encoder = FrameEncoder::new(DelayedEncoder::new(0));
+
+        let frame_data = encoder.try_process(frame(255)).await.unwrap().unwrap();
+        assert_eq!(frame_data.get_drop_reason(), Some(DropReason::CodecError));
+
+        let mut frame_data = encoder.try_process(frame(1)).await.unwrap().unwrap();
+        check_encoded(&mut frame_data);
+    }
+
+    #[tokio::test]
+    async fn target_bitrate_changes_are_forwarded() {
+        let delayed_encoder = DelayedEncoder::new(0);
+        let bitrates = delayed_encoder.bitrates.clone();
+        let mut encoder = FrameEncoder::new(delayed_encoder);
+
+        for (id, bitrate) in [1_000, 1_000, 2_000, 1 << 40].into_iter().enumerate() {
+            let mut frame_data = frame(id as u8);
+            frame_data.set(TARGET_BITRATE_STAT, bitrate);
+            encoder.try_process(frame_data).await.unwrap();
+        }
+
+        encoder.try_process(frame(4)).await.unwrap();
+
+        assert_eq!(*bitrates.lock().unwrap(), vec![1_000, 2_000, u32::MAX]);
+    }
+}
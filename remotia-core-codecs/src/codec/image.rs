@@ -0,0 +1,458 @@
+use std::io::Cursor;
+
+use remotia_core::error::CodecError;
+
+use crate::pixel_format::{convert_packed, FrameFormat, PixelFormat};
+
+use super::{Decoder, EncodedFrame, Encoder};
+
+/// Still image format in which each frame is compressed independently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Lossy, with a quality from 1 to 100
+    Jpeg { quality: u8 },
+
+    /// Lossless, with fast compression settings
+    Png,
+
+    /// Lossless, trading compression ratio for speed
+    Qoi,
+}
+
+const JPEG_SIGNATURE: &[u8] = &[0xFF, 0xD8];
+const PNG_SIGNATURE: &[u8] = b"\x89PNG";
+const QOI_SIGNATURE: &[u8] = b"qoif";
+
+/// RGB(A) format with the same number of channels of a packed format
+fn rgb_format(pixel_format: PixelFormat) -> PixelFormat {
+    match pixel_format.bytes_per_pixel() {
+        Some(4) => PixelFormat::RGBA,
+        _ => PixelFormat::RGB24,
+    }
+}
+
+fn decoding_failed<E: std::error::Error>(error: E) -> CodecError {
+    CodecError::DecodingFailed(error.to_string())
+}
+
+fn packed_bytes_per_pixel(format: &FrameFormat) -> usize {
+    format
+        .get_pixel_format()
+        .bytes_per_pixel()
+        .expect("Image codecs require a packed pixel format")
+}
+
+/// Intra-only encoder compressing each frame as a JPEG, PNG or QOI image, as in MJPEG
+/// streaming. Every encoded frame is a keyframe.
+pub struct ImageEncoder {
+    format: FrameFormat,
+    image_format: ImageFormat,
+
+    /// Tightly packed pixels, when the frame must be repacked before encoding
+    pixels: Vec<u8>,
+}
+
+impl ImageEncoder {
+    pub fn new(format: FrameFormat, image_format: ImageFormat) -> Self {
+        packed_bytes_per_pixel(&format);
+
+        Self {
+            format,
+            image_format,
+            pixels: Vec::new(),
+        }
+    }
+
+    /// Tightly packed pixels of the frame, in the given packed format
+    fn pixels<'a>(&'a mut self, frame: &'a [u8], pixel_format: PixelFormat) -> &'a [u8] {
+        let plane = self.format.planes()[0];
+        let source_format = self.format.get_pixel_format();
+
+        if source_format == pixel_format && plane.stride == plane.row_size {
+            return frame;
+        }
+
+        let row_size = self.format.get_width() * pixel_format.bytes_per_pixel().unwrap();
+        self.pixels.resize(row_size * plane.rows, 0);
+
+        for (row, pixels_row) in self.pixels.chunks_exact_mut(row_size).enumerate() {
+            let offset = row * plane.stride;
+            let source_row = &frame[offset..offset + plane.row_size];
+
+            convert_packed(source_format, pixel_format, source_row, pixels_row);
+        }
+
+        &self.pixels
+    }
+
+    fn encode_jpeg(&mut self, frame: &[u8], quality: u8) -> Result<Vec<u8>, CodecError> {
+        let (width, height) = (self.format.get_width(), self.format.get_height());
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => {
+                return Err(CodecError::InvalidConfiguration(format!(
+                    "{}x{} frames exceed the JPEG size limit",
+                    width, height
+                )))
+            }
+        };
+
+        let pixel_format = self.format.get_pixel_format();
+        let color_type = match pixel_format {
+            PixelFormat::BGRA => jpeg_encoder::ColorType::Bgra,
+            PixelFormat::RGBA => jpeg_encoder::ColorType::Rgba,
+            PixelFormat::BGR24 => jpeg_encoder::ColorType::Bgr,
+            _ => jpeg_encoder::ColorType::Rgb,
+        };
+
+        let mut data = Vec::new();
+        jpeg_encoder::Encoder::new(&mut data, quality)
+            .encode(self.pixels(frame, pixel_format), width, height, color_type)
+            .map_err(|error| CodecError::EncodingFailed(error.to_string()))?;
+
+        Ok(data)
+    }
+
+    fn encode_png(&mut self, frame: &[u8]) -> Result<Vec<u8>, CodecError> {
+        let pixel_format = rgb_format(self.format.get_pixel_format());
+        let (width, height) = (self.format.get_width(), self.format.get_height());
+
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, width as u32, height as u32);
+        encoder.set_color(match pixel_format {
+            PixelFormat::RGBA => png::ColorType::Rgba,
+            _ => png::ColorType::Rgb,
+        });
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Fast);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(self.pixels(frame, pixel_format)))
+            .map_err(|error| CodecError::EncodingFailed(error.to_string()))?;
+
+        Ok(data)
+    }
+
+    fn encode_qoi(&mut self, frame: &[u8]) -> Result<Vec<u8>, CodecError> {
+        let pixel_format = rgb_format(self.format.get_pixel_format());
+        let (width, height) = (self.format.get_width(), self.format.get_height());
+
+        qoi::encode_to_vec(
+            self.pixels(frame, pixel_format),
+            width as u32,
+            height as u32,
+        )
+        .map_err(|error| CodecError::EncodingFailed(error.to_string()))
+    }
+}
+
+impl Encoder for ImageEncoder {
+    fn input_format(&self) -> &FrameFormat {
+        &self.format
+    }
+
+    fn encode(&mut self, planes: &[&[u8]]) -> Result<Option<EncodedFrame>, CodecError> {
+        let data = match self.image_format {
+            ImageFormat::Jpeg { quality } => self.encode_jpeg(planes[0], quality)?,
+            ImageFormat::Png => self.encode_png(planes[0])?,
+            ImageFormat::Qoi => self.encode_qoi(planes[0])?,
+        };
+
+        Ok(Some(EncodedFrame::new(data, true)))
+    }
+}
+
+/// Decoder of the frames produced by an [`ImageEncoder`], whatever their image format
+pub struct ImageDecoder {
+    format: FrameFormat,
+}
+
+impl ImageDecoder {
+    pub fn new(format: FrameFormat) -> Self {
+        packed_bytes_per_pixel(&format);
+
+        Self { format }
+    }
+
+    fn check_size(&self, width: usize, height: usize) -> Result<(), CodecError> {
+        if (width, height) != (self.format.get_width(), self.format.get_height()) {
+            return Err(CodecError::DecodingFailed(format!(
+                "Decoded a {}x{} image, expected {}x{}",
+                width,
+                height,
+                self.format.get_width(),
+                self.format.get_height()
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn decode_jpeg(&self, data: &[u8]) -> Result<(PixelFormat, Vec<u8>), CodecError> {
+        let mut decoder = jpeg_decoder::Decoder::new(data);
+        let pixels = decoder.decode().map_err(decoding_failed)?;
+        let info = decoder.info().unwrap();
+
+        if info.pixel_format != jpeg_decoder::PixelFormat::RGB24 {
+            return Err(CodecError::DecodingFailed(format!(
+                "Unsupported JPEG pixel format {:?}",
+                info.pixel_format
+            )));
+        }
+
+        self.check_size(info.width as usize, info.height as usize)?;
+        Ok((PixelFormat::RGB24, pixels))
+    }
+
+    fn decode_png(&self, data: &[u8]) -> Result<(PixelFormat, Vec<u8>), CodecError> {
+        let mut decoder = png::Decoder::new(Cursor::new(data));
+        decoder.set_transformations(png::Transformations::EXPAND);
+
+        let mut reader = decoder.read_info().map_err(decoding_failed)?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).map_err(decoding_failed)?;
+
+        let pixel_format = match (info.color_type, info.bit_depth) {
+            (png::ColorType::Rgba, png::BitDepth::Eight) => PixelFormat::RGBA,
+            (png::ColorType::Rgb, png::BitDepth::Eight) => PixelFormat::RGB24,
+            (color_type, bit_depth) => {
+                return Err(CodecError::DecodingFailed(format!(
+                    "Unsupported PNG pixel format {:?} {:?}",
+                    color_type, bit_depth
+                )))
+            }
+        };
+
+        self.check_size(info.width as usize, info.height as usize)?;
+        pixels.truncate(info.buffer_size());
+        Ok((pixel_format, pixels))
+    }
+
+    fn decode_qoi(&self, data: &[u8]) -> Result<(PixelFormat, Vec<u8>), CodecError> {
+        let pixel_format = rgb_format(self.format.get_pixel_format());
+        let channels = match pixel_format {
+            PixelFormat::RGBA => qoi::Channels::Rgba,
+            _ => qoi::Channels::Rgb,
+        };
+
+        let mut decoder = qoi::Decoder::new(data)
+            .map_err(decoding_failed)?
+            .with_channels(channels);
+
+        let header = *decoder.header();
+        self.check_size(header.width as usize, header.height as usize)?;
+
+        let pixels = decoder.decode_to_vec().map_err(decoding_failed)?;
+        Ok((pixel_format, pixels))
+    }
+
+    /// Checks that the output plane can hold a frame of the output format
+    fn check_planes(&self, planes: &[&mut [u8]]) -> Result<(), CodecError> {
+        let (width, height) = (self.format.get_width(), self.format.get_height());
+        if width == 0 || height == 0 {
+            return Err(CodecError::InvalidConfiguration(format!(
+                "Unable to decode images into {}x{} frames",
+                width, height
+            )));
+        }
+
+        let required = self.format.planes()[0].size();
+        let found = planes.first().map_or(0, |plane| plane.len());
+        if found < required {
+            return Err(CodecError::InvalidConfiguration(format!(
+                "The output plane holds {} bytes, {} are required",
+                found, required
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Decodes an image into tightly packed RGB or RGBA pixels, detecting its format from
+    /// its signature
+    fn decode_image(&self, data: &[u8]) -> Result<(PixelFormat, Vec<u8>), CodecError> {
+        if data.starts_with(JPEG_SIGNATURE) {
+            self.decode_jpeg(data)
+        } else if data.starts_with(PNG_SIGNATURE) {
+            self.decode_png(data)
+        } else if data.starts_with(QOI_SIGNATURE) {
+            self.decode_qoi(data)
+        } else {
+            Err(CodecError::DecodingFailed(
+                "Unknown image format".to_string(),
+            ))
+        }
+    }
+}
+
+impl Decoder for ImageDecoder {
+    fn output_format(&self) -> &FrameFormat {
+        &self.format
+    }
+
+    fn decode(&mut self, data: &[u8], planes: &mut [&mut [u8]]) -> Result<bool, CodecError> {
+        self.check_planes(planes)?;
+        let (pixel_format, pixels) = self.decode_image(data)?;
+
+        let plane = self.format.planes()[0];
+        let row_size = self.format.get_width() * pixel_format.bytes_per_pixel().unwrap();
+
+        for (row, pixels_row) in pixels.chunks_exact(row_size).enumerate() {
+            let offset = row * plane.stride;
+            let frame_row = &mut planes[0][offset..offset + plane.row_size];
+
+            convert_packed(
+                pixel_format,
+                self.format.get_pixel_format(),
+                pixels_row,
+                frame_row,
+            );
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use remotia_core::error::CodecError;
+
+    use crate::{
+        codec::{Decoder, Encoder},
+        pixel_format::{FrameFormat, PixelFormat},
+    };
+
+    use super::{ImageDecoder, ImageEncoder, ImageFormat};
+
+    fn encoded_image(format: &FrameFormat) -> Vec<u8> {
+        let frame = vec![128; format.planes()[0].size()];
+        let mut encoder = ImageEncoder::new(format.clone(), ImageFormat::Qoi);
+
+        encoder.encode(&[&frame]).unwrap().unwrap().data
+    }
+
+    #[test]
+    fn zero_width_frames_are_rejected() {
+        let format = FrameFormat::new(PixelFormat::BGRA, 0, 4);
+        let mut decoder = ImageDecoder::new(format);
+
+        let result = decoder.decode(b"qoif", &mut [&mut []]);
+        assert!(matches!(result, Err(CodecError::InvalidConfiguration(_))));
+    }
+
+    #[test]
+    fn short_planes_are_rejected() {
+        let format = FrameFormat::new(PixelFormat::BGRA, 4, 4);
+        let data = encoded_image(&format);
+        let mut decoder = ImageDecoder::new(format);
+
+        let mut plane = vec![0; 4 * 4 * 4 - 1];
+        let result = decoder.decode(&data, &mut [&mut plane]);
+        assert!(matches!(result, Err(CodecError::InvalidConfiguration(_))));
+
+        let result = decoder.decode(&data, &mut []);
+        assert!(matches!(result, Err(CodecError::InvalidConfiguration(_))));
+    }
+
+    #[test]
+    fn valid_planes_are_decoded() {
+        let format = FrameFormat::new(PixelFormat::BGRA, 4, 4);
+        let data = encoded_image(&format);
+        let mut decoder = ImageDecoder::new(format);
+
+        let mut plane = vec![0; 4 * 4 * 4];
+        assert!(decoder.decode(&data, &mut [&mut plane]).unwrap());
+        assert!(plane.iter().all(|value| *value == 128));
+    }
+
+    /// Smooth pattern varying differently along each axis, so that transposed or mirrored
+    /// output is caught while a lossy codec stays close
+    fn pattern_pixel(x: usize, y: usize) -> [u8; 4] {
+        [(x * 10) as u8, (y * 15) as u8, (x * 3 + y * 7) as u8, 255]
+    }
+
+    fn pattern(format: &FrameFormat) -> Vec<u8> {
+        let plane = format.planes()[0];
+        let pixel_format = format.get_pixel_format();
+        let size = pixel_format.bytes_per_pixel().unwrap();
+
+        let mut frame = vec![0; plane.size()];
+        for y in 0..format.get_height() {
+            for x in 0..format.get_width() {
+                let offset = y * plane.stride + x * size;
+                pixel_format.write_bgra(pattern_pixel(x, y), &mut frame[offset..offset + size]);
+            }
+        }
+
+        frame
+    }
+
+    /// Largest difference between a channel of the decoded frame and the pattern
+    fn round_trip_error(image_format: ImageFormat, input: FrameFormat, output: FrameFormat) -> u8 {
+        let frame = pattern(&input);
+        let data = ImageEncoder::new(input, image_format)
+            .encode(&[&frame])
+            .unwrap()
+            .unwrap()
+            .data;
+
+        let plane = output.planes()[0];
+        let pixel_format = output.get_pixel_format();
+        let size = pixel_format.bytes_per_pixel().unwrap();
+
+        let mut decoded = vec![0; plane.size()];
+        assert!(ImageDecoder::new(output.clone())
+            .decode(&data, &mut [&mut decoded])
+            .unwrap());
+
+        let mut error = 0;
+        for y in 0..output.get_height() {
+            for x in 0..output.get_width() {
+                let offset = y * plane.stride + x * size;
+                let found = pixel_format.read_bgra(&decoded[offset..offset + size]);
+
+                for (found, expected) in found.iter().zip(pattern_pixel(x, y)) {
+                    error = error.max(found.abs_diff(expected));
+                }
+            }
+        }
+
+        error
+    }
+
+    #[test]
+    fn images_survive_a_round_trip() {
+        let (width, height) = (23, 13);
+        let inputs = [
+            FrameFormat::new(PixelFormat::BGRA, width, height).stride(width * 4 + 12),
+            FrameFormat::new(PixelFormat::BGR24, width, height),
+        ];
+        let outputs = [
+            FrameFormat::new(PixelFormat::RGB24, width, height).stride(width * 3 + 5),
+            FrameFormat::new(PixelFormat::BGRA, width, height),
+        ];
+
+        let image_formats = [
+            (ImageFormat::Jpeg { quality: 95 }, 8),
+            (ImageFormat::Png, 0),
+            (ImageFormat::Qoi, 0),
+        ];
+
+        for (image_format, tolerance) in image_formats {
+            for input in &inputs {
+                for output in &outputs {
+                    let error = round_trip_error(image_format, input.clone(), output.clone());
+                    assert!(
+                        error <= tolerance,
+                        "{:?} from {:?} to {:?}: error {}",
+                        image_format,
+                        input.get_pixel_format(),
+                        output.get_pixel_format(),
+                        error
+                    );
+                }
+            }
+        }
+    }
+}
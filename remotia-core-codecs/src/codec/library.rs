@@ -0,0 +1,64 @@
+use std::ffi::OsStr;
+
+use libloading::Library;
+use remotia_core::error::CodecError;
+
+use crate::pixel_format::PlaneLayout;
+
+/// Loads a codec library from the given path, or from the first of its default names found
+/// in the system
+pub(crate) fn load(
+    name: &str,
+    default_names: &[&str],
+    path: Option<&OsStr>,
+) -> Result<Library, CodecError> {
+    let candidates: Vec<&OsStr> = match path {
+        Some(path) => vec![path],
+        None => default_names.iter().map(OsStr::new).collect(),
+    };
+
+    let mut last_error = None;
+    for candidate in candidates {
+        // SAFETY: codec libraries have no initialization routines with preconditions
+        match unsafe { Library::new(candidate) } {
+            Ok(library) => return Ok(library),
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    Err(CodecError::InvalidConfiguration(format!(
+        "Unable to load the {} library: {}",
+        name,
+        last_error.unwrap()
+    )))
+}
+
+/// Looks up a function of the library, whose type must match its declaration
+pub(crate) fn symbol<T: Copy>(library: &Library, name: &str) -> Result<T, CodecError> {
+    // SAFETY: the caller declares the type of the symbol according to the library API
+    unsafe { library.get::<T>(name.as_bytes()) }
+        .map(|symbol| *symbol)
+        .map_err(|error| CodecError::InvalidConfiguration(error.to_string()))
+}
+
+/// Copies the rows of a plane decoded by a library, whose stride may differ from the
+/// destination one
+///
+/// # Safety
+///
+/// The source must hold the rows of the layout, each one spanning the source stride.
+pub(crate) unsafe fn copy_plane(
+    source: *const u8,
+    source_stride: usize,
+    layout: PlaneLayout,
+    destination: &mut [u8],
+) {
+    for row in 0..layout.rows {
+        // SAFETY: guaranteed by the caller
+        let source_row =
+            unsafe { std::slice::from_raw_parts(source.add(row * source_stride), layout.row_size) };
+
+        let offset = row * layout.stride;
+        destination[offset..offset + layout.row_size].copy_from_slice(source_row);
+    }
+}
@@ -0,0 +1,85 @@
+use remotia_core::error::CodecError;
+
+use crate::pixel_format::FrameFormat;
+
+pub mod decoder;
+pub mod encoder;
+pub mod image;
+
+#[cfg(feature = "av1")]
+pub mod av1;
+
+#[cfg(feature = "openh264")]
+pub mod openh264;
+
+#[cfg(any(feature = "av1", feature = "openh264"))]
+mod library;
+
+pub const ENCODED_FRAME_BUFFER: &str = "encoded_frame_buffer";
+
+pub const ENCODED_SIZE_STAT: &str = "encoded_size";
+pub const KEYFRAME_STAT: &str = "keyframe";
+pub const QP_STAT: &str = "qp";
+pub const ENCODE_TIME_STAT: &str = "encode_time";
+pub const DECODE_TIME_STAT: &str = "decode_time";
+
+/// Output of an encoder for a single frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedFrame {
+    pub data: Vec<u8>,
+    pub keyframe: bool,
+
+    /// Average quantization parameter, when reported by the encoder
+    pub qp: Option<u32>,
+}
+
+impl EncodedFrame {
+    pub fn new(data: Vec<u8>, keyframe: bool) -> Self {
+        Self {
+            data,
+            keyframe,
+            qp: None,
+        }
+    }
+
+    pub fn qp(mut self, qp: u32) -> Self {
+        self.qp = Some(qp);
+        self
+    }
+}
+
+/// Encoding backend driven by a [`encoder::FrameEncoder`]
+pub trait Encoder: Send {
+    /// Layout of the raw frames taken as input
+    fn input_format(&self) -> &FrameFormat;
+
+    /// Encodes a raw frame whose planes follow the input format. Encoded frames are returned
+    /// in input order, `None` meaning that the encoder is still buffering.
+    fn encode(&mut self, planes: &[&[u8]]) -> Result<Option<EncodedFrame>, CodecError>;
+
+    /// Returns the frames still buffered by the encoder at the end of the stream
+    fn flush(&mut self) -> Result<Vec<EncodedFrame>, CodecError> {
+        Ok(Vec::new())
+    }
+
+    /// Encodes the next frame as a keyframe
+    fn force_keyframe(&mut self) {}
+
+    /// Changes the target bitrate, in bits per second, of the following frames. Backends may
+    /// defer the change, e.g. to the next keyframe, or ignore small ones.
+    fn set_bitrate(&mut self, _bits_per_second: u32) -> Result<(), CodecError> {
+        Err(CodecError::InvalidConfiguration(
+            "The encoder does not support bitrate changes".to_string(),
+        ))
+    }
+}
+
+/// Decoding backend driven by a [`decoder::FrameDecoder`]
+pub trait Decoder: Send {
+    /// Layout of the raw frames produced as output
+    fn output_format(&self) -> &FrameFormat;
+
+    /// Decodes an encoded frame into the planes of the output format. Returns `false` when
+    /// no frame could be produced yet.
+    fn decode(&mut self, data: &[u8], planes: &mut [&mut [u8]]) -> Result<bool, CodecError>;
+}
@@ -0,0 +1,673 @@
+use std::{
+    ffi::{c_int, c_void, OsStr, OsString},
+    ptr,
+};
+
+use libloading::Library;
+use remotia_core::error::CodecError;
+
+use crate::pixel_format::{FrameFormat, PixelFormat};
+
+use super::{library, Decoder, EncodedFrame, Encoder};
+
+/// Subset of the OpenH264 2.x C API, as declared in codec_api.h and codec_app_def.h
+mod ffi {
+    use std::ffi::{c_char, c_float, c_int, c_long, c_longlong, c_uint, c_ulonglong, c_void};
+
+    pub const CAMERA_VIDEO_REAL_TIME: c_int = 0;
+    pub const SCREEN_CONTENT_REAL_TIME: c_int = 1;
+
+    pub const RC_BITRATE_MODE: c_int = 1;
+
+    pub const VIDEO_FORMAT_I420: c_int = 23;
+
+    pub const VIDEO_FRAME_TYPE_IDR: c_int = 1;
+    pub const VIDEO_FRAME_TYPE_I: c_int = 2;
+    pub const VIDEO_FRAME_TYPE_SKIP: c_int = 4;
+
+    pub const ENCODER_OPTION_IDR_INTERVAL: c_int = 1;
+    pub const ENCODER_OPTION_BITRATE: c_int = 5;
+    pub const SPATIAL_LAYER_ALL: c_int = 4;
+
+    pub const VIDEO_BITSTREAM_AVC: c_int = 0;
+    pub const TARGET_ALL_LAYERS: u8 = u8::MAX;
+
+    pub const MAX_LAYER_NUM_OF_FRAME: usize = 128;
+
+    #[repr(C)]
+    pub struct SEncParamBase {
+        pub usage_type: c_int,
+        pub width: c_int,
+        pub height: c_int,
+        pub target_bitrate: c_int,
+        pub rc_mode: c_int,
+        pub max_frame_rate: c_float,
+    }
+
+    #[repr(C)]
+    pub struct SSourcePicture {
+        pub color_format: c_int,
+        pub stride: [c_int; 4],
+        pub data: [*mut u8; 4],
+        pub width: c_int,
+        pub height: c_int,
+        pub timestamp: c_longlong,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct SLayerBSInfo {
+        pub temporal_id: u8,
+        pub spatial_id: u8,
+        pub quality_id: u8,
+        pub frame_type: c_int,
+        pub layer_type: u8,
+        pub sub_seq_id: c_int,
+        pub nal_count: c_int,
+        pub nal_lengths: *mut c_int,
+        pub bitstream: *mut u8,
+    }
+
+    #[repr(C)]
+    pub struct SFrameBSInfo {
+        pub layer_count: c_int,
+        pub layers: [SLayerBSInfo; MAX_LAYER_NUM_OF_FRAME],
+        pub frame_type: c_int,
+        pub frame_size: c_int,
+        pub timestamp: c_longlong,
+    }
+
+    #[repr(C)]
+    pub struct SBitrateInfo {
+        pub layer: c_int,
+        pub bitrate: c_int,
+    }
+
+    #[repr(C)]
+    pub struct SVideoProperty {
+        pub size: c_uint,
+        pub bitstream_type: c_int,
+    }
+
+    #[repr(C)]
+    pub struct SDecodingParam {
+        pub file_name_restructed: *mut c_char,
+        pub cpu_load: c_uint,
+        pub target_dq_layer: u8,
+        pub error_concealment: c_int,
+        pub parse_only: bool,
+        pub video_property: SVideoProperty,
+    }
+
+    #[repr(C)]
+    pub struct SSysMEMBuffer {
+        pub width: c_int,
+        pub height: c_int,
+        pub format: c_int,
+        pub stride: [c_int; 2],
+    }
+
+    #[repr(C)]
+    pub struct SBufferInfo {
+        pub buffer_status: c_int,
+        pub in_timestamp: c_ulonglong,
+        pub out_timestamp: c_ulonglong,
+        pub system_buffer: SSysMEMBuffer,
+        pub dst: [*mut u8; 3],
+    }
+
+    pub type ISVCEncoder = *const ISVCEncoderVtbl;
+
+    #[repr(C)]
+    pub struct ISVCEncoderVtbl {
+        pub initialize: unsafe extern "C" fn(*mut ISVCEncoder, *const SEncParamBase) -> c_int,
+        pub initialize_ext: unsafe extern "C" fn(*mut ISVCEncoder, *const c_void) -> c_int,
+        pub get_default_params: unsafe extern "C" fn(*mut ISVCEncoder, *mut c_void) -> c_int,
+        pub uninitialize: unsafe extern "C" fn(*mut ISVCEncoder) -> c_int,
+        pub encode_frame: unsafe extern "C" fn(
+            *mut ISVCEncoder,
+            *const SSourcePicture,
+            *mut SFrameBSInfo,
+        ) -> c_int,
+        pub encode_parameter_sets:
+            unsafe extern "C" fn(*mut ISVCEncoder, *mut SFrameBSInfo) -> c_int,
+        pub force_intra_frame: unsafe extern "C" fn(*mut ISVCEncoder, bool) -> c_int,
+        pub set_option: unsafe extern "C" fn(*mut ISVCEncoder, c_int, *mut c_void) -> c_int,
+        pub get_option: unsafe extern "C" fn(*mut ISVCEncoder, c_int, *mut c_void) -> c_int,
+    }
+
+    pub type ISVCDecoder = *const ISVCDecoderVtbl;
+
+    /// Leading entries of the decoder vtable, the only ones in use
+    #[repr(C)]
+    pub struct ISVCDecoderVtbl {
+        pub initialize: unsafe extern "C" fn(*mut ISVCDecoder, *const SDecodingParam) -> c_long,
+        pub uninitialize: unsafe extern "C" fn(*mut ISVCDecoder) -> c_long,
+        pub decode_frame: *const c_void,
+        pub decode_frame_no_delay: unsafe extern "C" fn(
+            *mut ISVCDecoder,
+            *const u8,
+            c_int,
+            *mut *mut u8,
+            *mut SBufferInfo,
+        ) -> c_int,
+    }
+
+    pub type CreateEncoder = unsafe extern "C" fn(*mut *mut ISVCEncoder) -> c_int;
+    pub type DestroyEncoder = unsafe extern "C" fn(*mut ISVCEncoder);
+    pub type CreateDecoder = unsafe extern "C" fn(*mut *mut ISVCDecoder) -> c_long;
+    pub type DestroyDecoder = unsafe extern "C" fn(*mut ISVCDecoder);
+}
+
+/// Names under which the library is looked up when no path is configured
+const LIBRARY_NAMES: &[&str] = if cfg!(target_os = "windows") {
+    &["openh264.dll"]
+} else if cfg!(target_os = "macos") {
+    &["libopenh264.dylib", "libopenh264.7.dylib"]
+} else {
+    &["libopenh264.so", "libopenh264.so.7"]
+};
+
+fn load_library(path: Option<&OsStr>) -> Result<Library, CodecError> {
+    library::load("OpenH264", LIBRARY_NAMES, path)
+}
+
+fn check_i420(format: &FrameFormat) -> Result<(), CodecError> {
+    match format.get_pixel_format() {
+        PixelFormat::I420 => Ok(()),
+        pixel_format => Err(CodecError::InvalidConfiguration(format!(
+            "OpenH264 handles I420 frames, not {:?}",
+            pixel_format
+        ))),
+    }
+}
+
+/// Kind of content being encoded, which OpenH264 tunes its tools for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenH264Usage {
+    Camera,
+    Screen,
+}
+
+/// Settings of an [`OpenH264Encoder`], tuned for real-time screen content by default
+pub struct OpenH264EncoderConfig {
+    format: FrameFormat,
+    library: Option<OsString>,
+
+    usage: OpenH264Usage,
+    bitrate: u32,
+    frame_rate: f32,
+    key_frame_interval: u32,
+}
+
+impl OpenH264EncoderConfig {
+    pub fn new(format: FrameFormat) -> Self {
+        Self {
+            format,
+            library: None,
+            usage: OpenH264Usage::Screen,
+            bitrate: 5_000_000,
+            frame_rate: 60.0,
+            key_frame_interval: 240,
+        }
+    }
+
+    /// Path of the OpenH264 library, looked up in the default locations otherwise
+    pub fn library(mut self, path: impl Into<OsString>) -> Self {
+        self.library = Some(path.into());
+        self
+    }
+
+    pub fn usage(mut self, usage: OpenH264Usage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Target bitrate in bits per second
+    pub fn bitrate(mut self, bits_per_second: u32) -> Self {
+        self.bitrate = bits_per_second;
+        self
+    }
+
+    pub fn frame_rate(mut self, frame_rate: f32) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    /// Maximum number of frames between two keyframes
+    pub fn key_frame_interval(mut self, interval: u32) -> Self {
+        self.key_frame_interval = interval;
+        self
+    }
+
+    pub fn build(self) -> Result<OpenH264Encoder, CodecError> {
+        check_i420(&self.format)?;
+
+        let library = load_library(self.library.as_deref())?;
+        let create: ffi::CreateEncoder = library::symbol(&library, "WelsCreateSVCEncoder")?;
+        let destroy: ffi::DestroyEncoder = library::symbol(&library, "WelsDestroySVCEncoder")?;
+
+        let mut handle = ptr::null_mut();
+
+        // SAFETY: the function stores a new encoder in the pointer
+        if unsafe { create(&mut handle) } != 0 || handle.is_null() {
+            return Err(CodecError::InvalidConfiguration(
+                "Unable to create the OpenH264 encoder".to_string(),
+            ));
+        }
+
+        let mut encoder = OpenH264Encoder {
+            format: self.format,
+            _library: library,
+            destroy,
+            handle,
+            initialized: false,
+            frame_rate: self.frame_rate,
+            frames_count: 0,
+        };
+
+        let parameters = ffi::SEncParamBase {
+            usage_type: match self.usage {
+                OpenH264Usage::Camera => ffi::CAMERA_VIDEO_REAL_TIME,
+                OpenH264Usage::Screen => ffi::SCREEN_CONTENT_REAL_TIME,
+            },
+            width: encoder.format.get_width() as c_int,
+            height: encoder.format.get_height() as c_int,
+            target_bitrate: self.bitrate.min(c_int::MAX as u32) as c_int,
+            rc_mode: ffi::RC_BITRATE_MODE,
+            max_frame_rate: self.frame_rate,
+        };
+
+        // SAFETY: the handle is a valid encoder and the parameters outlive the call
+        let status = unsafe { ((**encoder.handle).initialize)(encoder.handle, &parameters) };
+        if status != 0 {
+            return Err(CodecError::InvalidConfiguration(format!(
+                "Unable to initialize the OpenH264 encoder (error {})",
+                status
+            )));
+        }
+        encoder.initialized = true;
+
+        let mut interval = self.key_frame_interval.min(c_int::MAX as u32) as c_int;
+        encoder.set_option(ffi::ENCODER_OPTION_IDR_INTERVAL, &mut interval)?;
+
+        Ok(encoder)
+    }
+}
+
+/// H.264 software encoder based on a runtime-loaded OpenH264 library. Frames are encoded as
+/// soon as they are received, so the encoder never buffers.
+pub struct OpenH264Encoder {
+    format: FrameFormat,
+
+    _library: Library,
+    destroy: ffi::DestroyEncoder,
+    handle: *mut ffi::ISVCEncoder,
+    initialized: bool,
+
+    frame_rate: f32,
+    frames_count: u64,
+}
+
+// SAFETY: the encoder is only accessed through exclusive references
+unsafe impl Send for OpenH264Encoder {}
+
+impl OpenH264Encoder {
+    fn set_option<T>(&mut self, option: c_int, value: &mut T) -> Result<(), CodecError> {
+        // SAFETY: the handle is a valid encoder and the value matches the option
+        let status = unsafe {
+            ((**self.handle).set_option)(self.handle, option, value as *mut T as *mut c_void)
+        };
+
+        match status {
+            0 => Ok(()),
+            status => Err(CodecError::InvalidConfiguration(format!(
+                "Unable to set option {} of the OpenH264 encoder (error {})",
+                option, status
+            ))),
+        }
+    }
+}
+
+impl Encoder for OpenH264Encoder {
+    fn input_format(&self) -> &FrameFormat {
+        &self.format
+    }
+
+    fn encode(&mut self, planes: &[&[u8]]) -> Result<Option<EncodedFrame>, CodecError> {
+        let layouts = self.format.planes();
+        if planes.len() != layouts.len()
+            || planes
+                .iter()
+                .zip(&layouts)
+                .any(|(plane, layout)| plane.len() < layout.size())
+        {
+            return Err(CodecError::EncodingFailed(
+                "The planes do not match the input format".to_string(),
+            ));
+        }
+
+        let mut source = ffi::SSourcePicture {
+            color_format: ffi::VIDEO_FORMAT_I420,
+            stride: [0; 4],
+            data: [ptr::null_mut(); 4],
+            width: self.format.get_width() as c_int,
+            height: self.format.get_height() as c_int,
+            timestamp: (self.frames_count as f64 * 1000.0 / self.frame_rate as f64) as i64,
+        };
+
+        for (index, (plane, layout)) in planes.iter().zip(&layouts).enumerate() {
+            source.stride[index] = layout.stride as c_int;
+
+            // The encoder does not write to the source picture
+            source.data[index] = plane.as_ptr() as *mut u8;
+        }
+
+        // SAFETY: the bitstream info is a plain C struct, valid when zeroed
+        let mut info: ffi::SFrameBSInfo = unsafe { std::mem::zeroed() };
+
+        // SAFETY: the planes cover the layouts declared in the source picture
+        let status = unsafe { ((**self.handle).encode_frame)(self.handle, &source, &mut info) };
+        if status != 0 {
+            return Err(CodecError::EncodingFailed(format!(
+                "OpenH264 error {}",
+                status
+            )));
+        }
+
+        self.frames_count += 1;
+
+        if info.frame_type == ffi::VIDEO_FRAME_TYPE_SKIP {
+            return Err(CodecError::EncodingFailed(
+                "Frame skipped by the rate control".to_string(),
+            ));
+        }
+
+        let mut data = Vec::with_capacity(info.frame_size.max(0) as usize);
+        for layer in &info.layers[..info.layer_count.max(0) as usize] {
+            // SAFETY: the encoder reports the length of each NAL unit of the layer, stored
+            // contiguously in its bitstream buffer, valid until the next call
+            let size: usize =
+                unsafe { std::slice::from_raw_parts(layer.nal_lengths, layer.nal_count as usize) }
+                    .iter()
+                    .map(|length| *length as usize)
+                    .sum();
+
+            data.extend_from_slice(unsafe { std::slice::from_raw_parts(layer.bitstream, size) });
+        }
+
+        let keyframe = matches!(
+            info.frame_type,
+            ffi::VIDEO_FRAME_TYPE_IDR | ffi::VIDEO_FRAME_TYPE_I
+        );
+
+        Ok(Some(EncodedFrame::new(data, keyframe)))
+    }
+
+    fn force_keyframe(&mut self) {
+        // SAFETY: the handle is a valid encoder
+        unsafe { ((**self.handle).force_intra_frame)(self.handle, true) };
+    }
+
+    fn set_bitrate(&mut self, bits_per_second: u32) -> Result<(), CodecError> {
+        let mut bitrate = ffi::SBitrateInfo {
+            layer: ffi::SPATIAL_LAYER_ALL,
+            bitrate: bits_per_second.min(c_int::MAX as u32) as c_int,
+        };
+
+        self.set_option(ffi::ENCODER_OPTION_BITRATE, &mut bitrate)
+    }
+}
+
+impl Drop for OpenH264Encoder {
+    fn drop(&mut self) {
+        // SAFETY: the handle is a valid encoder, not used afterwards
+        unsafe {
+            if self.initialized {
+                ((**self.handle).uninitialize)(self.handle);
+            }
+
+            (self.destroy)(self.handle);
+        }
+    }
+}
+
+/// H.264 software decoder based on a runtime-loaded OpenH264 library, producing I420 frames
+/// as soon as their data is received
+pub struct OpenH264Decoder {
+    format: FrameFormat,
+
+    _library: Library,
+    destroy: ffi::DestroyDecoder,
+    handle: *mut ffi::ISVCDecoder,
+    initialized: bool,
+}
+
+// SAFETY: the decoder is only accessed through exclusive references
+unsafe impl Send for OpenH264Decoder {}
+
+impl OpenH264Decoder {
+    /// Loads the library from the default locations
+    pub fn new(format: FrameFormat) -> Result<Self, CodecError> {
+        Self::build(format, None)
+    }
+
+    /// Loads the library from the given path
+    pub fn with_library(format: FrameFormat, path: impl AsRef<OsStr>) -> Result<Self, CodecError> {
+        Self::build(format, Some(path.as_ref()))
+    }
+
+    fn build(format: FrameFormat, path: Option<&OsStr>) -> Result<Self, CodecError> {
+        check_i420(&format)?;
+
+        let library = load_library(path)?;
+        let create: ffi::CreateDecoder = library::symbol(&library, "WelsCreateDecoder")?;
+        let destroy: ffi::DestroyDecoder = library::symbol(&library, "WelsDestroyDecoder")?;
+
+        let mut handle = ptr::null_mut();
+
+        // SAFETY: the function stores a new decoder in the pointer
+        if unsafe { create(&mut handle) } != 0 || handle.is_null() {
+            return Err(CodecError::InvalidConfiguration(
+                "Unable to create the OpenH264 decoder".to_string(),
+            ));
+        }
+
+        let mut decoder = Self {
+            format,
+            _library: library,
+            destroy,
+            handle,
+            initialized: false,
+        };
+
+        let parameters = ffi::SDecodingParam {
+            file_name_restructed: ptr::null_mut(),
+            cpu_load: 0,
+            target_dq_layer: ffi::TARGET_ALL_LAYERS,
+            error_concealment: 0,
+            parse_only: false,
+            video_property: ffi::SVideoProperty {
+                size: std::mem::size_of::<ffi::SVideoProperty>() as u32,
+                bitstream_type: ffi::VIDEO_BITSTREAM_AVC,
+            },
+        };
+
+        // SAFETY: the handle is a valid decoder and the parameters outlive the call
+        let status = unsafe { ((**decoder.handle).initialize)(decoder.handle, &parameters) };
+        if status != 0 {
+            return Err(CodecError::InvalidConfiguration(format!(
+                "Unable to initialize the OpenH264 decoder (error {})",
+                status
+            )));
+        }
+        decoder.initialized = true;
+
+        Ok(decoder)
+    }
+}
+
+impl Decoder for OpenH264Decoder {
+    fn output_format(&self) -> &FrameFormat {
+        &self.format
+    }
+
+    fn decode(&mut self, data: &[u8], planes: &mut [&mut [u8]]) -> Result<bool, CodecError> {
+        let layouts = self.format.planes();
+        if planes.len() != layouts.len()
+            || planes
+                .iter()
+                .zip(&layouts)
+                .any(|(plane, layout)| plane.len() < layout.size())
+        {
+            return Err(CodecError::DecodingFailed(
+                "The planes do not match the output format".to_string(),
+            ));
+        }
+
+        let size = c_int::try_from(data.len())
+            .map_err(|_| CodecError::DecodingFailed("Encoded frame too large".to_string()))?;
+
+        let mut destination = [ptr::null_mut(); 3];
+
+        // SAFETY: the buffer info is a plain C struct, valid when zeroed
+        let mut info: ffi::SBufferInfo = unsafe { std::mem::zeroed() };
+
+        // SAFETY: the handle is a valid decoder and the data outlives the call
+        let state = unsafe {
+            ((**self.handle).decode_frame_no_delay)(
+                self.handle,
+                data.as_ptr(),
+                size,
+                destination.as_mut_ptr(),
+                &mut info,
+            )
+        };
+
+        if state != 0 {
+            return Err(CodecError::DecodingFailed(format!(
+                "OpenH264 decoding state {:#x}",
+                state
+            )));
+        }
+
+        if info.buffer_status != 1 {
+            return Ok(false);
+        }
+
+        let decoded_size = (info.system_buffer.width, info.system_buffer.height);
+        let (width, height) = (self.format.get_width(), self.format.get_height());
+        if decoded_size != (width as c_int, height as c_int) {
+            return Err(CodecError::DecodingFailed(format!(
+                "Decoded a {}x{} frame, expected {}x{}",
+                decoded_size.0, decoded_size.1, width, height
+            )));
+        }
+
+        let strides = [
+            info.system_buffer.stride[0],
+            info.system_buffer.stride[1],
+            info.system_buffer.stride[1],
+        ];
+
+        for (((plane, layout), source), stride) in
+            planes.iter_mut().zip(layouts).zip(info.dst).zip(strides)
+        {
+            // SAFETY: the decoded frame has the size of the output format
+            unsafe { library::copy_plane(source, stride as usize, layout, plane) };
+        }
+
+        Ok(true)
+    }
+}
+
+impl Drop for OpenH264Decoder {
+    fn drop(&mut self) {
+        // SAFETY: the handle is a valid decoder, not used afterwards
+        unsafe {
+            if self.initialized {
+                ((**self.handle).uninitialize)(self.handle);
+            }
+
+            (self.destroy)(self.handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codec::{Decoder, Encoder},
+        pixel_format::{FrameFormat, PixelFormat},
+    };
+
+    use super::{OpenH264Decoder, OpenH264EncoderConfig};
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 48;
+
+    fn format() -> FrameFormat {
+        FrameFormat::new(PixelFormat::I420, WIDTH, HEIGHT)
+    }
+
+    #[test]
+    fn missing_library_is_reported() {
+        let encoder = OpenH264EncoderConfig::new(format())
+            .library("/nonexistent/libopenh264.so")
+            .build();
+        assert!(encoder.is_err());
+
+        let decoder = OpenH264Decoder::with_library(format(), "/nonexistent/libopenh264.so");
+        assert!(decoder.is_err());
+    }
+
+    #[test]
+    fn non_i420_formats_are_rejected() {
+        let format = FrameFormat::new(PixelFormat::BGRA, WIDTH, HEIGHT);
+        assert!(OpenH264EncoderConfig::new(format.clone()).build().is_err());
+        assert!(OpenH264Decoder::new(format).is_err());
+    }
+
+    #[test]
+    #[ignore = "requires the OpenH264 library"]
+    fn encoded_frames_are_decoded() {
+        let mut encoder = OpenH264EncoderConfig::new(format()).build().unwrap();
+        let mut decoder = OpenH264Decoder::new(format()).unwrap();
+
+        let layouts = format().planes();
+        let source: Vec<Vec<u8>> = layouts
+            .iter()
+            .enumerate()
+            .map(|(index, layout)| {
+                (0..layout.size())
+                    .map(|i| (i % layout.stride * 2 + index * 40) as u8)
+                    .collect()
+            })
+            .collect();
+        let source_planes: Vec<&[u8]> = source.iter().map(Vec::as_slice).collect();
+
+        let mut decoded: Vec<Vec<u8>> = layouts
+            .iter()
+            .map(|layout| vec![0; layout.size()])
+            .collect();
+
+        for index in 0..3 {
+            let encoded_frame = encoder.encode(&source_planes).unwrap().unwrap();
+            assert_eq!(encoded_frame.keyframe, index == 0);
+
+            let mut planes: Vec<&mut [u8]> = decoded.iter_mut().map(Vec::as_mut_slice).collect();
+            assert!(decoder.decode(&encoded_frame.data, &mut planes).unwrap());
+
+            for (source, decoded) in source.iter().zip(&decoded) {
+                let max_error = source
+                    .iter()
+                    .zip(decoded)
+                    .map(|(source, decoded)| source.abs_diff(*decoded))
+                    .max()
+                    .unwrap();
+
+                assert!(max_error <= 16, "Error of {} in frame {}", max_error, index);
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fmt, str::FromStr};
+
+use remotia_core::{error::ProcessorError, types::FrameData};
+
+pub const COLOR_MATRIX_STAT: &str = "color_matrix";
+pub const COLOR_RANGE_STAT: &str = "color_range";
+
+/// Luma coefficients used to derive Y, Cb and Cr from RGB
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMatrix {
+    #[default]
+    BT601,
+    BT709,
+    BT2020,
+}
+
+/// Full range uses all the 0-255 values, limited range restricts luma to 16-235 and chroma to
+/// 16-240 as expected by most hardware decoders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorRange {
+    #[default]
+    Full,
+    Limited,
+}
+
+impl ColorMatrix {
+    /// Red and blue luma coefficients, the green one being their complement to one
+    pub(crate) fn coefficients(&self) -> (f32, f32) {
+        match self {
+            ColorMatrix::BT601 => (0.299, 0.114),
+            ColorMatrix::BT709 => (0.2126, 0.0722),
+            ColorMatrix::BT2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+impl fmt::Display for ColorMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorMatrix::BT601 => write!(f, "bt601"),
+            ColorMatrix::BT709 => write!(f, "bt709"),
+            ColorMatrix::BT2020 => write!(f, "bt2020"),
+        }
+    }
+}
+
+impl FromStr for ColorMatrix {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bt601" => Ok(ColorMatrix::BT601),
+            "bt709" => Ok(ColorMatrix::BT709),
+            "bt2020" => Ok(ColorMatrix::BT2020),
+            _ => Err(format!("Unknown color matrix '{}'", value)),
+        }
+    }
+}
+
+impl fmt::Display for ColorRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorRange::Full => write!(f, "full"),
+            ColorRange::Limited => write!(f, "limited"),
+        }
+    }
+}
+
+impl FromStr for ColorRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "full" => Ok(ColorRange::Full),
+            "limited" => Ok(ColorRange::Limited),
+            _ => Err(format!("Unknown color range '{}'", value)),
+        }
+    }
+}
+
+/// Color matrix and range of a YCbCr frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ColorSpace {
+    pub matrix: ColorMatrix,
+    pub range: ColorRange,
+}
+
+impl ColorSpace {
+    pub fn new(matrix: ColorMatrix, range: ColorRange) -> Self {
+        Self { matrix, range }
+    }
+
+    /// Records the color space in the stats of the frame
+    pub fn write_stats(&self, frame_data: &mut FrameData) {
+        frame_data.set_value(COLOR_MATRIX_STAT, self.matrix.to_string());
+        frame_data.set_value(COLOR_RANGE_STAT, self.range.to_string());
+    }
+
+    /// Reads the color space recorded in the stats of the frame, falling back to this one for
+    /// the missing parameters
+    pub fn read_stats(&self, frame_data: &FrameData) -> Result<Self, ProcessorError> {
+        let mut color_space = *self;
+
+        if frame_data.try_get_value(COLOR_MATRIX_STAT).is_ok() {
+            color_space.matrix = frame_data
+                .try_get_text(COLOR_MATRIX_STAT)?
+                .parse()
+                .map_err(ProcessorError::Other)?;
+        }
+
+        if frame_data.try_get_value(COLOR_RANGE_STAT).is_ok() {
+            color_space.range = frame_data
+                .try_get_text(COLOR_RANGE_STAT)?
+                .parse()
+                .map_err(ProcessorError::Other)?;
+        }
+
+        Ok(color_space)
+    }
+
+    /// Converts a BGR pixel to unrounded Y, Cb and Cr values
+    pub fn bgr_to_yuv(&self, b: u8, g: u8, r: u8) -> (f32, f32, f32) {
+        let (kr, kb) = self.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+
+        let y = kr * r + kg * g + kb * b;
+        let u = (b - y) / (2.0 * (1.0 - kb));
+        let v = (r - y) / (2.0 * (1.0 - kr));
+
+        match self.range {
+            ColorRange::Full => (y, u + 128.0, v + 128.0),
+            ColorRange::Limited => (
+                16.0 + y * (219.0 / 255.0),
+                128.0 + u * (224.0 / 255.0),
+                128.0 + v * (224.0 / 255.0),
+            ),
+        }
+    }
+
+    /// Converts Y, Cb and Cr values to a clamped BGR pixel
+    pub fn yuv_to_bgr(&self, y: u8, u: u8, v: u8) -> (u8, u8, u8) {
+        let (kr, kb) = self.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+
+        let (y, u, v) = match self.range {
+            ColorRange::Full => (y as f32, u as f32 - 128.0, v as f32 - 128.0),
+            ColorRange::Limited => (
+                (y as f32 - 16.0) * (255.0 / 219.0),
+                (u as f32 - 128.0) * (255.0 / 224.0),
+                (v as f32 - 128.0) * (255.0 / 224.0),
+            ),
+        };
+
+        let r = y + v * 2.0 * (1.0 - kr);
+        let b = y + u * 2.0 * (1.0 - kb);
+        let g = (y - kr * r - kb * b) / kg;
+
+        (clamp_channel(b), clamp_channel(g), clamp_channel(r))
+    }
+}
+
+pub(crate) fn clamp_channel(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
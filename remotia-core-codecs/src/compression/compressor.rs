@@ -0,0 +1,133 @@
+use async_trait::async_trait;
+use bytes::BytesMut;
+use log::debug;
+use remotia_core::{
+    error::{CodecError, DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use super::{
+    Backend, CompressionAlgorithm, COMPRESSED_FRAME_BUFFER, COMPRESSED_SIZE_STAT,
+    COMPRESSION_RATIO_STAT,
+};
+
+/// Losslessly compresses a buffer into another one, storing the compressed size and the
+/// compression ratio (uncompressed over compressed size) in the "compressed_size" and
+/// "compression_ratio" stats.
+///
+/// The destination buffer is allocated if not already present in the frame DTO, in which
+/// case it is sized for the worst case. Compression failures mark the frame with
+/// [`DropReason::CodecError`].
+pub struct BufferCompressor {
+    backend: Backend,
+
+    source_buffer_id: String,
+    source_size_stat_id: Option<String>,
+    destination_buffer_id: String,
+
+    size_stat_id: String,
+    ratio_stat_id: String,
+}
+
+impl BufferCompressor {
+    pub fn new(algorithm: CompressionAlgorithm) -> Result<Self, CodecError> {
+        Ok(Self {
+            backend: Backend::new(algorithm)?,
+            source_buffer_id: "raw_frame_buffer".to_string(),
+            source_size_stat_id: None,
+            destination_buffer_id: COMPRESSED_FRAME_BUFFER.to_string(),
+            size_stat_id: COMPRESSED_SIZE_STAT.to_string(),
+            ratio_stat_id: COMPRESSION_RATIO_STAT.to_string(),
+        })
+    }
+
+    pub fn source_buffer(mut self, buffer_id: &str) -> Self {
+        self.source_buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat holding the size of the data in the source buffer, when it is not entirely used
+    pub fn source_size_stat(mut self, stat_id: &str) -> Self {
+        self.source_size_stat_id = Some(stat_id.to_string());
+        self
+    }
+
+    pub fn destination_buffer(mut self, buffer_id: &str) -> Self {
+        self.destination_buffer_id = buffer_id.to_string();
+        self
+    }
+
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = stat_id.to_string();
+        self
+    }
+
+    pub fn ratio_stat(mut self, stat_id: &str) -> Self {
+        self.ratio_stat_id = stat_id.to_string();
+        self
+    }
+
+    fn source_size(&self, frame_data: &mut FrameData) -> Result<usize, ProcessorError> {
+        let found = frame_data
+            .get_writable_buffer_ref(&self.source_buffer_id)
+            .ok_or_else(|| ProcessorError::MissingBuffer(self.source_buffer_id.clone()))?
+            .len();
+
+        let size = match &self.source_size_stat_id {
+            Some(stat_id) => frame_data.try_get(stat_id)? as usize,
+            None => return Ok(found),
+        };
+
+        if found < size {
+            return Err(ProcessorError::BufferTooSmall {
+                key: self.source_buffer_id.clone(),
+                required: size,
+                found,
+            });
+        }
+
+        Ok(size)
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for BufferCompressor {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let size = match self.source_size(&mut frame_data) {
+            Ok(size) => size,
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        let source_buffer = frame_data
+            .extract_writable_buffer(&self.source_buffer_id)
+            .unwrap();
+
+        let mut destination_buffer = frame_data
+            .extract_writable_buffer(&self.destination_buffer_id)
+            .unwrap_or_else(|| BytesMut::zeroed(self.backend.max_compressed_size(size)));
+
+        let result = self
+            .backend
+            .compress(&source_buffer[..size], &mut destination_buffer);
+
+        frame_data.insert_writable_buffer(&self.source_buffer_id, source_buffer);
+        frame_data.insert_writable_buffer(&self.destination_buffer_id, destination_buffer);
+
+        match result {
+            Ok(compressed_size) => {
+                frame_data.set(&self.size_stat_id, compressed_size as u128);
+                frame_data.set_value(
+                    &self.ratio_stat_id,
+                    size as f64 / compressed_size.max(1) as f64,
+                );
+            }
+            Err(error) => {
+                debug!("Unable to compress buffer: {}", error);
+                frame_data.set_drop_reason(Some(DropReason::CodecError));
+            }
+        }
+
+        Ok(Some(frame_data))
+    }
+}
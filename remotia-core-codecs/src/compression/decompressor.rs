@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use log::debug;
+use remotia_core::{
+    error::{CodecError, DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use super::{
+    Backend, CompressionAlgorithm, COMPRESSED_FRAME_BUFFER, COMPRESSED_SIZE_STAT,
+    DECOMPRESSED_SIZE_STAT,
+};
+
+/// Decompresses the content of a buffer, whose size is read from the "compressed_size" stat,
+/// into another one which must be already present in the frame DTO. The size of the
+/// decompressed data is stored in the "decompressed_size" stat.
+///
+/// Decompression failures, including a destination buffer too small to hold the data, mark
+/// the frame with [`DropReason::CodecError`].
+pub struct BufferDecompressor {
+    backend: Backend,
+
+    source_buffer_id: String,
+    source_size_stat_id: String,
+    destination_buffer_id: String,
+
+    size_stat_id: String,
+}
+
+impl BufferDecompressor {
+    pub fn new(algorithm: CompressionAlgorithm) -> Result<Self, CodecError> {
+        Ok(Self {
+            backend: Backend::new(algorithm)?,
+            source_buffer_id: COMPRESSED_FRAME_BUFFER.to_string(),
+            source_size_stat_id: COMPRESSED_SIZE_STAT.to_string(),
+            destination_buffer_id: "raw_frame_buffer".to_string(),
+            size_stat_id: DECOMPRESSED_SIZE_STAT.to_string(),
+        })
+    }
+
+    pub fn source_buffer(mut self, buffer_id: &str) -> Self {
+        self.source_buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat holding the size of the compressed data
+    pub fn source_size_stat(mut self, stat_id: &str) -> Self {
+        self.source_size_stat_id = stat_id.to_string();
+        self
+    }
+
+    pub fn destination_buffer(mut self, buffer_id: &str) -> Self {
+        self.destination_buffer_id = buffer_id.to_string();
+        self
+    }
+
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = stat_id.to_string();
+        self
+    }
+
+    fn source_size(&self, frame_data: &mut FrameData) -> Result<usize, ProcessorError> {
+        let size = frame_data.try_get(&self.source_size_stat_id)? as usize;
+
+        let found = frame_data
+            .get_writable_buffer_ref(&self.source_buffer_id)
+            .ok_or_else(|| ProcessorError::MissingBuffer(self.source_buffer_id.clone()))?
+            .len();
+
+        if found < size {
+            return Err(ProcessorError::BufferTooSmall {
+                key: self.source_buffer_id.clone(),
+                required: size,
+                found,
+            });
+        }
+
+        Ok(size)
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for BufferDecompressor {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let size = match self.source_size(&mut frame_data) {
+            Ok(size) => size,
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        let result = frame_data.with_writable_buffers(
+            &[&self.source_buffer_id, &self.destination_buffer_id],
+            |buffers| {
+                let [source_buffer, destination_buffer] = buffers else {
+                    unreachable!()
+                };
+
+                self.backend
+                    .decompress(&source_buffer[..size], destination_buffer)
+            },
+        );
+
+        match result {
+            Ok(Ok(decompressed_size)) => {
+                frame_data.set(&self.size_stat_id, decompressed_size as u128)
+            }
+            Ok(Err(error)) => {
+                debug!("Unable to decompress buffer: {}", error);
+                frame_data.set_drop_reason(Some(DropReason::CodecError));
+            }
+            Err(error) => return Err((frame_data, error)),
+        }
+
+        Ok(Some(frame_data))
+    }
+}
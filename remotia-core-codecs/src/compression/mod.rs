@@ -0,0 +1,179 @@
+use remotia_core::error::CodecError;
+
+pub mod compressor;
+pub mod decompressor;
+
+pub const COMPRESSED_FRAME_BUFFER: &str = "compressed_frame_buffer";
+
+pub const COMPRESSED_SIZE_STAT: &str = "compressed_size";
+pub const COMPRESSION_RATIO_STAT: &str = "compression_ratio";
+pub const DECOMPRESSED_SIZE_STAT: &str = "decompressed_size";
+
+/// Lossless general-purpose compression algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    /// Zstandard with the given level, from 1 (fastest) to 22, 0 selecting the default one
+    Zstd { level: i32 },
+
+    /// LZ4 block format, without size prefix
+    Lz4,
+}
+
+impl Default for CompressionAlgorithm {
+    fn default() -> Self {
+        Self::Zstd { level: 1 }
+    }
+}
+
+/// Compression contexts kept across frames
+enum Backend {
+    Zstd {
+        compressor: zstd::bulk::Compressor<'static>,
+        decompressor: zstd::bulk::Decompressor<'static>,
+    },
+    Lz4,
+}
+
+impl Backend {
+    fn new(algorithm: CompressionAlgorithm) -> Result<Self, CodecError> {
+        match algorithm {
+            CompressionAlgorithm::Zstd { level } => Ok(Self::Zstd {
+                compressor: zstd::bulk::Compressor::new(level)
+                    .map_err(|error| CodecError::InvalidConfiguration(error.to_string()))?,
+                decompressor: zstd::bulk::Decompressor::new()
+                    .map_err(|error| CodecError::InvalidConfiguration(error.to_string()))?,
+            }),
+            CompressionAlgorithm::Lz4 => Ok(Self::Lz4),
+        }
+    }
+
+    /// Worst-case size of the compressed data
+    fn max_compressed_size(&self, size: usize) -> usize {
+        match self {
+            Self::Zstd { .. } => zstd::zstd_safe::compress_bound(size),
+            Self::Lz4 => lz4_flex::block::get_maximum_output_size(size),
+        }
+    }
+
+    fn compress(&mut self, source: &[u8], destination: &mut [u8]) -> Result<usize, CodecError> {
+        match self {
+            Self::Zstd { compressor, .. } => compressor
+                .compress_to_buffer(source, destination)
+                .map_err(|error| CodecError::EncodingFailed(error.to_string())),
+            Self::Lz4 => lz4_flex::block::compress_into(source, destination)
+                .map_err(|error| CodecError::EncodingFailed(error.to_string())),
+        }
+    }
+
+    fn decompress(&mut self, source: &[u8], destination: &mut [u8]) -> Result<usize, CodecError> {
+        match self {
+            Self::Zstd { decompressor, .. } => decompressor
+                .decompress_to_buffer(source, destination)
+                .map_err(|error| CodecError::DecodingFailed(error.to_string())),
+            Self::Lz4 => lz4_flex::block::decompress_into(source, destination)
+                .map_err(|error| CodecError::DecodingFailed(error.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use remotia_core::{error::DropReason, traits::FrameProcessor, types::FrameData};
+
+    use super::{
+        compressor::BufferCompressor, decompressor::BufferDecompressor, CompressionAlgorithm,
+        COMPRESSED_SIZE_STAT, COMPRESSION_RATIO_STAT, DECOMPRESSED_SIZE_STAT,
+    };
+
+    const ALGORITHMS: [CompressionAlgorithm; 3] = [
+        CompressionAlgorithm::Zstd { level: 0 },
+        CompressionAlgorithm::Zstd { level: 19 },
+        CompressionAlgorithm::Lz4,
+    ];
+
+    /// Compressible content, followed by garbage past the given size
+    fn frame(size: usize) -> Vec<u8> {
+        let mut frame: Vec<u8> = (0..size).map(|i| (i / 16 % 7) as u8).collect();
+        frame.extend([0xAB; 100]);
+        frame
+    }
+
+    #[tokio::test]
+    async fn buffers_survive_a_round_trip() {
+        let size = 10_000;
+        let original = frame(size);
+
+        for algorithm in ALGORITHMS {
+            let mut frame_data = FrameData::default();
+            frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::from(&original[..]));
+            frame_data.set("used_size", size as u128);
+
+            let mut compressor = BufferCompressor::new(algorithm)
+                .unwrap()
+                .source_size_stat("used_size");
+            let mut frame_data = compressor.try_process(frame_data).await.unwrap().unwrap();
+
+            assert_eq!(frame_data.get_drop_reason(), None);
+            let compressed_size = frame_data.get(COMPRESSED_SIZE_STAT) as usize;
+            assert!(compressed_size < size, "{:?} did not compress", algorithm);
+            assert_eq!(
+                frame_data.get_float(COMPRESSION_RATIO_STAT),
+                size as f64 / compressed_size as f64
+            );
+
+            frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::zeroed(size));
+
+            let mut decompressor = BufferDecompressor::new(algorithm).unwrap();
+            let mut frame_data = decompressor.try_process(frame_data).await.unwrap().unwrap();
+
+            assert_eq!(frame_data.get_drop_reason(), None);
+            assert_eq!(frame_data.get(DECOMPRESSED_SIZE_STAT), size as u128);
+            assert_eq!(
+                &frame_data
+                    .get_writable_buffer_ref("raw_frame_buffer")
+                    .unwrap()[..],
+                &original[..size],
+                "{:?} round trip differs",
+                algorithm
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn too_small_destinations_drop_the_frame() {
+        let size = 10_000;
+
+        for algorithm in ALGORITHMS {
+            let mut frame_data = FrameData::default();
+            frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::from(&frame(size)[..]));
+
+            let mut compressor = BufferCompressor::new(algorithm).unwrap();
+            let mut frame_data = compressor.try_process(frame_data).await.unwrap().unwrap();
+            assert_eq!(frame_data.get_drop_reason(), None);
+
+            frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::zeroed(size / 2));
+
+            let mut decompressor = BufferDecompressor::new(algorithm).unwrap();
+            let frame_data = decompressor.try_process(frame_data).await.unwrap().unwrap();
+            assert_eq!(
+                frame_data.get_drop_reason(),
+                Some(DropReason::CodecError),
+                "{:?} decompressed into a too small buffer",
+                algorithm
+            );
+
+            let mut frame_data = FrameData::default();
+            frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::from(&frame(size)[..]));
+            frame_data.insert_writable_buffer("compressed_frame_buffer", BytesMut::zeroed(4));
+
+            let frame_data = compressor.try_process(frame_data).await.unwrap().unwrap();
+            assert_eq!(
+                frame_data.get_drop_reason(),
+                Some(DropReason::CodecError),
+                "{:?} compressed into a too small buffer",
+                algorithm
+            );
+        }
+    }
+}
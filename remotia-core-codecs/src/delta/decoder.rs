@@ -0,0 +1,201 @@
+use async_trait::async_trait;
+use log::debug;
+use remotia_core::{
+    error::{CodecError, DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use crate::pixel_format::FrameFormat;
+
+use super::{
+    TileGrid, DEFAULT_TILE_SIZE, FULL_FRAME_FLAG, HEADER_SIZE, TILE_DELTA_BUFFER,
+    TILE_DELTA_SIZE_STAT, TILE_HEADER_SIZE,
+};
+
+/// Tile of a delta, along with the offset of its payload
+struct DeltaTile {
+    column: usize,
+    row: usize,
+    offset: usize,
+}
+
+/// Patches the previously decoded frame with the tiles of the "tile_delta_buffer", whose size
+/// is read from the "tile_delta_size" stat, and writes the result in the frame buffer.
+///
+/// Deltas received before the first full frame are marked with
+/// [`DropReason::NoDecodedFrames`] and malformed ones with [`DropReason::CodecError`]. The
+/// tile size must match the one of the encoder.
+pub struct TileDeltaDecoder {
+    format: FrameFormat,
+    grid: TileGrid,
+
+    buffer_id: String,
+    size_stat_id: String,
+
+    frame: Option<Vec<u8>>,
+}
+
+impl TileDeltaDecoder {
+    pub fn new(format: FrameFormat) -> Self {
+        Self {
+            grid: TileGrid::new(&format, DEFAULT_TILE_SIZE),
+            format,
+            buffer_id: TILE_DELTA_BUFFER.to_string(),
+            size_stat_id: TILE_DELTA_SIZE_STAT.to_string(),
+            frame: None,
+        }
+    }
+
+    /// Side of the square tiles, in pixels
+    pub fn tile_size(mut self, tile_size: usize) -> Self {
+        self.grid = TileGrid::new(&self.format, tile_size);
+        self
+    }
+
+    /// Buffer from which the delta is read
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat holding the size of the delta
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = stat_id.to_string();
+        self
+    }
+
+    fn delta_size(&self, frame_data: &mut FrameData) -> Result<usize, ProcessorError> {
+        let size = frame_data.try_get(&self.size_stat_id)? as usize;
+
+        let found = frame_data
+            .get_writable_buffer_ref(&self.buffer_id)
+            .ok_or_else(|| ProcessorError::MissingBuffer(self.buffer_id.clone()))?
+            .len();
+
+        if found < size {
+            return Err(ProcessorError::BufferTooSmall {
+                key: self.buffer_id.clone(),
+                required: size,
+                found,
+            });
+        }
+
+        Ok(size)
+    }
+
+    /// Validates the delta, returning whether it is a full frame along with the position of
+    /// each tile
+    fn parse(&self, delta: &[u8]) -> Result<(bool, Vec<DeltaTile>), CodecError> {
+        let malformed = |reason: &str| CodecError::DecodingFailed(format!("{} delta", reason));
+
+        if delta.len() < HEADER_SIZE {
+            return Err(malformed("Truncated"));
+        }
+
+        let full_frame = delta[0] & FULL_FRAME_FLAG != 0;
+        let count = u32::from_le_bytes(delta[1..HEADER_SIZE].try_into().unwrap()) as usize;
+        if count > self.grid.count() {
+            return Err(malformed("Oversized"));
+        }
+
+        let mut tiles = Vec::with_capacity(count);
+        let mut offset = HEADER_SIZE;
+        for _ in 0..count {
+            let header = delta
+                .get(offset..offset + TILE_HEADER_SIZE)
+                .ok_or_else(|| malformed("Truncated"))?;
+            let column = u16::from_le_bytes([header[0], header[1]]) as usize;
+            let row = u16::from_le_bytes([header[2], header[3]]) as usize;
+
+            if column >= self.grid.columns || row >= self.grid.rows {
+                return Err(malformed("Out of bounds tile in"));
+            }
+
+            offset += TILE_HEADER_SIZE;
+            tiles.push(DeltaTile {
+                column,
+                row,
+                offset,
+            });
+            offset += self.grid.payload_size(&self.grid.rect(column, row));
+        }
+
+        if offset > delta.len() {
+            return Err(malformed("Truncated"));
+        }
+
+        Ok((full_frame, tiles))
+    }
+
+    fn patch(&mut self, delta: &[u8], tiles: &[DeltaTile]) {
+        let grid = self.grid;
+        let frame = self
+            .frame
+            .get_or_insert_with(|| vec![0; self.format.planes()[0].size()]);
+
+        for tile in tiles {
+            let mut offset = tile.offset;
+            for range in grid.row_ranges(&grid.rect(tile.column, tile.row)) {
+                let row_size = range.len();
+                frame[range].copy_from_slice(&delta[offset..offset + row_size]);
+                offset += row_size;
+            }
+        }
+    }
+
+    fn decode(&mut self, delta: &[u8]) -> Result<bool, CodecError> {
+        let (full_frame, tiles) = self.parse(delta)?;
+
+        if !full_frame && self.frame.is_none() {
+            return Ok(false);
+        }
+
+        self.patch(delta, &tiles);
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for TileDeltaDecoder {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let size = match self.delta_size(&mut frame_data) {
+            Ok(size) => size,
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        if let Err(error) = self.format.check_buffers(&mut frame_data) {
+            return Err((frame_data, error));
+        }
+
+        let keys = [self.buffer_id.clone(), self.format.get_buffers()[0].clone()];
+        let result = frame_data.with_writable_buffers(&[&keys[0], &keys[1]], |buffers| {
+            let [delta, frame_buffer] = buffers else {
+                unreachable!()
+            };
+
+            let decoded = self.decode(&delta[..size]);
+            if let Ok(true) = decoded {
+                let frame = self.frame.as_ref().unwrap();
+                frame_buffer[..frame.len()].copy_from_slice(frame);
+            }
+
+            decoded
+        });
+
+        match result {
+            Ok(Ok(true)) => {}
+            Ok(Ok(false)) => {
+                debug!("Delta received before the first full frame");
+                frame_data.set_drop_reason(Some(DropReason::NoDecodedFrames));
+            }
+            Ok(Err(error)) => {
+                debug!("Unable to decode delta: {}", error);
+                frame_data.set_drop_reason(Some(DropReason::CodecError));
+            }
+            Err(error) => return Err((frame_data, error)),
+        }
+
+        Ok(Some(frame_data))
+    }
+}
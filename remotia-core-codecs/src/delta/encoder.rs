@@ -0,0 +1,207 @@
+use async_trait::async_trait;
+use bytes::BytesMut;
+use log::debug;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use remotia_core::{
+    error::{DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use crate::{codec::KEYFRAME_STAT, pixel_format::FrameFormat};
+
+use super::{
+    TileGrid, CHANGED_TILES_RATIO_STAT, CHANGED_TILES_STAT, DEFAULT_TILE_SIZE, FULL_FRAME_FLAG,
+    HEADER_SIZE, STATIC_FRAME_STAT, TILE_DELTA_BUFFER, TILE_DELTA_SIZE_STAT, TILE_HEADER_SIZE,
+};
+
+/// Compares each packed frame against the previous one, writing the tiles which changed in the
+/// "tile_delta_buffer". The size of the delta is stored in the "tile_delta_size" stat, along
+/// with the "changed_tiles", "changed_tiles_ratio", "static_frame" and "keyframe" stats.
+///
+/// The first frame, and one every keyframe interval if set, carries all the tiles. Frames
+/// without any changed tile can be marked with [`DropReason::StaticFrame`] to skip them.
+pub struct TileDeltaEncoder {
+    format: FrameFormat,
+    grid: TileGrid,
+
+    buffer_id: String,
+    size_stat_id: String,
+
+    keyframe_interval: Option<usize>,
+    drop_static_frames: bool,
+
+    previous_frame: Option<Vec<u8>>,
+    frames_since_keyframe: usize,
+}
+
+impl TileDeltaEncoder {
+    pub fn new(format: FrameFormat) -> Self {
+        Self {
+            grid: TileGrid::new(&format, DEFAULT_TILE_SIZE),
+            format,
+            buffer_id: TILE_DELTA_BUFFER.to_string(),
+            size_stat_id: TILE_DELTA_SIZE_STAT.to_string(),
+            keyframe_interval: None,
+            drop_static_frames: false,
+            previous_frame: None,
+            frames_since_keyframe: 0,
+        }
+    }
+
+    /// Side of the square tiles, in pixels
+    pub fn tile_size(mut self, tile_size: usize) -> Self {
+        self.grid = TileGrid::new(&self.format, tile_size);
+        self
+    }
+
+    /// Buffer in which the delta is written. The buffer is allocated if not already present
+    /// in the frame DTO, e.g. by a buffers pool.
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = stat_id.to_string();
+        self
+    }
+
+    /// Number of frames after which all the tiles are sent again, letting the decoder
+    /// recover from lost deltas
+    pub fn keyframe_interval(mut self, interval: usize) -> Self {
+        self.keyframe_interval = Some(interval);
+        self
+    }
+
+    pub fn drop_static_frames(mut self, drop_static_frames: bool) -> Self {
+        self.drop_static_frames = drop_static_frames;
+        self
+    }
+
+    fn is_keyframe(&self) -> bool {
+        match (&self.previous_frame, self.keyframe_interval) {
+            (None, _) => true,
+            (Some(_), Some(interval)) => self.frames_since_keyframe >= interval,
+            (Some(_), None) => false,
+        }
+    }
+
+    fn changed_tiles(&self, frame: &[u8], keyframe: bool) -> Vec<(usize, usize)> {
+        let grid = self.grid;
+        let previous_frame = self.previous_frame.as_deref();
+
+        (0..grid.count())
+            .into_par_iter()
+            .map(|index| (index % grid.columns, index / grid.columns))
+            .filter(|&(column, row)| match previous_frame {
+                Some(previous_frame) if !keyframe => grid
+                    .row_ranges(&grid.rect(column, row))
+                    .any(|range| frame[range.clone()] != previous_frame[range]),
+                _ => true,
+            })
+            .collect()
+    }
+
+    fn write_delta(
+        &self,
+        frame: &[u8],
+        tiles: &[(usize, usize)],
+        keyframe: bool,
+        delta: &mut [u8],
+    ) {
+        delta[0] = if keyframe { FULL_FRAME_FLAG } else { 0 };
+        delta[1..HEADER_SIZE].copy_from_slice(&(tiles.len() as u32).to_le_bytes());
+
+        let mut offset = HEADER_SIZE;
+        for &(column, row) in tiles {
+            delta[offset..offset + 2].copy_from_slice(&(column as u16).to_le_bytes());
+            delta[offset + 2..offset + 4].copy_from_slice(&(row as u16).to_le_bytes());
+            offset += TILE_HEADER_SIZE;
+
+            for range in self.grid.row_ranges(&self.grid.rect(column, row)) {
+                let row_size = range.len();
+                delta[offset..offset + row_size].copy_from_slice(&frame[range]);
+                offset += row_size;
+            }
+        }
+    }
+
+    fn delta_size(&self, tiles: &[(usize, usize)]) -> usize {
+        HEADER_SIZE
+            + tiles
+                .iter()
+                .map(|&(column, row)| {
+                    TILE_HEADER_SIZE + self.grid.payload_size(&self.grid.rect(column, row))
+                })
+                .sum::<usize>()
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for TileDeltaEncoder {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        if let Err(error) = self.format.check_buffers(&mut frame_data) {
+            return Err((frame_data, error));
+        }
+
+        let frame_key = &self.format.get_buffers()[0];
+        let frame_size = self.format.planes()[0].size();
+        let frame = frame_data.extract_writable_buffer(frame_key).unwrap();
+
+        let keyframe = self.is_keyframe();
+        let tiles = self.changed_tiles(&frame[..frame_size], keyframe);
+        let delta_size = self.delta_size(&tiles);
+
+        let mut delta = match frame_data.extract_writable_buffer(&self.buffer_id) {
+            Some(delta) if delta.len() < delta_size => {
+                let found = delta.len();
+                frame_data.insert_writable_buffer(frame_key, frame);
+                frame_data.insert_writable_buffer(&self.buffer_id, delta);
+
+                return Err((
+                    frame_data,
+                    ProcessorError::BufferTooSmall {
+                        key: self.buffer_id.clone(),
+                        required: delta_size,
+                        found,
+                    },
+                ));
+            }
+            Some(delta) => delta,
+            None => BytesMut::zeroed(self.grid.max_delta_size()),
+        };
+
+        self.write_delta(&frame[..frame_size], &tiles, keyframe, &mut delta);
+
+        match &mut self.previous_frame {
+            Some(previous_frame) => previous_frame.copy_from_slice(&frame[..frame_size]),
+            None => self.previous_frame = Some(frame[..frame_size].to_vec()),
+        }
+
+        if keyframe {
+            self.frames_since_keyframe = 0;
+        }
+        self.frames_since_keyframe += 1;
+
+        frame_data.insert_writable_buffer(frame_key, frame);
+        frame_data.insert_writable_buffer(&self.buffer_id, delta);
+
+        let static_frame = tiles.is_empty();
+        frame_data.set(&self.size_stat_id, delta_size as u128);
+        frame_data.set(CHANGED_TILES_STAT, tiles.len() as u128);
+        frame_data.set_value(
+            CHANGED_TILES_RATIO_STAT,
+            tiles.len() as f64 / self.grid.count().max(1) as f64,
+        );
+        frame_data.set_value(STATIC_FRAME_STAT, static_frame);
+        frame_data.set_value(KEYFRAME_STAT, keyframe);
+
+        if static_frame && self.drop_static_frames {
+            debug!("Dropping static frame");
+            frame_data.set_drop_reason(Some(DropReason::StaticFrame));
+        }
+
+        Ok(Some(frame_data))
+    }
+}
@@ -0,0 +1,331 @@
+//! Dirty-tile delta coding of packed frames.
+//!
+//! Frames are split in square tiles and only the tiles which changed since the previous frame
+//! are transmitted. A delta starts with a flags byte and the number of tiles as a little
+//! endian u32, followed by each tile as its column and row indices (little endian u16) and its
+//! tightly packed pixel rows. Tiles in the last column and row are cropped to the frame size.
+
+use crate::pixel_format::FrameFormat;
+
+pub mod decoder;
+pub mod encoder;
+
+pub const TILE_DELTA_BUFFER: &str = "tile_delta_buffer";
+
+pub const TILE_DELTA_SIZE_STAT: &str = "tile_delta_size";
+pub const CHANGED_TILES_STAT: &str = "changed_tiles";
+pub const CHANGED_TILES_RATIO_STAT: &str = "changed_tiles_ratio";
+pub const STATIC_FRAME_STAT: &str = "static_frame";
+
+pub const DEFAULT_TILE_SIZE: usize = 64;
+
+const HEADER_SIZE: usize = 5;
+const TILE_HEADER_SIZE: usize = 4;
+
+/// Set when the delta carries all the tiles and does not depend on the previous frame
+const FULL_FRAME_FLAG: u8 = 1;
+
+/// Partition of a packed frame in tiles
+#[derive(Debug, Clone, Copy)]
+struct TileGrid {
+    width: usize,
+    height: usize,
+    stride: usize,
+    bytes_per_pixel: usize,
+    tile_size: usize,
+    columns: usize,
+    rows: usize,
+}
+
+/// Area of a tile, in pixels
+struct TileRect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl TileGrid {
+    fn new(format: &FrameFormat, tile_size: usize) -> Self {
+        let bytes_per_pixel = format
+            .get_pixel_format()
+            .bytes_per_pixel()
+            .expect("Tile delta coding requires a packed pixel format");
+
+        assert!(tile_size > 0, "Tiles cannot be empty");
+
+        let columns = format.get_width().div_ceil(tile_size);
+        let rows = format.get_height().div_ceil(tile_size);
+        assert!(
+            columns <= u16::MAX as usize && rows <= u16::MAX as usize,
+            "Too many tiles"
+        );
+
+        Self {
+            width: format.get_width(),
+            height: format.get_height(),
+            stride: format.planes()[0].stride,
+            bytes_per_pixel,
+            tile_size,
+            columns,
+            rows,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn rect(&self, column: usize, row: usize) -> TileRect {
+        let x = column * self.tile_size;
+        let y = row * self.tile_size;
+
+        TileRect {
+            x,
+            y,
+            width: self.tile_size.min(self.width - x),
+            height: self.tile_size.min(self.height - y),
+        }
+    }
+
+    /// Byte ranges of the rows of a tile in a frame buffer
+    fn row_ranges(&self, rect: &TileRect) -> impl Iterator<Item = std::ops::Range<usize>> {
+        let start = rect.x * self.bytes_per_pixel;
+        let row_size = rect.width * self.bytes_per_pixel;
+        let stride = self.stride;
+
+        (rect.y..rect.y + rect.height).map(move |y| {
+            let offset = y * stride + start;
+            offset..offset + row_size
+        })
+    }
+
+    fn payload_size(&self, rect: &TileRect) -> usize {
+        rect.width * rect.height * self.bytes_per_pixel
+    }
+
+    /// Size of a delta carrying all the tiles
+    fn max_delta_size(&self) -> usize {
+        HEADER_SIZE
+            + self.count() * TILE_HEADER_SIZE
+            + self.width * self.height * self.bytes_per_pixel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use remotia_core::{error::DropReason, traits::FrameProcessor, types::FrameData};
+
+    use crate::{
+        codec::KEYFRAME_STAT,
+        pixel_format::{FrameFormat, PixelFormat},
+    };
+
+    use super::{
+        decoder::TileDeltaDecoder, encoder::TileDeltaEncoder, CHANGED_TILES_STAT,
+        STATIC_FRAME_STAT, TILE_DELTA_BUFFER, TILE_DELTA_SIZE_STAT,
+    };
+
+    const WIDTH: usize = 37;
+    const HEIGHT: usize = 21;
+    const TILE_SIZE: usize = 8;
+
+    /// Odd sized frame with padded rows, whose last column and row of tiles are cropped
+    fn format() -> FrameFormat {
+        FrameFormat::new(PixelFormat::BGR24, WIDTH, HEIGHT).stride(WIDTH * 3 + 7)
+    }
+
+    fn pattern(format: &FrameFormat) -> Vec<u8> {
+        (0..format.planes()[0].size())
+            .map(|i| (i as u32).wrapping_mul(2654435761).to_le_bytes()[1])
+            .collect()
+    }
+
+    fn set_pixel(format: &FrameFormat, frame: &mut [u8], x: usize, y: usize, value: u8) {
+        let offset = y * format.planes()[0].stride + x * 3;
+        frame[offset..offset + 3].fill(value);
+    }
+
+    async fn encode(encoder: &mut TileDeltaEncoder, frame: &[u8]) -> FrameData {
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::from(frame));
+        encoder.try_process(frame_data).await.unwrap().unwrap()
+    }
+
+    async fn decode(decoder: &mut TileDeltaDecoder, delta: &[u8]) -> FrameData {
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer(TILE_DELTA_BUFFER, BytesMut::from(delta));
+        frame_data.set(TILE_DELTA_SIZE_STAT, delta.len() as u128);
+        frame_data.insert_writable_buffer(
+            "raw_frame_buffer",
+            BytesMut::zeroed(format().planes()[0].size()),
+        );
+        decoder.try_process(frame_data).await.unwrap().unwrap()
+    }
+
+    fn delta(frame_data: &mut FrameData) -> Vec<u8> {
+        let size = frame_data.get(TILE_DELTA_SIZE_STAT) as usize;
+        frame_data
+            .get_writable_buffer_ref(TILE_DELTA_BUFFER)
+            .unwrap()[..size]
+            .to_vec()
+    }
+
+    fn assert_same_pixels(found: &[u8], expected: &[u8]) {
+        let plane = format().planes()[0];
+        for y in 0..HEIGHT {
+            let row = y * plane.stride..y * plane.stride + plane.row_size;
+            assert_eq!(found[row.clone()], expected[row], "row {} differs", y);
+        }
+    }
+
+    #[tokio::test]
+    async fn frames_survive_a_round_trip() {
+        let format = format();
+        let mut encoder = TileDeltaEncoder::new(format.clone()).tile_size(TILE_SIZE);
+        let mut decoder = TileDeltaDecoder::new(format.clone()).tile_size(TILE_SIZE);
+
+        let mut frame = pattern(&format);
+        let changes: Vec<Vec<(usize, usize)>> = vec![
+            vec![],
+            vec![(0, 0)],
+            vec![(WIDTH - 1, HEIGHT - 1), (33, 2), (20, 10)],
+        ];
+
+        for (index, pixels) in changes.into_iter().enumerate() {
+            for &(x, y) in &pixels {
+                set_pixel(&format, &mut frame, x, y, index as u8);
+            }
+
+            let mut encoded = encode(&mut encoder, &frame).await;
+            let expected_tiles = match index {
+                0 => 5 * 3,
+                _ => pixels.len(),
+            };
+            assert_eq!(encoded.get(CHANGED_TILES_STAT), expected_tiles as u128);
+
+            let mut decoded = decode(&mut decoder, &delta(&mut encoded)).await;
+            assert_eq!(decoded.get_drop_reason(), None);
+            assert_same_pixels(
+                decoded.get_writable_buffer_ref("raw_frame_buffer").unwrap(),
+                &frame,
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn static_frames_can_be_dropped() {
+        let format = format();
+        let frame = pattern(&format);
+        let mut encoder = TileDeltaEncoder::new(format.clone())
+            .tile_size(TILE_SIZE)
+            .drop_static_frames(true);
+
+        let first = encode(&mut encoder, &frame).await;
+        assert_eq!(first.get_drop_reason(), None);
+        assert!(!first.get_bool(STATIC_FRAME_STAT));
+
+        let second = encode(&mut encoder, &frame).await;
+        assert_eq!(second.get(CHANGED_TILES_STAT), 0);
+        assert!(second.get_bool(STATIC_FRAME_STAT));
+        assert_eq!(second.get_drop_reason(), Some(DropReason::StaticFrame));
+    }
+
+    #[tokio::test]
+    async fn keyframes_carry_all_the_tiles() {
+        let format = format();
+        let frame = pattern(&format);
+        let mut encoder = TileDeltaEncoder::new(format.clone())
+            .tile_size(TILE_SIZE)
+            .keyframe_interval(3);
+
+        let mut deltas = Vec::new();
+        for index in 0..7 {
+            let mut encoded = encode(&mut encoder, &frame).await;
+            let keyframe = index % 3 == 0;
+
+            assert_eq!(encoded.get_bool(KEYFRAME_STAT), keyframe);
+            assert_eq!(
+                encoded.get(CHANGED_TILES_STAT),
+                if keyframe { 15 } else { 0 }
+            );
+            deltas.push(delta(&mut encoded));
+        }
+
+        // A decoder joining the stream waits for the next keyframe
+        let mut decoder = TileDeltaDecoder::new(format.clone()).tile_size(TILE_SIZE);
+        let skipped = decode(&mut decoder, &deltas[1]).await;
+        assert_eq!(skipped.get_drop_reason(), Some(DropReason::NoDecodedFrames));
+
+        let mut decoded = decode(&mut decoder, &deltas[3]).await;
+        assert_eq!(decoded.get_drop_reason(), None);
+        assert_same_pixels(
+            decoded.get_writable_buffer_ref("raw_frame_buffer").unwrap(),
+            &frame,
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_deltas_are_rejected() {
+        let tile = |column: u16, row: u16| [column.to_le_bytes(), row.to_le_bytes()].concat();
+        let full_tile = [tile(0, 0), vec![0; TILE_SIZE * TILE_SIZE * 3]].concat();
+
+        let deltas: Vec<(&str, Vec<u8>)> = vec![
+            ("truncated header", vec![1, 1, 0]),
+            (
+                "oversized",
+                [vec![1], 16u32.to_le_bytes().to_vec()].concat(),
+            ),
+            (
+                "truncated tile header",
+                [vec![1], 1u32.to_le_bytes().to_vec(), vec![0, 0]].concat(),
+            ),
+            (
+                "truncated payload",
+                [
+                    vec![1],
+                    1u32.to_le_bytes().to_vec(),
+                    tile(0, 0),
+                    vec![0; 10],
+                ]
+                .concat(),
+            ),
+            (
+                "out of bounds column",
+                [
+                    vec![1],
+                    1u32.to_le_bytes().to_vec(),
+                    tile(5, 0),
+                    vec![0; 64 * 3],
+                ]
+                .concat(),
+            ),
+            (
+                "out of bounds row",
+                [
+                    vec![1],
+                    1u32.to_le_bytes().to_vec(),
+                    tile(0, 3),
+                    vec![0; 64 * 3],
+                ]
+                .concat(),
+            ),
+        ];
+
+        for (case, delta) in deltas {
+            let mut decoder = TileDeltaDecoder::new(format()).tile_size(TILE_SIZE);
+            let decoded = decode(&mut decoder, &delta).await;
+            assert_eq!(
+                decoded.get_drop_reason(),
+                Some(DropReason::CodecError),
+                "{} delta accepted",
+                case
+            );
+        }
+
+        let mut decoder = TileDeltaDecoder::new(format()).tile_size(TILE_SIZE);
+        let valid = [vec![1], 1u32.to_le_bytes().to_vec(), full_tile].concat();
+        assert_eq!(decode(&mut decoder, &valid).await.get_drop_reason(), None);
+    }
+}
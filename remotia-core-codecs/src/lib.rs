@@ -1 +1,6 @@
-pub mod yuv420p;
+pub mod codec;
+pub mod color;
+pub mod compression;
+pub mod delta;
+pub mod pixel_format;
+pub mod yuv420p;
\ No newline at end of file
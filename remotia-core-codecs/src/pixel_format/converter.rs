@@ -0,0 +1,540 @@
+use async_trait::async_trait;
+use bytes::BytesMut;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use remotia_core::{
+    error::ConfigError,
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use crate::color::{clamp_channel, ColorMatrix, ColorRange, ColorSpace};
+
+use super::{FrameFormat, PixelFormat, PlaneLayout};
+
+/// Converts frames between any two pixel formats of the same size.
+///
+/// The destination buffers must already be in the frame DTO, unless they share their key with
+/// a source buffer, in which case the conversion is performed in place. The color space is
+/// only involved when converting between packed and YCbCr formats: the one recorded in the
+/// stats of a YCbCr source takes precedence over the configured one, and it is recorded in
+/// the stats when producing a YCbCr frame.
+pub struct PixelFormatConverter {
+    source: FrameFormat,
+    destination: FrameFormat,
+    color_space: ColorSpace,
+}
+
+impl PixelFormatConverter {
+    pub fn new(source: FrameFormat, destination: FrameFormat) -> Result<Self, ConfigError> {
+        if (source.get_width(), source.get_height())
+            != (destination.get_width(), destination.get_height())
+        {
+            return Err(ConfigError::UnsupportedConversion(
+                "pixel format conversion does not support scaling".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            source,
+            destination,
+            color_space: ColorSpace::default(),
+        })
+    }
+
+    pub fn color_matrix(mut self, matrix: ColorMatrix) -> Self {
+        self.color_space.matrix = matrix;
+        self
+    }
+
+    pub fn color_range(mut self, range: ColorRange) -> Self {
+        self.color_space.range = range;
+        self
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for PixelFormatConverter {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let mut color_space = self.color_space;
+        if !self.source.get_pixel_format().is_packed() {
+            color_space = match color_space.read_stats(&frame_data) {
+                Ok(color_space) => color_space,
+                Err(error) => return Err((frame_data, error)),
+            };
+        }
+
+        // Position of the lent buffer backing each destination plane
+        let source_keys = self.source.get_buffers();
+        let mut destination_only = source_keys.len()..;
+        let slots: Vec<usize> = self
+            .destination
+            .get_buffers()
+            .iter()
+            .map(|key| match source_keys.iter().position(|k| k == key) {
+                Some(slot) => slot,
+                None => destination_only.next().unwrap(),
+            })
+            .collect();
+
+        let converted = FrameFormat::with_buffers(
+            &[&self.source, &self.destination],
+            &mut frame_data,
+            |buffers| {
+                // In place conversions read from a copy of the source
+                let mut destination_buffers: Vec<BytesMut> = slots
+                    .iter()
+                    .map(|&slot| match slot < source_keys.len() {
+                        true => buffers[slot].clone(),
+                        false => std::mem::take(&mut buffers[slot]),
+                    })
+                    .collect();
+
+                convert(
+                    &self.source,
+                    &self.destination,
+                    &color_space,
+                    &buffers[..source_keys.len()],
+                    &mut destination_buffers,
+                );
+
+                for (slot, buffer) in slots.iter().zip(destination_buffers) {
+                    buffers[*slot] = buffer;
+                }
+            },
+        );
+
+        if let Err(error) = converted {
+            return Err((frame_data, error));
+        }
+
+        if !self.destination.get_pixel_format().is_packed() {
+            color_space.write_stats(&mut frame_data);
+        }
+
+        Ok(Some(frame_data))
+    }
+}
+
+/// Random access to the pixels of a frame
+struct FrameReader<'a> {
+    format: &'a FrameFormat,
+    planes: Vec<PlaneLayout>,
+    buffers: &'a [BytesMut],
+    color_space: &'a ColorSpace,
+}
+
+impl<'a> FrameReader<'a> {
+    fn new(format: &'a FrameFormat, buffers: &'a [BytesMut], color_space: &'a ColorSpace) -> Self {
+        Self {
+            format,
+            planes: format.planes(),
+            buffers,
+            color_space,
+        }
+    }
+
+    fn packed(&self, x: usize, y: usize) -> [u8; 4] {
+        let pixel_format = self.format.get_pixel_format();
+        let size = pixel_format.bytes_per_pixel().unwrap();
+        let offset = y * self.planes[0].stride + x * size;
+
+        pixel_format.read_bgra(&self.buffers[0][offset..offset + size])
+    }
+
+    fn ycbcr(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        let luma = self.buffers[0][y * self.planes[0].stride + x];
+
+        let (chroma_x, chroma_y) = match self.format.get_pixel_format().is_420() {
+            true => (x / 2, y / 2),
+            false => (x, y),
+        };
+
+        let (cb, cr) = match self.format.get_pixel_format() {
+            PixelFormat::NV12 => {
+                let offset = chroma_y * self.planes[1].stride + chroma_x * 2;
+                (self.buffers[1][offset], self.buffers[1][offset + 1])
+            }
+            _ => {
+                let offset = chroma_y * self.planes[1].stride + chroma_x;
+                (self.buffers[1][offset], self.buffers[2][offset])
+            }
+        };
+
+        (luma, cb, cr)
+    }
+
+    fn bgra(&self, x: usize, y: usize) -> [u8; 4] {
+        if self.format.get_pixel_format().is_packed() {
+            return self.packed(x, y);
+        }
+
+        let (luma, cb, cr) = self.ycbcr(x, y);
+        let (b, g, r) = self.color_space.yuv_to_bgr(luma, cb, cr);
+        [b, g, r, 255]
+    }
+
+    /// Unrounded Y, Cb and Cr values of a pixel
+    fn yuv(&self, x: usize, y: usize) -> (f32, f32, f32) {
+        if self.format.get_pixel_format().is_packed() {
+            let [b, g, r, _] = self.packed(x, y);
+            return self.color_space.bgr_to_yuv(b, g, r);
+        }
+
+        let (luma, cb, cr) = self.ycbcr(x, y);
+        (luma as f32, cb as f32, cr as f32)
+    }
+}
+
+fn convert(
+    source: &FrameFormat,
+    destination: &FrameFormat,
+    color_space: &ColorSpace,
+    source_buffers: &[BytesMut],
+    destination_buffers: &mut [BytesMut],
+) {
+    let reader = FrameReader::new(source, source_buffers, color_space);
+    let pixel_format = destination.get_pixel_format();
+    let (width, height) = (destination.get_width(), destination.get_height());
+
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    // Each group of rows shares a row of chroma samples in the destination
+    let group_height = if pixel_format.is_420() { 2 } else { 1 };
+    let groups_count = height.div_ceil(group_height);
+
+    let groups: Vec<Vec<Vec<u8>>> = (0..groups_count)
+        .into_par_iter()
+        .map(|group| {
+            let rows = group * group_height..height.min((group + 1) * group_height);
+
+            if pixel_format.is_packed() {
+                convert_packed_rows(&reader, pixel_format, width, rows)
+            } else {
+                convert_ycbcr_rows(&reader, destination, rows)
+            }
+        })
+        .collect();
+
+    let planes = destination.planes();
+    for (group, group_planes) in groups.iter().enumerate() {
+        for (plane_index, plane_rows) in group_planes.iter().enumerate() {
+            let plane = &planes[plane_index];
+
+            // The first plane has a row per pixel row, the chroma planes a row per group
+            let first_row = if plane_index == 0 {
+                group * group_height
+            } else {
+                group
+            };
+
+            for (row, data) in plane_rows.chunks(plane.row_size).enumerate() {
+                let offset = (first_row + row) * plane.stride;
+                destination_buffers[plane_index][offset..offset + plane.row_size]
+                    .copy_from_slice(data);
+            }
+        }
+    }
+}
+
+fn convert_packed_rows(
+    reader: &FrameReader,
+    pixel_format: PixelFormat,
+    width: usize,
+    rows: std::ops::Range<usize>,
+) -> Vec<Vec<u8>> {
+    let size = pixel_format.bytes_per_pixel().unwrap();
+    let mut packed = vec![0; rows.len() * width * size];
+
+    let mut pixels = packed.chunks_exact_mut(size);
+    for y in rows {
+        for x in 0..width {
+            pixel_format.write_bgra(reader.bgra(x, y), pixels.next().unwrap());
+        }
+    }
+
+    vec![packed]
+}
+
+fn convert_ycbcr_rows(
+    reader: &FrameReader,
+    destination: &FrameFormat,
+    rows: std::ops::Range<usize>,
+) -> Vec<Vec<u8>> {
+    let width = destination.get_width();
+    let chroma_width = destination.chroma_width();
+    let horizontal_step = if destination.get_pixel_format().is_420() {
+        2
+    } else {
+        1
+    };
+
+    let mut luma = Vec::with_capacity(rows.len() * width);
+    let mut cb_sums = vec![0.0; chroma_width];
+    let mut cr_sums = vec![0.0; chroma_width];
+    let mut counts = vec![0.0; chroma_width];
+
+    for y in rows {
+        for x in 0..width {
+            let (y_value, cb, cr) = reader.yuv(x, y);
+            luma.push(clamp_channel(y_value));
+
+            cb_sums[x / horizontal_step] += cb;
+            cr_sums[x / horizontal_step] += cr;
+            counts[x / horizontal_step] += 1.0;
+        }
+    }
+
+    // Partial blocks at odd edges are averaged over the pixels they cover
+    let average = |sums: &[f32]| -> Vec<u8> {
+        sums.iter()
+            .zip(&counts)
+            .map(|(sum, count)| clamp_channel(sum / count))
+            .collect()
+    };
+
+    let cb = average(&cb_sums);
+    let cr = average(&cr_sums);
+
+    match destination.get_pixel_format() {
+        PixelFormat::NV12 => {
+            let cbcr = cb.iter().zip(&cr).flat_map(|(cb, cr)| [*cb, *cr]).collect();
+            vec![luma, cbcr]
+        }
+        _ => vec![luma, cb, cr],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use remotia_core::{error::ConfigError, traits::FrameProcessor, types::FrameData};
+
+    use crate::pixel_format::{FrameFormat, PixelFormat};
+
+    use super::PixelFormatConverter;
+
+    const FORMATS: [PixelFormat; 7] = [
+        PixelFormat::BGRA,
+        PixelFormat::RGBA,
+        PixelFormat::RGB24,
+        PixelFormat::BGR24,
+        PixelFormat::I420,
+        PixelFormat::NV12,
+        PixelFormat::I444,
+    ];
+
+    /// Frame format whose buffers are named after the given prefix, so that the two sides of
+    /// a conversion never share a buffer
+    fn format(pixel_format: PixelFormat, width: usize, height: usize, prefix: &str) -> FrameFormat {
+        let mut format = FrameFormat::new(pixel_format, width, height);
+        for plane in 0..format.get_buffers().len() {
+            format = format.buffer(plane, &format!("{}_{}", prefix, plane));
+        }
+        format
+    }
+
+    /// Pads the rows of the first plane
+    fn padded(format: FrameFormat) -> FrameFormat {
+        let row_size = format.planes()[0].row_size;
+        format.stride(row_size + 5)
+    }
+
+    fn insert_buffers(frame_data: &mut FrameData, format: &FrameFormat, planes: &[Vec<u8>]) {
+        for (key, plane) in format.get_buffers().iter().zip(planes) {
+            frame_data.insert_writable_buffer(key, BytesMut::from(&plane[..]));
+        }
+    }
+
+    fn insert_zeroed(frame_data: &mut FrameData, format: &FrameFormat) {
+        let planes: Vec<Vec<u8>> = format
+            .planes()
+            .iter()
+            .map(|plane| vec![0; plane.size()])
+            .collect();
+        insert_buffers(frame_data, format, &planes);
+    }
+
+    fn planes(frame_data: &mut FrameData, format: &FrameFormat) -> Vec<Vec<u8>> {
+        format
+            .get_buffers()
+            .iter()
+            .map(|key| frame_data.get_writable_buffer_ref(key).unwrap().to_vec())
+            .collect()
+    }
+
+    async fn convert(
+        source: &FrameFormat,
+        destination: &FrameFormat,
+        mut frame_data: FrameData,
+    ) -> FrameData {
+        insert_zeroed(&mut frame_data, destination);
+
+        PixelFormatConverter::new(source.clone(), destination.clone())
+            .unwrap()
+            .try_process(frame_data)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    /// A pure red pixel as stored in each plane of the format with the default BT.601 full
+    /// range color space
+    fn red(pixel_format: PixelFormat) -> Vec<Vec<u8>> {
+        match pixel_format {
+            PixelFormat::BGRA => vec![vec![0, 0, 255, 255]],
+            PixelFormat::RGBA => vec![vec![255, 0, 0, 255]],
+            PixelFormat::RGB24 => vec![vec![255, 0, 0]],
+            PixelFormat::BGR24 => vec![vec![0, 0, 255]],
+            PixelFormat::I420 | PixelFormat::I444 => vec![vec![76], vec![85], vec![255]],
+            PixelFormat::NV12 => vec![vec![76], vec![85, 255]],
+        }
+    }
+
+    fn red_frame(format: &FrameFormat) -> Vec<Vec<u8>> {
+        format
+            .planes()
+            .iter()
+            .zip(red(format.get_pixel_format()))
+            .map(|(plane, pixel)| pixel.repeat(plane.size() / pixel.len()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn known_colors_are_converted_between_every_pair_of_formats() {
+        for source_format in FORMATS {
+            for destination_format in FORMATS {
+                let source = format(source_format, 3, 3, "source");
+                let destination = format(destination_format, 3, 3, "destination");
+
+                let mut frame_data = FrameData::default();
+                insert_buffers(&mut frame_data, &source, &red_frame(&source));
+                let mut frame_data = convert(&source, &destination, frame_data).await;
+
+                // Decoding YCbCr rounds each channel on its own
+                let tolerance = match !source_format.is_packed() && destination_format.is_packed() {
+                    true => 1,
+                    false => 0,
+                };
+
+                let found = planes(&mut frame_data, &destination);
+                for (found, expected) in found.iter().zip(red_frame(&destination)) {
+                    let error = found
+                        .iter()
+                        .zip(&expected)
+                        .map(|(found, expected)| found.abs_diff(*expected))
+                        .max()
+                        .unwrap();
+                    assert!(
+                        error <= tolerance,
+                        "{:?} to {:?}: found {:?}, expected {:?}",
+                        source_format,
+                        destination_format,
+                        found,
+                        expected
+                    );
+                }
+            }
+        }
+    }
+
+    /// Odd sized BGRA frame with padded rows, made of 2x2 blocks of a single color so that
+    /// chroma subsampling loses nothing
+    fn block_frame(format: &FrameFormat) -> Vec<u8> {
+        let plane = format.planes()[0];
+        let mut frame = vec![0; plane.size()];
+
+        for y in 0..format.get_height() {
+            for x in 0..format.get_width() {
+                let block = (y / 2 * format.get_width().div_ceil(2) + x / 2) as u32;
+                let color = block.wrapping_mul(2654435761).to_le_bytes();
+
+                let offset = y * plane.stride + x * 4;
+                frame[offset..offset + 3].copy_from_slice(&color[..3]);
+                frame[offset + 3] = 255;
+            }
+        }
+
+        frame
+    }
+
+    #[tokio::test]
+    async fn round_trips_of_odd_strided_frames_are_bounded() {
+        let (width, height) = (37, 21);
+        let original = padded(format(PixelFormat::BGRA, width, height, "original"));
+        let output = format(PixelFormat::BGRA, width, height, "output");
+        let frame = block_frame(&original);
+
+        for source_format in FORMATS {
+            for destination_format in FORMATS {
+                let source = padded(format(source_format, width, height, "source"));
+                let destination = padded(format(destination_format, width, height, "destination"));
+
+                let mut frame_data = FrameData::default();
+                insert_buffers(&mut frame_data, &original, std::slice::from_ref(&frame));
+                let frame_data = convert(&original, &source, frame_data).await;
+                let frame_data = convert(&source, &destination, frame_data).await;
+                let mut frame_data = convert(&destination, &output, frame_data).await;
+
+                let tolerance = match source_format.is_packed() && destination_format.is_packed() {
+                    true => 0,
+                    false => 1,
+                };
+
+                let found = &planes(&mut frame_data, &output)[0];
+                let stride = original.planes()[0].stride;
+                for y in 0..height {
+                    let expected = &frame[y * stride..y * stride + width * 4];
+                    let row = &found[y * width * 4..(y + 1) * width * 4];
+
+                    for (found, expected) in row.iter().zip(expected) {
+                        assert!(
+                            found.abs_diff(*expected) <= tolerance,
+                            "{:?} to {:?}: row {} differs",
+                            source_format,
+                            destination_format,
+                            y
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn shared_buffers_are_converted_in_place() {
+        let source = FrameFormat::new(PixelFormat::BGRA, 2, 1);
+        let destination = FrameFormat::new(PixelFormat::RGBA, 2, 1);
+
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer(
+            "raw_frame_buffer",
+            BytesMut::from(&[1, 2, 3, 4, 5, 6, 7, 8][..]),
+        );
+
+        let mut frame_data = PixelFormatConverter::new(source, destination)
+            .unwrap()
+            .try_process(frame_data)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            &frame_data
+                .get_writable_buffer_ref("raw_frame_buffer")
+                .unwrap()[..],
+            &[3, 2, 1, 4, 7, 6, 5, 8]
+        );
+    }
+
+    #[test]
+    fn scaling_is_rejected() {
+        let result = PixelFormatConverter::new(
+            FrameFormat::new(PixelFormat::BGRA, 4, 4),
+            FrameFormat::new(PixelFormat::I420, 2, 2),
+        );
+
+        assert!(matches!(result, Err(ConfigError::UnsupportedConversion(_))));
+    }
+}
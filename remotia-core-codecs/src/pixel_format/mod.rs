@@ -0,0 +1,271 @@
+use bytes::BytesMut;
+use remotia_core::{error::ProcessorError, types::FrameData};
+
+pub mod converter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Packed, 4 bytes per pixel
+    BGRA,
+
+    /// Packed, 4 bytes per pixel
+    RGBA,
+
+    /// Packed, 3 bytes per pixel
+    RGB24,
+
+    /// Packed, 3 bytes per pixel
+    BGR24,
+
+    /// Y plane followed by Cb and Cr planes subsampled over 2x2 blocks
+    I420,
+
+    /// Y plane followed by an interleaved CbCr plane subsampled over 2x2 blocks
+    NV12,
+
+    /// Y, Cb and Cr planes at full resolution
+    I444,
+}
+
+impl PixelFormat {
+    pub fn is_packed(&self) -> bool {
+        matches!(
+            self,
+            PixelFormat::BGRA | PixelFormat::RGBA | PixelFormat::RGB24 | PixelFormat::BGR24
+        )
+    }
+
+    /// Whether the chroma is subsampled over 2x2 blocks
+    pub fn is_420(&self) -> bool {
+        matches!(self, PixelFormat::I420 | PixelFormat::NV12)
+    }
+
+    /// Bytes per pixel of the packed formats
+    pub fn bytes_per_pixel(&self) -> Option<usize> {
+        match self {
+            PixelFormat::BGRA | PixelFormat::RGBA => Some(4),
+            PixelFormat::RGB24 | PixelFormat::BGR24 => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Reads a packed pixel as BGRA, alpha defaulting to opaque
+    pub(crate) fn read_bgra(&self, pixel: &[u8]) -> [u8; 4] {
+        match self {
+            PixelFormat::BGRA => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            PixelFormat::RGBA => [pixel[2], pixel[1], pixel[0], pixel[3]],
+            PixelFormat::RGB24 => [pixel[2], pixel[1], pixel[0], 255],
+            PixelFormat::BGR24 => [pixel[0], pixel[1], pixel[2], 255],
+            _ => unreachable!("{:?} is not a packed format", self),
+        }
+    }
+
+    /// Writes a BGRA pixel in this packed format
+    pub(crate) fn write_bgra(&self, [b, g, r, a]: [u8; 4], pixel: &mut [u8]) {
+        match self {
+            PixelFormat::BGRA => pixel.copy_from_slice(&[b, g, r, a]),
+            PixelFormat::RGBA => pixel.copy_from_slice(&[r, g, b, a]),
+            PixelFormat::RGB24 => pixel.copy_from_slice(&[r, g, b]),
+            PixelFormat::BGR24 => pixel.copy_from_slice(&[b, g, r]),
+            _ => unreachable!("{:?} is not a packed format", self),
+        }
+    }
+
+    fn default_buffers(&self) -> Vec<String> {
+        let keys: &[&str] = match self {
+            PixelFormat::BGRA | PixelFormat::RGBA | PixelFormat::RGB24 | PixelFormat::BGR24 => {
+                &["raw_frame_buffer"]
+            }
+            PixelFormat::I420 | PixelFormat::I444 => {
+                &["y_channel_buffer", "cb_channel_buffer", "cr_channel_buffer"]
+            }
+            PixelFormat::NV12 => &["y_channel_buffer", "cbcr_channel_buffer"],
+        };
+
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+}
+
+/// Layout of one buffer of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneLayout {
+    pub row_size: usize,
+    pub rows: usize,
+    pub stride: usize,
+}
+
+impl PlaneLayout {
+    /// Minimum size of the buffer, the padding of the last row excluded
+    pub fn size(&self) -> usize {
+        match self.rows {
+            0 => 0,
+            rows => self.stride * (rows - 1) + self.row_size,
+        }
+    }
+}
+
+/// Describes how a frame of a given pixel format and size is laid out in the buffers of the
+/// frame DTO
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameFormat {
+    pixel_format: PixelFormat,
+    width: usize,
+    height: usize,
+    stride: Option<usize>,
+    buffers: Vec<String>,
+}
+
+impl FrameFormat {
+    pub fn new(pixel_format: PixelFormat, width: usize, height: usize) -> Self {
+        Self {
+            pixel_format,
+            width,
+            height,
+            stride: None,
+            buffers: pixel_format.default_buffers(),
+        }
+    }
+
+    /// Bytes between the start of two consecutive rows of the packed frame or of the Y plane,
+    /// padding included. Chroma planes are always tightly packed.
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.stride = Some(stride);
+        self
+    }
+
+    /// Overrides the key of the buffer holding the given plane
+    pub fn buffer(mut self, plane: usize, key: &str) -> Self {
+        self.buffers[plane] = key.to_string();
+        self
+    }
+
+    pub fn get_pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_buffers(&self) -> &[String] {
+        &self.buffers
+    }
+
+    pub fn chroma_width(&self) -> usize {
+        match self.pixel_format.is_420() {
+            true => self.width.div_ceil(2),
+            false => self.width,
+        }
+    }
+
+    pub fn chroma_height(&self) -> usize {
+        match self.pixel_format.is_420() {
+            true => self.height.div_ceil(2),
+            false => self.height,
+        }
+    }
+
+    pub fn planes(&self) -> Vec<PlaneLayout> {
+        let tight = |row_size, rows| PlaneLayout {
+            row_size,
+            rows,
+            stride: row_size,
+        };
+
+        let mut planes = match self.pixel_format {
+            PixelFormat::BGRA | PixelFormat::RGBA => vec![tight(self.width * 4, self.height)],
+            PixelFormat::RGB24 | PixelFormat::BGR24 => vec![tight(self.width * 3, self.height)],
+            PixelFormat::I420 | PixelFormat::I444 => vec![
+                tight(self.width, self.height),
+                tight(self.chroma_width(), self.chroma_height()),
+                tight(self.chroma_width(), self.chroma_height()),
+            ],
+            PixelFormat::NV12 => vec![
+                tight(self.width, self.height),
+                tight(self.chroma_width() * 2, self.chroma_height()),
+            ],
+        };
+
+        if let Some(stride) = self.stride {
+            planes[0].stride = stride;
+        }
+
+        planes
+    }
+
+    /// Checks that all the buffers of the frame are in the DTO and large enough
+    pub fn check_buffers(&self, frame_data: &mut FrameData) -> Result<(), ProcessorError> {
+        for (key, plane) in self.buffers.iter().zip(self.planes()) {
+            if plane.stride < plane.row_size {
+                return Err(ProcessorError::Other(format!(
+                    "Stride {} of '{}' is smaller than the row size {}",
+                    plane.stride, key, plane.row_size
+                )));
+            }
+
+            let found = frame_data
+                .get_writable_buffer_ref(key)
+                .ok_or_else(|| ProcessorError::MissingBuffer(key.to_string()))?
+                .len();
+
+            if found < plane.size() {
+                return Err(ProcessorError::BufferTooSmall {
+                    key: key.to_string(),
+                    required: plane.size(),
+                    found,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the buffers of all the formats in advance, then lends them to `f` in order. A
+    /// buffer shared by several formats is lent once, at its first position.
+    pub fn with_buffers<R>(
+        formats: &[&FrameFormat],
+        frame_data: &mut FrameData,
+        f: impl FnOnce(&mut [BytesMut]) -> R,
+    ) -> Result<R, ProcessorError> {
+        let mut keys: Vec<&str> = Vec::new();
+
+        for format in formats {
+            format.check_buffers(frame_data)?;
+
+            for key in format.get_buffers() {
+                if !keys.contains(&key.as_str()) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        frame_data.with_writable_buffers(&keys, f)
+    }
+}
+
+/// Reorders the channels of tightly packed pixels, e.g. to hand a BGRA frame to a renderer
+/// expecting RGBA. Converts as many pixels as both buffers hold.
+pub fn convert_packed(
+    source_format: PixelFormat,
+    destination_format: PixelFormat,
+    source: &[u8],
+    destination: &mut [u8],
+) {
+    let source_size = source_format
+        .bytes_per_pixel()
+        .unwrap_or_else(|| panic!("{:?} is not a packed format", source_format));
+    let destination_size = destination_format
+        .bytes_per_pixel()
+        .unwrap_or_else(|| panic!("{:?} is not a packed format", destination_format));
+
+    for (source_pixel, destination_pixel) in source
+        .chunks_exact(source_size)
+        .zip(destination.chunks_exact_mut(destination_size))
+    {
+        destination_format.write_bgra(source_format.read_bgra(source_pixel), destination_pixel);
+    }
+}
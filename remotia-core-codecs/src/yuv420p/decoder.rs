@@ -0,0 +1,423 @@
+use async_trait::async_trait;
+use rayon::{iter::IndexedParallelIterator, iter::ParallelIterator, slice::ParallelSliceMut};
+use remotia_core::{
+    error::ConfigError,
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use crate::{
+    color::{ColorMatrix, ColorRange, ColorSpace},
+    pixel_format::{FrameFormat, PixelFormat},
+};
+
+use super::{
+    check_formats,
+    simd::{yuv420_to_bgra_row, InverseCoefficients},
+};
+
+/// Inverse of [`super::encoder::RGBAToYUV420PConverter`], rebuilding the packed BGRA frame.
+/// The padding bytes of each row, if any, are left untouched. The color space recorded in
+/// the stats of the frame takes precedence over the configured one.
+pub struct YUV420PToRGBAConverter {
+    source: FrameFormat,
+    destination: FrameFormat,
+    color_space: ColorSpace,
+}
+
+impl YUV420PToRGBAConverter {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            source: FrameFormat::new(PixelFormat::I420, width, height),
+            destination: FrameFormat::new(PixelFormat::BGRA, width, height),
+            color_space: ColorSpace::default(),
+        }
+    }
+
+    /// Converts between frames laid out in custom buffers, the source being an I420 frame and
+    /// the destination a BGRA one of the same size
+    pub fn with_formats(
+        source: FrameFormat,
+        destination: FrameFormat,
+    ) -> Result<Self, ConfigError> {
+        check_formats(&destination, &source)?;
+
+        Ok(Self {
+            source,
+            destination,
+            color_space: ColorSpace::default(),
+        })
+    }
+
+    pub fn color_matrix(mut self, matrix: ColorMatrix) -> Self {
+        self.color_space.matrix = matrix;
+        self
+    }
+
+    pub fn color_range(mut self, range: ColorRange) -> Self {
+        self.color_space.range = range;
+        self
+    }
+
+    /// Bytes between the start of two consecutive rows of the BGRA frame, padding included
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.destination = self.destination.stride(stride);
+        self
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for YUV420PToRGBAConverter {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let color_space = match self.color_space.read_stats(&frame_data) {
+            Ok(color_space) => color_space,
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        let converted = FrameFormat::with_buffers(
+            &[&self.source, &self.destination],
+            &mut frame_data,
+            |buffers| {
+                let [y, cb, cr, bgra] = buffers else {
+                    unreachable!()
+                };
+
+                yuv_separate_to_bgra(
+                    &self.source,
+                    &self.destination,
+                    &color_space,
+                    y,
+                    cb,
+                    cr,
+                    bgra,
+                );
+            },
+        );
+
+        match converted {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
+    }
+}
+
+/// Fixed-point, vectorized conversion writing straight into the BGRA buffer
+pub fn yuv_separate_to_bgra(
+    source: &FrameFormat,
+    destination: &FrameFormat,
+    color_space: &ColorSpace,
+    y_pixels: &[u8],
+    u_pixels: &[u8],
+    v_pixels: &[u8],
+    bgra_pixels: &mut [u8],
+) {
+    let (width, height) = (source.get_width(), source.get_height());
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let [y_plane, u_plane, v_plane] = source.planes()[..] else {
+        unreachable!()
+    };
+    let bgra_plane = destination.planes()[0];
+
+    let coefficients = InverseCoefficients::new(color_space);
+
+    bgra_pixels[..bgra_plane.size()]
+        .par_chunks_mut(bgra_plane.stride)
+        .enumerate()
+        .for_each(|(row, bgra_row)| {
+            let y_offset = row * y_plane.stride;
+            let u_offset = (row / 2) * u_plane.stride;
+            let v_offset = (row / 2) * v_plane.stride;
+
+            yuv420_to_bgra_row(
+                &coefficients,
+                &y_pixels[y_offset..y_offset + width],
+                &u_pixels[u_offset..u_offset + u_plane.row_size],
+                &v_pixels[v_offset..v_offset + v_plane.row_size],
+                &mut bgra_row[..bgra_plane.row_size],
+            );
+        });
+}
+
+/// Floating point reference of [`yuv_separate_to_bgra`]
+pub fn yuv_separate_to_bgra_f32(
+    source: &FrameFormat,
+    destination: &FrameFormat,
+    color_space: &ColorSpace,
+    y_pixels: &[u8],
+    u_pixels: &[u8],
+    v_pixels: &[u8],
+    bgra_pixels: &mut [u8],
+) {
+    let (width, height) = (source.get_width(), source.get_height());
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let [y_plane, u_plane, v_plane] = source.planes()[..] else {
+        unreachable!()
+    };
+    let bgra_plane = destination.planes()[0];
+
+    // Each chroma sample is shared by the pixels of the 2x2 block it was averaged from
+    bgra_pixels[..bgra_plane.size()]
+        .par_chunks_mut(bgra_plane.stride)
+        .enumerate()
+        .for_each(|(row, bgra_row)| {
+            let y_row = &y_pixels[row * y_plane.stride..];
+            let u_row = &u_pixels[(row / 2) * u_plane.stride..];
+            let v_row = &v_pixels[(row / 2) * v_plane.stride..];
+
+            for (x, pixel) in bgra_row[..bgra_plane.row_size].chunks_mut(4).enumerate() {
+                let (b, g, r) = color_space.yuv_to_bgr(y_row[x], u_row[x / 2], v_row[x / 2]);
+
+                pixel[0] = b;
+                pixel[1] = g;
+                pixel[2] = r;
+                pixel[3] = 255;
+            }
+        });
+}
+
+/// Inverse of [`super::encoder::bgr_to_yuv_f32`]
+pub fn yuv_to_bgr_f32(y: u8, u: u8, v: u8) -> (u8, u8, u8) {
+    ColorSpace::default().yuv_to_bgr(y, u, v)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use remotia_core::{
+        error::{ConfigError, ProcessorError},
+        traits::FrameProcessor,
+        types::FrameData,
+    };
+
+    use crate::{
+        color::{ColorMatrix, ColorRange, ColorSpace},
+        pixel_format::{FrameFormat, PixelFormat},
+        yuv420p::encoder::{
+            bgra_to_yuv_separate, bgra_to_yuv_separate_f32, RGBAToYUV420PConverter,
+        },
+    };
+
+    use super::{yuv_separate_to_bgra, yuv_separate_to_bgra_f32, YUV420PToRGBAConverter};
+
+    const MATRICES: [ColorMatrix; 3] =
+        [ColorMatrix::BT601, ColorMatrix::BT709, ColorMatrix::BT2020];
+    const RANGES: [ColorRange; 2] = [ColorRange::Full, ColorRange::Limited];
+
+    /// Largest difference allowed between a channel and its round trip. Limited range has
+    /// fewer levels, hence a coarser quantization.
+    fn max_error(range: ColorRange) -> u8 {
+        match range {
+            ColorRange::Full => 1,
+            ColorRange::Limited => 2,
+        }
+    }
+
+    /// Frame made of 2x2 blocks of a single color, so that chroma subsampling loses nothing and
+    /// the error of a round trip only comes from quantization
+    fn block_frame(format: &FrameFormat) -> Vec<u8> {
+        let plane = format.planes()[0];
+        let mut frame = vec![0; plane.size()];
+
+        for y in 0..format.get_height() {
+            for x in 0..format.get_width() {
+                let block = (y / 2 * format.get_width().div_ceil(2) + x / 2) as u32;
+                let color = block.wrapping_mul(2654435761).to_le_bytes();
+
+                let offset = y * plane.stride + x * 4;
+                frame[offset..offset + 3].copy_from_slice(&color[..3]);
+                frame[offset + 3] = 255;
+            }
+        }
+
+        frame
+    }
+
+    fn round_trip_error(
+        bgra: &FrameFormat,
+        i420: &FrameFormat,
+        color_space: &ColorSpace,
+        fixed_point: bool,
+    ) -> u8 {
+        let frame = block_frame(bgra);
+
+        let [y_plane, u_plane, v_plane] = i420.planes()[..] else {
+            unreachable!()
+        };
+        let mut y = vec![0; y_plane.size()];
+        let mut u = vec![0; u_plane.size()];
+        let mut v = vec![0; v_plane.size()];
+        let mut output = vec![0; frame.len()];
+
+        if fixed_point {
+            bgra_to_yuv_separate(bgra, i420, color_space, &frame, &mut y, &mut u, &mut v);
+            yuv_separate_to_bgra(i420, bgra, color_space, &y, &u, &v, &mut output);
+        } else {
+            bgra_to_yuv_separate_f32(bgra, i420, color_space, &frame, &mut y, &mut u, &mut v);
+            yuv_separate_to_bgra_f32(i420, bgra, color_space, &y, &u, &v, &mut output);
+        }
+
+        frame
+            .iter()
+            .zip(&output)
+            .map(|(expected, found)| expected.abs_diff(*found))
+            .max()
+            .unwrap()
+    }
+
+    fn check_round_trip(bgra: FrameFormat, i420: FrameFormat, fixed_point: bool) {
+        for matrix in MATRICES {
+            for range in RANGES {
+                let color_space = ColorSpace::new(matrix, range);
+                let error = round_trip_error(&bgra, &i420, &color_space, fixed_point);
+                assert!(
+                    error <= max_error(range),
+                    "Round trip error {} with {:?}",
+                    error,
+                    color_space
+                );
+            }
+        }
+    }
+
+    fn formats(width: usize, height: usize) -> (FrameFormat, FrameFormat) {
+        (
+            FrameFormat::new(PixelFormat::BGRA, width, height),
+            FrameFormat::new(PixelFormat::I420, width, height),
+        )
+    }
+
+    #[test]
+    fn f32_round_trip_is_bounded() {
+        let (bgra, i420) = formats(64, 48);
+        check_round_trip(bgra, i420, false);
+    }
+
+    #[test]
+    fn fixed_point_round_trip_is_bounded() {
+        let (bgra, i420) = formats(64, 48);
+        check_round_trip(bgra, i420, true);
+    }
+
+    #[test]
+    fn round_trip_of_odd_padded_frames_is_bounded() {
+        let (bgra, i420) = formats(37, 21);
+        check_round_trip(bgra.stride(37 * 4 + 12), i420.stride(40), true);
+    }
+
+    fn convert_color(bgr: [u8; 3], color_space: &ColorSpace, fixed_point: bool) -> [u8; 3] {
+        let (bgra, i420) = formats(2, 2);
+        let frame: Vec<u8> = [bgr[0], bgr[1], bgr[2], 255].repeat(4);
+
+        let (mut y, mut u, mut v) = (vec![0; 4], vec![0; 1], vec![0; 1]);
+        if fixed_point {
+            bgra_to_yuv_separate(&bgra, &i420, color_space, &frame, &mut y, &mut u, &mut v);
+        } else {
+            bgra_to_yuv_separate_f32(&bgra, &i420, color_space, &frame, &mut y, &mut u, &mut v);
+        }
+
+        assert!(y.iter().all(|luma| *luma == y[0]));
+        [y[0], u[0], v[0]]
+    }
+
+    #[test]
+    fn known_colors_are_converted_to_reference_values() {
+        let full = ColorSpace::new(ColorMatrix::BT601, ColorRange::Full);
+        let limited = ColorSpace::new(ColorMatrix::BT601, ColorRange::Limited);
+
+        for fixed_point in [false, true] {
+            // BGR order
+            assert_eq!(
+                convert_color([0, 0, 255], &full, fixed_point),
+                [76, 85, 255]
+            );
+            assert_eq!(
+                convert_color([0, 0, 0], &limited, fixed_point),
+                [16, 128, 128]
+            );
+            assert_eq!(
+                convert_color([255, 255, 255], &limited, fixed_point),
+                [235, 128, 128]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn processors_convert_the_default_buffers() {
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer(
+            "raw_frame_buffer",
+            BytesMut::from(&[0, 0, 255, 255].repeat(6)[..]),
+        );
+        frame_data.insert_writable_buffer("y_channel_buffer", BytesMut::zeroed(6));
+        frame_data.insert_writable_buffer("cb_channel_buffer", BytesMut::zeroed(2));
+        frame_data.insert_writable_buffer("cr_channel_buffer", BytesMut::zeroed(2));
+
+        let mut encoder = RGBAToYUV420PConverter::new(3, 2);
+        let mut frame_data = encoder.try_process(frame_data).await.unwrap().unwrap();
+        assert_eq!(frame_data.get_text("color_matrix"), "bt601");
+        assert_eq!(
+            &frame_data
+                .get_writable_buffer_ref("y_channel_buffer")
+                .unwrap()[..],
+            &[76; 6]
+        );
+        assert_eq!(
+            &frame_data
+                .get_writable_buffer_ref("cb_channel_buffer")
+                .unwrap()[..],
+            &[85; 2]
+        );
+
+        frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap()
+            .fill(0);
+
+        let mut decoder = YUV420PToRGBAConverter::new(3, 2);
+        let mut frame_data = decoder.try_process(frame_data).await.unwrap().unwrap();
+        for pixel in frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap()
+            .chunks(4)
+        {
+            assert!(pixel[0] <= 1 && pixel[1] <= 1 && pixel[2] >= 254 && pixel[3] == 255);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_buffers_are_reported_without_losing_the_others() {
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer("raw_frame_buffer", BytesMut::zeroed(16));
+        frame_data.insert_writable_buffer("y_channel_buffer", BytesMut::zeroed(4));
+
+        let (frame_data, error) = RGBAToYUV420PConverter::new(2, 2)
+            .try_process(frame_data)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, ProcessorError::MissingBuffer(key) if key == "cb_channel_buffer"));
+        assert!(frame_data.has_writable_buffer("raw_frame_buffer"));
+        assert!(frame_data.has_writable_buffer("y_channel_buffer"));
+    }
+
+    #[test]
+    fn mismatched_formats_are_rejected() {
+        let (bgra, i420) = formats(4, 4);
+
+        assert!(matches!(
+            RGBAToYUV420PConverter::with_formats(i420.clone(), bgra.clone()),
+            Err(ConfigError::UnsupportedConversion(_))
+        ));
+        assert!(matches!(
+            YUV420PToRGBAConverter::with_formats(i420, FrameFormat::new(PixelFormat::BGRA, 2, 2)),
+            Err(ConfigError::UnsupportedConversion(_))
+        ));
+        assert!(YUV420PToRGBAConverter::with_formats(formats(4, 4).1, formats(4, 4).0).is_ok());
+    }
+}
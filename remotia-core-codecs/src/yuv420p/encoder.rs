@@ -1,104 +1,217 @@
 use async_trait::async_trait;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use remotia_core::{traits::FrameProcessor, types::FrameData};
-
-pub struct RGBAToYUV420PConverter {}
+use rayon::{
+    iter::{IndexedParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+use remotia_core::{
+    error::ConfigError,
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use crate::{
+    color::{clamp_channel, ColorMatrix, ColorRange, ColorSpace},
+    pixel_format::{FrameFormat, PixelFormat},
+};
+
+use super::{
+    check_formats,
+    simd::{bgra_to_yuv420_rows, ForwardCoefficients},
+};
+
+/// Converts a packed BGRA frame, by default the "raw_frame_buffer", into planar 4:2:0 Y, Cb
+/// and Cr buffers. The color space is recorded in the "color_matrix" and "color_range" stats
+/// of the frame.
+pub struct RGBAToYUV420PConverter {
+    source: FrameFormat,
+    destination: FrameFormat,
+    color_space: ColorSpace,
+}
 
 impl RGBAToYUV420PConverter {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            source: FrameFormat::new(PixelFormat::BGRA, width, height),
+            destination: FrameFormat::new(PixelFormat::I420, width, height),
+            color_space: ColorSpace::default(),
+        }
     }
-}
 
-impl Default for RGBAToYUV420PConverter {
-    fn default() -> Self {
-        Self::new()
+    /// Converts between frames laid out in custom buffers, the source being a BGRA frame and
+    /// the destination an I420 one of the same size
+    pub fn with_formats(
+        source: FrameFormat,
+        destination: FrameFormat,
+    ) -> Result<Self, ConfigError> {
+        check_formats(&source, &destination)?;
+
+        Ok(Self {
+            source,
+            destination,
+            color_space: ColorSpace::default(),
+        })
+    }
+
+    pub fn color_matrix(mut self, matrix: ColorMatrix) -> Self {
+        self.color_space.matrix = matrix;
+        self
+    }
+
+    pub fn color_range(mut self, range: ColorRange) -> Self {
+        self.color_space.range = range;
+        self
+    }
+
+    /// Bytes between the start of two consecutive rows of the BGRA frame, padding included
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.source = self.source.stride(stride);
+        self
     }
 }
 
 #[async_trait]
-impl FrameProcessor for RGBAToYUV420PConverter {
-    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
-        let raw_frame_buffer = frame_data
-            .extract_writable_buffer("raw_frame_buffer")
-            .unwrap();
-        let mut y_channel_buffer = frame_data
-            .extract_writable_buffer("y_channel_buffer")
-            .unwrap();
-        let mut cb_channel_buffer = frame_data
-            .extract_writable_buffer("cb_channel_buffer")
-            .unwrap();
-        let mut cr_channel_buffer = frame_data
-            .extract_writable_buffer("cr_channel_buffer")
-            .unwrap();
-
-        cb_channel_buffer.fill(0);
-        cr_channel_buffer.fill(0);
-
-        bgra_to_yuv_separate(
-            &raw_frame_buffer,
-            &mut y_channel_buffer,
-            &mut cb_channel_buffer,
-            &mut cr_channel_buffer,
+impl FallibleFrameProcessor for RGBAToYUV420PConverter {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let converted = FrameFormat::with_buffers(
+            &[&self.source, &self.destination],
+            &mut frame_data,
+            |buffers| {
+                let [bgra, y, cb, cr] = buffers else {
+                    unreachable!()
+                };
+
+                bgra_to_yuv_separate(
+                    &self.source,
+                    &self.destination,
+                    &self.color_space,
+                    bgra,
+                    y,
+                    cb,
+                    cr,
+                );
+            },
         );
 
-        frame_data.insert_writable_buffer("raw_frame_buffer", raw_frame_buffer);
-        frame_data.insert_writable_buffer("y_channel_buffer", y_channel_buffer);
-        frame_data.insert_writable_buffer("cb_channel_buffer", cb_channel_buffer);
-        frame_data.insert_writable_buffer("cr_channel_buffer", cr_channel_buffer);
+        if let Err(error) = converted {
+            return Err((frame_data, error));
+        }
 
-        Some(frame_data)
+        self.color_space.write_stats(&mut frame_data);
+
+        Ok(Some(frame_data))
     }
 }
 
-fn bgra_to_yuv_separate(
+/// Fixed-point, vectorized conversion writing straight into the Y, Cb and Cr buffers
+pub fn bgra_to_yuv_separate(
+    source: &FrameFormat,
+    destination: &FrameFormat,
+    color_space: &ColorSpace,
     bgra_pixels: &[u8],
     y_pixels: &mut [u8],
     u_pixels: &mut [u8],
     v_pixels: &mut [u8],
 ) {
-    let pixels_count = bgra_pixels.len() / 4;
-
-    let yuv_pixels = (0..pixels_count)
-        .into_par_iter()
-        .map(|i| {
-            let (b, g, r) = (
-                bgra_pixels[i * 4],
-                bgra_pixels[i * 4 + 1],
-                bgra_pixels[i * 4 + 2],
-            );
-
-            (i, bgr_to_yuv_f32(b, g, r))
-        })
-        .collect::<Vec<(usize, (f32, f32, f32))>>();
-
-    /*y_pixels.par_iter_mut().zip(yuv_pixels.clone()).for_each(|(value, (y, _, _))| {
-        *value = y as u8;
-    });
-
-    u_pixels.par_iter_mut().zip(yuv_pixels.clone().chunks(4)).for_each(|(value, chunk)| {
-        *value = chunk.iter().map(|(_, u, _)| (u * 0.25) as u8).sum();
-    });
-
-    v_pixels.par_iter_mut().zip(yuv_pixels.clone().chunks(4)).for_each(|(value, chunk)| {
-        *value = chunk.iter().map(|(_, _, v)| (v * 0.25) as u8).sum();
-    });*/
+    let (width, height) = (source.get_width(), source.get_height());
+    if width == 0 || height == 0 {
+        return;
+    }
 
-    yuv_pixels.into_iter().for_each(|(i, (y, u, v))| {
-        y_pixels[i] = y as u8;
-        u_pixels[i / 4] += (u * 0.25) as u8;
-        v_pixels[i / 4] += (v * 0.25) as u8;
-    });
+    let bgra_plane = source.planes()[0];
+    let [y_plane, u_plane, v_plane] = destination.planes()[..] else {
+        unreachable!()
+    };
+
+    let coefficients = ForwardCoefficients::new(color_space);
+    let bgra_row = |row: usize| {
+        let offset = row * bgra_plane.stride;
+        &bgra_pixels[offset..offset + bgra_plane.row_size]
+    };
+
+    y_pixels[..y_plane.size()]
+        .par_chunks_mut(y_plane.stride * 2)
+        .zip(u_pixels[..u_plane.size()].par_chunks_mut(u_plane.stride))
+        .zip(v_pixels[..v_plane.size()].par_chunks_mut(v_plane.stride))
+        .enumerate()
+        .for_each(|(chroma_row, ((y_rows, u_row), v_row))| {
+            let top_row = chroma_row * 2;
+            let bottom_row = (top_row + 1).min(height - 1);
+
+            // The bottom luma row is empty for the last row of an odd frame
+            let (y_top, y_bottom) = y_rows.split_at_mut(y_plane.stride.min(y_rows.len()));
+            let y_bottom_size = y_bottom.len().min(width);
+
+            bgra_to_yuv420_rows(
+                &coefficients,
+                bgra_row(top_row),
+                bgra_row(bottom_row),
+                &mut y_top[..width],
+                &mut y_bottom[..y_bottom_size],
+                &mut u_row[..u_plane.row_size],
+                &mut v_row[..v_plane.row_size],
+            );
+        });
 }
 
-pub fn bgr_to_yuv_f32(_b: u8, _g: u8, _r: u8) -> (f32, f32, f32) {
-    let r = _r as f32;
-    let g = _g as f32;
-    let b = _b as f32;
+/// Floating point reference of [`bgra_to_yuv_separate`]
+pub fn bgra_to_yuv_separate_f32(
+    source: &FrameFormat,
+    destination: &FrameFormat,
+    color_space: &ColorSpace,
+    bgra_pixels: &[u8],
+    y_pixels: &mut [u8],
+    u_pixels: &mut [u8],
+    v_pixels: &mut [u8],
+) {
+    let (width, height) = (source.get_width(), source.get_height());
+    if width == 0 || height == 0 {
+        return;
+    }
 
-    let y = r * 0.29900 + g * 0.58700 + b * 0.11400;
-    let u = (r * -0.16874 + g * -0.33126 + b * 0.50000) + 128.0;
-    let v = (r * 0.50000 + g * -0.41869 + b * -0.08131) + 128.0;
+    let bgra_plane = source.planes()[0];
+    let [y_plane, u_plane, v_plane] = destination.planes()[..] else {
+        unreachable!()
+    };
+    let chroma_width = u_plane.row_size;
+
+    // Each chroma row is computed along with the (up to) two luma rows it covers
+    y_pixels[..y_plane.size()]
+        .par_chunks_mut(y_plane.stride * 2)
+        .zip(u_pixels[..u_plane.size()].par_chunks_mut(u_plane.stride))
+        .zip(v_pixels[..v_plane.size()].par_chunks_mut(v_plane.stride))
+        .enumerate()
+        .for_each(|(chroma_row, ((y_rows, u_row), v_row))| {
+            let mut u_sums = vec![0.0; chroma_width];
+            let mut v_sums = vec![0.0; chroma_width];
+            let mut counts = vec![0.0; chroma_width];
+
+            for (row_offset, y_row) in y_rows.chunks_mut(y_plane.stride).enumerate() {
+                let row = chroma_row * 2 + row_offset;
+                let offset = row * bgra_plane.stride;
+                let bgra_row = &bgra_pixels[offset..offset + bgra_plane.row_size];
+
+                for (x, (y_value, bgra)) in y_row[..width]
+                    .iter_mut()
+                    .zip(bgra_row.chunks(4))
+                    .enumerate()
+                {
+                    let (y, u, v) = color_space.bgr_to_yuv(bgra[0], bgra[1], bgra[2]);
+
+                    *y_value = clamp_channel(y);
+                    u_sums[x / 2] += u;
+                    v_sums[x / 2] += v;
+                    counts[x / 2] += 1.0;
+                }
+            }
+
+            for x in 0..chroma_width {
+                u_row[x] = clamp_channel(u_sums[x] / counts[x]);
+                v_row[x] = clamp_channel(v_sums[x] / counts[x]);
+            }
+        });
+}
 
-    (y, u, v)
+/// Full range BT.601 conversion
+pub fn bgr_to_yuv_f32(b: u8, g: u8, r: u8) -> (f32, f32, f32) {
+    ColorSpace::default().bgr_to_yuv(b, g, r)
 }
@@ -1 +1,27 @@
-pub mod encoder;
\ No newline at end of file
+use remotia_core::error::ConfigError;
+
+use crate::pixel_format::{FrameFormat, PixelFormat};
+
+pub mod decoder;
+pub mod encoder;
+
+mod simd;
+
+/// Checks that the converters are given a BGRA frame and an I420 one of the same size
+fn check_formats(bgra: &FrameFormat, i420: &FrameFormat) -> Result<(), ConfigError> {
+    let unsupported = |reason: &str| Err(ConfigError::UnsupportedConversion(reason.to_string()));
+
+    if bgra.get_pixel_format() != PixelFormat::BGRA {
+        return unsupported("expected a BGRA frame");
+    }
+
+    if i420.get_pixel_format() != PixelFormat::I420 {
+        return unsupported("expected an I420 frame");
+    }
+
+    if (bgra.get_width(), bgra.get_height()) != (i420.get_width(), i420.get_height()) {
+        return unsupported("YUV420P conversion does not support scaling");
+    }
+
+    Ok(())
+}
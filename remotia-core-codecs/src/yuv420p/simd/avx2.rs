@@ -0,0 +1,239 @@
+//! AVX2 kernels, converting 16 pixels at a time
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::{ForwardCoefficients, InverseCoefficients, BLOCK_OFFSET, BLOCK_SHIFT, ROUNDING, SHIFT};
+
+#[inline]
+#[target_feature(enable = "avx2")]
+fn load(bytes: &[u8; 32]) -> __m256i {
+    // SAFETY: the array holds 32 bytes
+    unsafe { _mm256_loadu_si256(bytes.as_ptr().cast()) }
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+fn to_bytes(vector: __m256i) -> [u8; 32] {
+    let mut bytes = [0; 32];
+
+    // SAFETY: the array holds 32 bytes
+    unsafe { _mm256_storeu_si256(bytes.as_mut_ptr().cast(), vector) };
+    bytes
+}
+
+/// B, G and R values of 8 BGRA pixels
+#[inline]
+#[target_feature(enable = "avx2")]
+fn channels(pixels: &[u8; 32]) -> [__m256i; 3] {
+    let pixels = load(pixels);
+    let mask = _mm256_set1_epi32(0xFF);
+
+    [
+        _mm256_and_si256(pixels, mask),
+        _mm256_and_si256(_mm256_srli_epi32::<8>(pixels), mask),
+        _mm256_and_si256(_mm256_srli_epi32::<16>(pixels), mask),
+    ]
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+fn weighted_sum([b, g, r]: [__m256i; 3], [wb, wg, wr]: [i32; 3], offset: i32) -> __m256i {
+    let sum = _mm256_add_epi32(
+        _mm256_mullo_epi32(b, _mm256_set1_epi32(wb)),
+        _mm256_mullo_epi32(g, _mm256_set1_epi32(wg)),
+    );
+
+    _mm256_add_epi32(
+        sum,
+        _mm256_add_epi32(
+            _mm256_mullo_epi32(r, _mm256_set1_epi32(wr)),
+            _mm256_set1_epi32(offset),
+        ),
+    )
+}
+
+/// Shifts the lanes of both vectors and narrows them to 16 bytes, the 8 values of the first
+/// vector followed by the ones of the second, clamped to 0-255
+#[inline]
+#[target_feature(enable = "avx2")]
+fn narrow<const SHIFT: i32>(first: __m256i, second: __m256i) -> [u8; 16] {
+    // Packing works within each 128 bit half, interleaving groups of 4 values
+    let words = _mm256_packs_epi32(
+        _mm256_srai_epi32::<SHIFT>(first),
+        _mm256_srai_epi32::<SHIFT>(second),
+    );
+    let bytes = _mm256_packus_epi16(words, words);
+    let ordered = _mm256_permutevar8x32_epi32(bytes, _mm256_setr_epi32(0, 4, 1, 5, 0, 4, 1, 5));
+
+    to_bytes(ordered)[..16].try_into().unwrap()
+}
+
+/// Converts the pixels of the row in groups of 16, returning how many were converted
+#[target_feature(enable = "avx2")]
+pub(super) fn luma_row(c: &ForwardCoefficients, bgra: &[u8], y: &mut [u8]) -> usize {
+    let pixels = (bgra.len() / 4).min(y.len()) / 16 * 16;
+    let weights = [c.yb, c.yg, c.yr];
+
+    for (bgra, y) in bgra[..pixels * 4]
+        .chunks_exact(64)
+        .zip(y[..pixels].chunks_exact_mut(16))
+    {
+        let first = weighted_sum(
+            channels(bgra[..32].try_into().unwrap()),
+            weights,
+            c.y_offset,
+        );
+        let second = weighted_sum(
+            channels(bgra[32..].try_into().unwrap()),
+            weights,
+            c.y_offset,
+        );
+
+        y.copy_from_slice(&narrow::<{ SHIFT as i32 }>(first, second));
+    }
+
+    pixels
+}
+
+/// Sums of the B, G and R values of 8 blocks of 2x2 pixels
+#[inline]
+#[target_feature(enable = "avx2")]
+fn block_sums(top: &[u8], bottom: &[u8]) -> [__m256i; 3] {
+    let top_first = channels(top[..32].try_into().unwrap());
+    let top_second = channels(top[32..].try_into().unwrap());
+    let bottom_first = channels(bottom[..32].try_into().unwrap());
+    let bottom_second = channels(bottom[32..].try_into().unwrap());
+
+    let mut sums = [_mm256_setzero_si256(); 3];
+    for channel in 0..3 {
+        let first = _mm256_add_epi32(top_first[channel], bottom_first[channel]);
+        let second = _mm256_add_epi32(top_second[channel], bottom_second[channel]);
+
+        // Adding the columns of each block yields blocks 0, 1, 4, 5 in the lower half and
+        // 2, 3, 6, 7 in the upper one
+        let columns = _mm256_hadd_epi32(first, second);
+        sums[channel] = _mm256_permute4x64_epi64::<0b11_01_10_00>(columns);
+    }
+
+    sums
+}
+
+/// Converts the full blocks of the rows in groups of 8, returning how many were converted
+#[target_feature(enable = "avx2")]
+pub(super) fn chroma_row(
+    c: &ForwardCoefficients,
+    top: &[u8],
+    bottom: &[u8],
+    u: &mut [u8],
+    v: &mut [u8],
+) -> usize {
+    let blocks = (top.len().min(bottom.len()) / 8).min(u.len()).min(v.len()) / 8 * 8;
+
+    for (((top, bottom), u), v) in top[..blocks * 8]
+        .chunks_exact(64)
+        .zip(bottom.chunks_exact(64))
+        .zip(u[..blocks].chunks_exact_mut(8))
+        .zip(v.chunks_exact_mut(8))
+    {
+        let sums = block_sums(top, bottom);
+        let u_values = weighted_sum(sums, [c.ub, c.ug, c.ur], BLOCK_OFFSET);
+        let v_values = weighted_sum(sums, [c.vb, c.vg, c.vr], BLOCK_OFFSET);
+
+        let bytes = narrow::<{ BLOCK_SHIFT as i32 }>(u_values, v_values);
+        u.copy_from_slice(&bytes[..8]);
+        v.copy_from_slice(&bytes[8..]);
+    }
+
+    blocks
+}
+
+/// Widens 8 bytes to 32 bit lanes
+#[inline]
+#[target_feature(enable = "avx2")]
+fn widen(bytes: &[u8]) -> __m256i {
+    let bytes = _mm_set_epi64x(0, i64::from_le_bytes(bytes.try_into().unwrap()));
+    _mm256_cvtepu8_epi32(bytes)
+}
+
+/// Shifts and clamps the channels of 8 pixels, interleaving them into BGRA bytes
+#[inline]
+#[target_feature(enable = "avx2")]
+fn interleave([b, g, r]: [__m256i; 3]) -> [u8; 32] {
+    let shift = |channel| _mm256_srai_epi32::<{ SHIFT as i32 }>(channel);
+
+    // Each 128 bit half holds the B, G, R and A values of 4 pixels
+    let bg = _mm256_packs_epi32(shift(b), shift(g));
+    let ra = _mm256_packs_epi32(shift(r), _mm256_set1_epi32(255));
+    let planar = _mm256_packus_epi16(bg, ra);
+
+    let order = _mm256_setr_epi8(
+        0, 4, 8, 12, 1, 5, 9, 13, 2, 6, 10, 14, 3, 7, 11, 15, 0, 4, 8, 12, 1, 5, 9, 13, 2, 6, 10,
+        14, 3, 7, 11, 15,
+    );
+
+    to_bytes(_mm256_shuffle_epi8(planar, order))
+}
+
+/// Converts the pixels of the row in groups of 16, returning how many were converted
+#[target_feature(enable = "avx2")]
+pub(super) fn bgra_row(
+    c: &InverseCoefficients,
+    y: &[u8],
+    u: &[u8],
+    v: &[u8],
+    bgra: &mut [u8],
+) -> usize {
+    let pixels = y
+        .len()
+        .min(u.len() * 2)
+        .min(v.len() * 2)
+        .min(bgra.len() / 4)
+        / 16
+        * 16;
+
+    for (((y, u), v), bgra) in y[..pixels]
+        .chunks_exact(16)
+        .zip(u.chunks_exact(8))
+        .zip(v.chunks_exact(8))
+        .zip(bgra[..pixels * 4].chunks_exact_mut(64))
+    {
+        let u = _mm256_sub_epi32(widen(u), _mm256_set1_epi32(128));
+        let v = _mm256_sub_epi32(widen(v), _mm256_set1_epi32(128));
+
+        let rounding = _mm256_set1_epi32(ROUNDING);
+        let chroma = [
+            _mm256_add_epi32(_mm256_mullo_epi32(u, _mm256_set1_epi32(c.bu)), rounding),
+            _mm256_sub_epi32(
+                _mm256_sub_epi32(rounding, _mm256_mullo_epi32(u, _mm256_set1_epi32(c.gu))),
+                _mm256_mullo_epi32(v, _mm256_set1_epi32(c.gv)),
+            ),
+            _mm256_add_epi32(_mm256_mullo_epi32(v, _mm256_set1_epi32(c.rv)), rounding),
+        ];
+
+        // Each chroma sample is shared by two consecutive pixels
+        let halves = [
+            _mm256_setr_epi32(0, 0, 1, 1, 2, 2, 3, 3),
+            _mm256_setr_epi32(4, 4, 5, 5, 6, 6, 7, 7),
+        ];
+
+        for (half, (y, bgra)) in halves
+            .into_iter()
+            .zip(y.chunks_exact(8).zip(bgra.chunks_exact_mut(32)))
+        {
+            let luma = _mm256_mullo_epi32(
+                _mm256_sub_epi32(widen(y), _mm256_set1_epi32(c.y_offset)),
+                _mm256_set1_epi32(c.y_scale),
+            );
+
+            let channels = chroma
+                .map(|chroma| _mm256_add_epi32(luma, _mm256_permutevar8x32_epi32(chroma, half)));
+
+            bgra.copy_from_slice(&interleave(channels));
+        }
+    }
+
+    pixels
+}
@@ -0,0 +1,578 @@
+//! Fixed-point BGRA <-> YCbCr 4:2:0 row kernels.
+//!
+//! Rows are converted with explicit SSE2 or AVX2 kernels on x86 and NEON ones on aarch64,
+//! picked at runtime according to the features of the CPU. The scalar kernels convert the
+//! pixels left over by the vector ones and are used on any other target. All of them compute
+//! the same integer operations, so their results are identical.
+
+use std::sync::OnceLock;
+
+use crate::color::{ColorRange, ColorSpace};
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod avx2;
+#[cfg(target_arch = "aarch64")]
+mod neon;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod sse2;
+
+const SHIFT: u32 = 16;
+const ROUNDING: i32 = 1 << (SHIFT - 1);
+
+/// Chroma is computed from the sums of the four pixels of a block, hence the extra shift
+const BLOCK_SHIFT: u32 = SHIFT + 2;
+const BLOCK_OFFSET: i32 = (128 << BLOCK_SHIFT) + (1 << (BLOCK_SHIFT - 1));
+
+fn fixed(value: f32) -> i32 {
+    (value * (1 << SHIFT) as f32).round() as i32
+}
+
+fn range_scales(range: ColorRange) -> (f32, f32, i32) {
+    match range {
+        ColorRange::Full => (1.0, 1.0, 0),
+        ColorRange::Limited => (219.0 / 255.0, 224.0 / 255.0, 16),
+    }
+}
+
+/// BGR to YCbCr coefficients in 16.16 fixed point
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ForwardCoefficients {
+    yb: i32,
+    yg: i32,
+    yr: i32,
+    ub: i32,
+    ug: i32,
+    ur: i32,
+    vb: i32,
+    vg: i32,
+    vr: i32,
+    y_offset: i32,
+}
+
+impl ForwardCoefficients {
+    pub(crate) fn new(color_space: &ColorSpace) -> Self {
+        let (kr, kb) = color_space.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+        let (luma_scale, chroma_scale, luma_offset) = range_scales(color_space.range);
+
+        // Luma coefficients sum up to the scale and chroma ones to zero, so that greys map
+        // exactly to neutral chroma and white to the top of the range
+        let yb = fixed(kb * luma_scale);
+        let yr = fixed(kr * luma_scale);
+        let ug = fixed(-kg / (2.0 * (1.0 - kb)) * chroma_scale);
+        let ur = fixed(-kr / (2.0 * (1.0 - kb)) * chroma_scale);
+        let vb = fixed(-kb / (2.0 * (1.0 - kr)) * chroma_scale);
+        let vg = fixed(-kg / (2.0 * (1.0 - kr)) * chroma_scale);
+
+        Self {
+            yb,
+            yg: fixed(luma_scale) - yb - yr,
+            yr,
+            ub: -ug - ur,
+            ug,
+            ur,
+            vb,
+            vg,
+            vr: -vb - vg,
+            y_offset: (luma_offset << SHIFT) + ROUNDING,
+        }
+    }
+}
+
+/// YCbCr to BGR coefficients in 16.16 fixed point
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InverseCoefficients {
+    y_scale: i32,
+    y_offset: i32,
+    bu: i32,
+    gu: i32,
+    gv: i32,
+    rv: i32,
+}
+
+impl InverseCoefficients {
+    pub(crate) fn new(color_space: &ColorSpace) -> Self {
+        let (kr, kb) = color_space.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+        let (luma_scale, chroma_scale, luma_offset) = range_scales(color_space.range);
+
+        let rv = 2.0 * (1.0 - kr) / chroma_scale;
+        let bu = 2.0 * (1.0 - kb) / chroma_scale;
+
+        Self {
+            y_scale: fixed(1.0 / luma_scale),
+            y_offset: luma_offset,
+            bu: fixed(bu),
+            gu: fixed(kb * bu / kg),
+            gv: fixed(kr * rv / kg),
+            rv: fixed(rv),
+        }
+    }
+}
+
+#[inline(always)]
+fn clamp_fixed(value: i32, shift: u32) -> u8 {
+    (value >> shift).clamp(0, 255) as u8
+}
+
+#[inline(always)]
+fn luma_row(c: &ForwardCoefficients, bgra: &[u8], y: &mut [u8]) {
+    for (pixel, y) in bgra.chunks_exact(4).zip(y.iter_mut()) {
+        let value =
+            c.yb * pixel[0] as i32 + c.yg * pixel[1] as i32 + c.yr * pixel[2] as i32 + c.y_offset;
+
+        *y = clamp_fixed(value, SHIFT);
+    }
+}
+
+/// Chroma of a 2x2 block from the sums of its four B, G and R values
+#[inline(always)]
+fn block_chroma(c: &ForwardCoefficients, b: i32, g: i32, r: i32) -> (u8, u8) {
+    (
+        clamp_fixed(c.ub * b + c.ug * g + c.ur * r + BLOCK_OFFSET, BLOCK_SHIFT),
+        clamp_fixed(c.vb * b + c.vg * g + c.vr * r + BLOCK_OFFSET, BLOCK_SHIFT),
+    )
+}
+
+#[inline(always)]
+fn chroma_row(c: &ForwardCoefficients, top: &[u8], bottom: &[u8], u: &mut [u8], v: &mut [u8]) {
+    let top_blocks = top.chunks_exact(8);
+    let bottom_blocks = bottom.chunks_exact(8);
+
+    // The last pixel of an odd row forms a partial block, counted twice
+    let top_tail = top_blocks.remainder();
+    let bottom_tail = bottom_blocks.remainder();
+
+    for ((top, bottom), (u, v)) in top_blocks
+        .zip(bottom_blocks)
+        .zip(u.iter_mut().zip(v.iter_mut()))
+    {
+        let sum = |channel: usize| {
+            top[channel] as i32
+                + top[channel + 4] as i32
+                + bottom[channel] as i32
+                + bottom[channel + 4] as i32
+        };
+
+        (*u, *v) = block_chroma(c, sum(0), sum(1), sum(2));
+    }
+
+    if top_tail.len() >= 4 {
+        let sum = |channel: usize| 2 * (top_tail[channel] as i32 + bottom_tail[channel] as i32);
+        let last = top.len() / 8;
+
+        (u[last], v[last]) = block_chroma(c, sum(0), sum(1), sum(2));
+    }
+}
+
+#[inline(always)]
+fn bgra_pixel(c: &InverseCoefficients, y: u8, chroma: (i32, i32, i32), pixel: &mut [u8]) {
+    let luma = (y as i32 - c.y_offset) * c.y_scale;
+
+    pixel[0] = clamp_fixed(luma + chroma.0, SHIFT);
+    pixel[1] = clamp_fixed(luma + chroma.1, SHIFT);
+    pixel[2] = clamp_fixed(luma + chroma.2, SHIFT);
+    pixel[3] = 255;
+}
+
+#[inline(always)]
+fn bgra_row(c: &InverseCoefficients, y: &[u8], u: &[u8], v: &[u8], bgra: &mut [u8]) {
+    let chroma = |u: u8, v: u8| {
+        let (u, v) = (u as i32 - 128, v as i32 - 128);
+        (
+            u * c.bu + ROUNDING,
+            ROUNDING - u * c.gu - v * c.gv,
+            v * c.rv + ROUNDING,
+        )
+    };
+
+    let mut pixel_pairs = bgra.chunks_exact_mut(8);
+    let luma_pairs = y.chunks_exact(2);
+    let luma_tail = luma_pairs.remainder();
+
+    for ((pixels, luma), (u, v)) in (&mut pixel_pairs).zip(luma_pairs).zip(u.iter().zip(v)) {
+        let chroma = chroma(*u, *v);
+
+        bgra_pixel(c, luma[0], chroma, &mut pixels[..4]);
+        bgra_pixel(c, luma[1], chroma, &mut pixels[4..]);
+    }
+
+    if let [luma] = luma_tail {
+        let last = y.len() / 2;
+        let pixel = pixel_pairs.into_remainder();
+
+        bgra_pixel(c, *luma, chroma(u[last], v[last]), &mut pixel[..4]);
+    }
+}
+
+/// Instruction set of the kernels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InstructionSet {
+    Scalar,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Sse2,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Avx2,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl InstructionSet {
+    /// Instruction sets supported by the CPU, from the slowest to the fastest
+    fn supported() -> Vec<Self> {
+        let mut supported = vec![InstructionSet::Scalar];
+
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse2") {
+                supported.push(InstructionSet::Sse2);
+            }
+
+            if is_x86_feature_detected!("avx2") {
+                supported.push(InstructionSet::Avx2);
+            }
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            supported.push(InstructionSet::Neon);
+        }
+
+        supported
+    }
+
+    fn fastest() -> Self {
+        static FASTEST: OnceLock<InstructionSet> = OnceLock::new();
+        *FASTEST.get_or_init(|| *Self::supported().last().unwrap())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn bgra_to_yuv420_rows_with(
+    instruction_set: InstructionSet,
+    c: &ForwardCoefficients,
+    top: &[u8],
+    bottom: &[u8],
+    y_top: &mut [u8],
+    y_bottom: &mut [u8],
+    u: &mut [u8],
+    v: &mut [u8],
+) {
+    // SAFETY: the instruction set is supported by the CPU
+    let (top_pixels, bottom_pixels, blocks) = match instruction_set {
+        InstructionSet::Scalar => (0, 0, 0),
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        InstructionSet::Sse2 => unsafe {
+            (
+                sse2::luma_row(c, top, y_top),
+                sse2::luma_row(c, bottom, y_bottom),
+                sse2::chroma_row(c, top, bottom, u, v),
+            )
+        },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        InstructionSet::Avx2 => unsafe {
+            (
+                avx2::luma_row(c, top, y_top),
+                avx2::luma_row(c, bottom, y_bottom),
+                avx2::chroma_row(c, top, bottom, u, v),
+            )
+        },
+        #[cfg(target_arch = "aarch64")]
+        InstructionSet::Neon => unsafe {
+            (
+                neon::luma_row(c, top, y_top),
+                neon::luma_row(c, bottom, y_bottom),
+                neon::chroma_row(c, top, bottom, u, v),
+            )
+        },
+    };
+
+    luma_row(c, &top[top_pixels * 4..], &mut y_top[top_pixels..]);
+    luma_row(
+        c,
+        &bottom[bottom_pixels * 4..],
+        &mut y_bottom[bottom_pixels..],
+    );
+    chroma_row(
+        c,
+        &top[blocks * 8..],
+        &bottom[blocks * 8..],
+        &mut u[blocks..],
+        &mut v[blocks..],
+    );
+}
+
+fn yuv420_to_bgra_row_with(
+    instruction_set: InstructionSet,
+    c: &InverseCoefficients,
+    y: &[u8],
+    u: &[u8],
+    v: &[u8],
+    bgra: &mut [u8],
+) {
+    // SAFETY: the instruction set is supported by the CPU
+    let pixels = match instruction_set {
+        InstructionSet::Scalar => 0,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        InstructionSet::Sse2 => unsafe { sse2::bgra_row(c, y, u, v, bgra) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        InstructionSet::Avx2 => unsafe { avx2::bgra_row(c, y, u, v, bgra) },
+        #[cfg(target_arch = "aarch64")]
+        InstructionSet::Neon => unsafe { neon::bgra_row(c, y, u, v, bgra) },
+    };
+
+    // Vector kernels convert an even amount of pixels, so the chroma samples stay aligned
+    bgra_row(
+        c,
+        &y[pixels..],
+        &u[pixels / 2..],
+        &v[pixels / 2..],
+        &mut bgra[pixels * 4..],
+    );
+}
+
+/// Converts two BGRA rows into their luma rows and the chroma row they share. The last row
+/// of an odd frame is passed as both top and bottom, with an empty bottom luma row.
+pub(crate) fn bgra_to_yuv420_rows(
+    c: &ForwardCoefficients,
+    top: &[u8],
+    bottom: &[u8],
+    y_top: &mut [u8],
+    y_bottom: &mut [u8],
+    u: &mut [u8],
+    v: &mut [u8],
+) {
+    bgra_to_yuv420_rows_with(
+        InstructionSet::fastest(),
+        c,
+        top,
+        bottom,
+        y_top,
+        y_bottom,
+        u,
+        v,
+    )
+}
+
+/// Converts a luma row and its chroma row into a BGRA row
+pub(crate) fn yuv420_to_bgra_row(
+    c: &InverseCoefficients,
+    y: &[u8],
+    u: &[u8],
+    v: &[u8],
+    bgra: &mut [u8],
+) {
+    yuv420_to_bgra_row_with(InstructionSet::fastest(), c, y, u, v, bgra)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{clamp_channel, ColorMatrix, ColorRange, ColorSpace};
+
+    use super::{
+        bgra_to_yuv420_rows, bgra_to_yuv420_rows_with, yuv420_to_bgra_row, yuv420_to_bgra_row_with,
+        ForwardCoefficients, InstructionSet, InverseCoefficients,
+    };
+
+    const WIDTHS: [usize; 6] = [1, 2, 7, 16, 33, 64];
+
+    /// Fixed point results may only differ from the reference when rounding a tie
+    const MAX_ERROR: u8 = 1;
+
+    fn color_spaces() -> impl Iterator<Item = ColorSpace> {
+        [ColorMatrix::BT601, ColorMatrix::BT709, ColorMatrix::BT2020]
+            .into_iter()
+            .flat_map(|matrix| {
+                [ColorRange::Full, ColorRange::Limited]
+                    .into_iter()
+                    .map(move |range| ColorSpace::new(matrix, range))
+            })
+    }
+
+    /// Deterministic pseudo-random bytes
+    fn noise(seed: u32, size: usize) -> Vec<u8> {
+        (0..size as u32)
+            .map(|index| (index.wrapping_add(seed).wrapping_mul(2654435761) >> 13) as u8)
+            .collect()
+    }
+
+    fn max_difference(expected: &[u8], found: &[u8]) -> u8 {
+        expected
+            .iter()
+            .zip(found)
+            .map(|(expected, found)| expected.abs_diff(*found))
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn forward_kernel_matches_the_f32_reference() {
+        for color_space in color_spaces() {
+            let coefficients = ForwardCoefficients::new(&color_space);
+
+            for width in WIDTHS {
+                let chroma_width = width.div_ceil(2);
+                let top = noise(width as u32, width * 4);
+                let bottom = noise(width as u32 + 1, width * 4);
+
+                let mut y = vec![0; width * 2];
+                let (mut u, mut v) = (vec![0; chroma_width], vec![0; chroma_width]);
+                let (y_top, y_bottom) = y.split_at_mut(width);
+                bgra_to_yuv420_rows(
+                    &coefficients,
+                    &top,
+                    &bottom,
+                    y_top,
+                    y_bottom,
+                    &mut u,
+                    &mut v,
+                );
+
+                let mut expected_y = Vec::new();
+                let mut u_sums = vec![0.0; chroma_width];
+                let mut v_sums = vec![0.0; chroma_width];
+                let mut counts = vec![0.0; chroma_width];
+
+                for row in [&top, &bottom] {
+                    for (x, pixel) in row.chunks(4).enumerate() {
+                        let (y, u, v) = color_space.bgr_to_yuv(pixel[0], pixel[1], pixel[2]);
+
+                        expected_y.push(clamp_channel(y));
+                        u_sums[x / 2] += u;
+                        v_sums[x / 2] += v;
+                        counts[x / 2] += 1.0;
+                    }
+                }
+
+                let expected_u: Vec<u8> = (0..chroma_width)
+                    .map(|x| clamp_channel(u_sums[x] / counts[x]))
+                    .collect();
+                let expected_v: Vec<u8> = (0..chroma_width)
+                    .map(|x| clamp_channel(v_sums[x] / counts[x]))
+                    .collect();
+
+                for (expected, found) in [(&expected_y, &y), (&expected_u, &u), (&expected_v, &v)] {
+                    assert!(
+                        max_difference(expected, found) <= MAX_ERROR,
+                        "{:?}, width {}: expected {:?}, found {:?}",
+                        color_space,
+                        width,
+                        expected,
+                        found
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_kernel_matches_the_f32_reference() {
+        for color_space in color_spaces() {
+            let coefficients = InverseCoefficients::new(&color_space);
+
+            for width in WIDTHS {
+                let chroma_width = width.div_ceil(2);
+                let y = noise(width as u32, width);
+                let u = noise(width as u32 + 1, chroma_width);
+                let v = noise(width as u32 + 2, chroma_width);
+
+                let mut bgra = vec![0; width * 4];
+                yuv420_to_bgra_row(&coefficients, &y, &u, &v, &mut bgra);
+
+                let expected: Vec<u8> = (0..width)
+                    .flat_map(|x| {
+                        let (b, g, r) = color_space.yuv_to_bgr(y[x], u[x / 2], v[x / 2]);
+                        [b, g, r, 255]
+                    })
+                    .collect();
+
+                assert!(
+                    max_difference(&expected, &bgra) <= MAX_ERROR,
+                    "{:?}, width {}: expected {:?}, found {:?}",
+                    color_space,
+                    width,
+                    expected,
+                    bgra
+                );
+            }
+        }
+    }
+
+    /// Covers both the vector loops and the scalar leftovers of each instruction set
+    const VECTOR_WIDTHS: [usize; 12] = [1, 2, 7, 8, 15, 16, 17, 31, 32, 33, 64, 100];
+
+    #[test]
+    fn forward_kernels_match_the_scalar_one() {
+        for color_space in color_spaces() {
+            let coefficients = ForwardCoefficients::new(&color_space);
+
+            for width in VECTOR_WIDTHS {
+                let chroma_width = width.div_ceil(2);
+                let top = noise(width as u32, width * 4);
+                let bottom = noise(width as u32 + 1, width * 4);
+
+                let convert = |instruction_set| {
+                    let mut y = vec![0; width * 2];
+                    let (mut u, mut v) = (vec![0; chroma_width], vec![0; chroma_width]);
+                    let (y_top, y_bottom) = y.split_at_mut(width);
+                    bgra_to_yuv420_rows_with(
+                        instruction_set,
+                        &coefficients,
+                        &top,
+                        &bottom,
+                        y_top,
+                        y_bottom,
+                        &mut u,
+                        &mut v,
+                    );
+
+                    (y, u, v)
+                };
+
+                let expected = convert(InstructionSet::Scalar);
+                for instruction_set in InstructionSet::supported() {
+                    assert_eq!(
+                        convert(instruction_set),
+                        expected,
+                        "{:?}, {:?}, width {}",
+                        instruction_set,
+                        color_space,
+                        width
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_kernels_match_the_scalar_one() {
+        for color_space in color_spaces() {
+            let coefficients = InverseCoefficients::new(&color_space);
+
+            for width in VECTOR_WIDTHS {
+                let chroma_width = width.div_ceil(2);
+                let y = noise(width as u32, width);
+                let u = noise(width as u32 + 1, chroma_width);
+                let v = noise(width as u32 + 2, chroma_width);
+
+                let convert = |instruction_set| {
+                    let mut bgra = vec![0; width * 4];
+                    yuv420_to_bgra_row_with(instruction_set, &coefficients, &y, &u, &v, &mut bgra);
+                    bgra
+                };
+
+                let expected = convert(InstructionSet::Scalar);
+                for instruction_set in InstructionSet::supported() {
+                    assert_eq!(
+                        convert(instruction_set),
+                        expected,
+                        "{:?}, {:?}, width {}",
+                        instruction_set,
+                        color_space,
+                        width
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! NEON kernels, converting 8 pixels at a time
+
+use std::arch::aarch64::*;
+
+use super::{ForwardCoefficients, InverseCoefficients, BLOCK_OFFSET, BLOCK_SHIFT, ROUNDING, SHIFT};
+
+#[inline]
+#[target_feature(enable = "neon")]
+fn to_bytes(vector: uint8x8_t) -> [u8; 8] {
+    let mut bytes = [0; 8];
+
+    // SAFETY: the array holds 8 bytes
+    unsafe { vst1_u8(bytes.as_mut_ptr(), vector) };
+    bytes
+}
+
+/// B, G and R values of 8 BGRA pixels
+#[inline]
+#[target_feature(enable = "neon")]
+fn channels(pixels: &[u8]) -> [uint8x8_t; 3] {
+    assert!(pixels.len() >= 32);
+
+    // SAFETY: the slice holds at least 32 bytes
+    let pixels = unsafe { vld4_u8(pixels.as_ptr()) };
+    [pixels.0, pixels.1, pixels.2]
+}
+
+/// Widens 8 bytes to two vectors of 32 bit lanes
+#[inline]
+#[target_feature(enable = "neon")]
+fn widen(bytes: uint8x8_t) -> [int32x4_t; 2] {
+    let words = vmovl_u8(bytes);
+
+    [
+        vreinterpretq_s32_u32(vmovl_u16(vget_low_u16(words))),
+        vreinterpretq_s32_u32(vmovl_high_u16(words)),
+    ]
+}
+
+#[inline]
+#[target_feature(enable = "neon")]
+fn weighted_sum([b, g, r]: [int32x4_t; 3], [wb, wg, wr]: [i32; 3], offset: i32) -> int32x4_t {
+    let sum = vmlaq_n_s32(vdupq_n_s32(offset), b, wb);
+    vmlaq_n_s32(vmlaq_n_s32(sum, g, wg), r, wr)
+}
+
+/// Shifts the lanes of both vectors and narrows them to 8 bytes, clamped to 0-255
+#[inline]
+#[target_feature(enable = "neon")]
+fn narrow<const SHIFT: i32>(low: int32x4_t, high: int32x4_t) -> uint8x8_t {
+    let words = vcombine_s16(
+        vqmovn_s32(vshrq_n_s32::<SHIFT>(low)),
+        vqmovn_s32(vshrq_n_s32::<SHIFT>(high)),
+    );
+
+    vqmovun_s16(words)
+}
+
+/// Converts the pixels of the row in groups of 8, returning how many were converted
+#[target_feature(enable = "neon")]
+pub(super) fn luma_row(c: &ForwardCoefficients, bgra: &[u8], y: &mut [u8]) -> usize {
+    let pixels = (bgra.len() / 4).min(y.len()) / 8 * 8;
+    let weights = [c.yb, c.yg, c.yr];
+
+    for (bgra, y) in bgra[..pixels * 4]
+        .chunks_exact(32)
+        .zip(y[..pixels].chunks_exact_mut(8))
+    {
+        let [[b_low, b_high], [g_low, g_high], [r_low, r_high]] = channels(bgra).map(widen);
+
+        let low = weighted_sum([b_low, g_low, r_low], weights, c.y_offset);
+        let high = weighted_sum([b_high, g_high, r_high], weights, c.y_offset);
+
+        y.copy_from_slice(&to_bytes(narrow::<{ SHIFT as i32 }>(low, high)));
+    }
+
+    pixels
+}
+
+/// Converts the full blocks of the rows in groups of 4, returning how many were converted
+#[target_feature(enable = "neon")]
+pub(super) fn chroma_row(
+    c: &ForwardCoefficients,
+    top: &[u8],
+    bottom: &[u8],
+    u: &mut [u8],
+    v: &mut [u8],
+) -> usize {
+    let blocks = (top.len().min(bottom.len()) / 8).min(u.len()).min(v.len()) / 4 * 4;
+
+    for (((top, bottom), u), v) in top[..blocks * 8]
+        .chunks_exact(32)
+        .zip(bottom.chunks_exact(32))
+        .zip(u[..blocks].chunks_exact_mut(4))
+        .zip(v.chunks_exact_mut(4))
+    {
+        let top = channels(top);
+        let bottom = channels(bottom);
+
+        // Adds the columns of each block, then its rows
+        let sums: [int32x4_t; 3] = [0, 1, 2].map(|channel| {
+            let sum = vadd_u16(vpaddl_u8(top[channel]), vpaddl_u8(bottom[channel]));
+            vreinterpretq_s32_u32(vmovl_u16(sum))
+        });
+
+        let u_values = weighted_sum(sums, [c.ub, c.ug, c.ur], BLOCK_OFFSET);
+        let v_values = weighted_sum(sums, [c.vb, c.vg, c.vr], BLOCK_OFFSET);
+
+        let bytes = to_bytes(narrow::<{ BLOCK_SHIFT as i32 }>(u_values, v_values));
+        u.copy_from_slice(&bytes[..4]);
+        v.copy_from_slice(&bytes[4..]);
+    }
+
+    blocks
+}
+
+/// Widens 4 bytes to 32 bit lanes, centered on zero
+#[inline]
+#[target_feature(enable = "neon")]
+fn centered_chroma(bytes: &[u8]) -> int32x4_t {
+    let bytes = vcreate_u8(u32::from_le_bytes(bytes.try_into().unwrap()) as u64);
+    vsubq_s32(widen(bytes)[0], vdupq_n_s32(128))
+}
+
+/// Converts the pixels of the row in groups of 8, returning how many were converted
+#[target_feature(enable = "neon")]
+pub(super) fn bgra_row(
+    c: &InverseCoefficients,
+    y: &[u8],
+    u: &[u8],
+    v: &[u8],
+    bgra: &mut [u8],
+) -> usize {
+    let pixels = y
+        .len()
+        .min(u.len() * 2)
+        .min(v.len() * 2)
+        .min(bgra.len() / 4)
+        / 8
+        * 8;
+
+    for (((y, u), v), bgra) in y[..pixels]
+        .chunks_exact(8)
+        .zip(u.chunks_exact(4))
+        .zip(v.chunks_exact(4))
+        .zip(bgra[..pixels * 4].chunks_exact_mut(32))
+    {
+        let u = centered_chroma(u);
+        let v = centered_chroma(v);
+
+        let rounding = vdupq_n_s32(ROUNDING);
+        let chroma = [
+            vmlaq_n_s32(rounding, u, c.bu),
+            vmlsq_n_s32(vmlsq_n_s32(rounding, u, c.gu), v, c.gv),
+            vmlaq_n_s32(rounding, v, c.rv),
+        ];
+
+        let y = vcreate_u8(u64::from_le_bytes(y.try_into().unwrap()));
+        let luma =
+            widen(y).map(|luma| vmulq_n_s32(vsubq_s32(luma, vdupq_n_s32(c.y_offset)), c.y_scale));
+
+        // Each chroma sample is shared by two consecutive pixels
+        let [b, g, r] = chroma.map(|chroma| {
+            narrow::<{ SHIFT as i32 }>(
+                vaddq_s32(luma[0], vzip1q_s32(chroma, chroma)),
+                vaddq_s32(luma[1], vzip2q_s32(chroma, chroma)),
+            )
+        });
+
+        // SAFETY: the chunk holds 32 bytes
+        unsafe { vst4_u8(bgra.as_mut_ptr(), uint8x8x4_t(b, g, r, vdup_n_u8(255))) };
+    }
+
+    pixels
+}
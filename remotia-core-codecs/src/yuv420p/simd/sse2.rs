@@ -0,0 +1,240 @@
+//! SSE2 kernels, converting 8 pixels at a time. SSE2 lacks a 32 bit multiplication, which is
+//! emulated through the 64 bit one.
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::{ForwardCoefficients, InverseCoefficients, BLOCK_OFFSET, BLOCK_SHIFT, ROUNDING, SHIFT};
+
+#[inline]
+#[target_feature(enable = "sse2")]
+fn load(bytes: &[u8; 16]) -> __m128i {
+    // SAFETY: the array holds 16 bytes
+    unsafe { _mm_loadu_si128(bytes.as_ptr().cast()) }
+}
+
+#[inline]
+#[target_feature(enable = "sse2")]
+fn to_bytes(vector: __m128i) -> [u8; 16] {
+    let mut bytes = [0; 16];
+
+    // SAFETY: the array holds 16 bytes
+    unsafe { _mm_storeu_si128(bytes.as_mut_ptr().cast(), vector) };
+    bytes
+}
+
+/// Low 32 bits of the products of the lanes
+#[inline]
+#[target_feature(enable = "sse2")]
+fn mullo(a: __m128i, b: __m128i) -> __m128i {
+    let even = _mm_mul_epu32(a, b);
+    let odd = _mm_mul_epu32(_mm_srli_epi64::<32>(a), _mm_srli_epi64::<32>(b));
+
+    _mm_unpacklo_epi32(
+        _mm_shuffle_epi32::<0b00_00_10_00>(even),
+        _mm_shuffle_epi32::<0b00_00_10_00>(odd),
+    )
+}
+
+/// B, G and R values of 4 BGRA pixels
+#[inline]
+#[target_feature(enable = "sse2")]
+fn channels(pixels: &[u8; 16]) -> [__m128i; 3] {
+    let pixels = load(pixels);
+    let mask = _mm_set1_epi32(0xFF);
+
+    [
+        _mm_and_si128(pixels, mask),
+        _mm_and_si128(_mm_srli_epi32::<8>(pixels), mask),
+        _mm_and_si128(_mm_srli_epi32::<16>(pixels), mask),
+    ]
+}
+
+#[inline]
+#[target_feature(enable = "sse2")]
+fn weighted_sum([b, g, r]: [__m128i; 3], [wb, wg, wr]: [i32; 3], offset: i32) -> __m128i {
+    let sum = _mm_add_epi32(mullo(b, _mm_set1_epi32(wb)), mullo(g, _mm_set1_epi32(wg)));
+
+    _mm_add_epi32(
+        sum,
+        _mm_add_epi32(mullo(r, _mm_set1_epi32(wr)), _mm_set1_epi32(offset)),
+    )
+}
+
+/// Shifts the lanes of both vectors and narrows them to the first 8 bytes, clamped to 0-255
+#[inline]
+#[target_feature(enable = "sse2")]
+fn narrow<const SHIFT: i32>(low: __m128i, high: __m128i) -> [u8; 16] {
+    let words = _mm_packs_epi32(_mm_srai_epi32::<SHIFT>(low), _mm_srai_epi32::<SHIFT>(high));
+
+    to_bytes(_mm_packus_epi16(words, words))
+}
+
+/// Converts the pixels of the row in groups of 8, returning how many were converted
+#[target_feature(enable = "sse2")]
+pub(super) fn luma_row(c: &ForwardCoefficients, bgra: &[u8], y: &mut [u8]) -> usize {
+    let pixels = (bgra.len() / 4).min(y.len()) / 8 * 8;
+    let weights = [c.yb, c.yg, c.yr];
+
+    for (bgra, y) in bgra[..pixels * 4]
+        .chunks_exact(32)
+        .zip(y[..pixels].chunks_exact_mut(8))
+    {
+        let low = weighted_sum(
+            channels(bgra[..16].try_into().unwrap()),
+            weights,
+            c.y_offset,
+        );
+        let high = weighted_sum(
+            channels(bgra[16..].try_into().unwrap()),
+            weights,
+            c.y_offset,
+        );
+
+        y.copy_from_slice(&narrow::<{ SHIFT as i32 }>(low, high)[..8]);
+    }
+
+    pixels
+}
+
+/// Sums of the B, G and R values of 4 blocks of 2x2 pixels
+#[inline]
+#[target_feature(enable = "sse2")]
+fn block_sums(top: &[u8], bottom: &[u8]) -> [__m128i; 3] {
+    let top_low = channels(top[..16].try_into().unwrap());
+    let top_high = channels(top[16..].try_into().unwrap());
+    let bottom_low = channels(bottom[..16].try_into().unwrap());
+    let bottom_high = channels(bottom[16..].try_into().unwrap());
+
+    let mut sums = [_mm_setzero_si128(); 3];
+    for channel in 0..3 {
+        let low = _mm_castsi128_ps(_mm_add_epi32(top_low[channel], bottom_low[channel]));
+        let high = _mm_castsi128_ps(_mm_add_epi32(top_high[channel], bottom_high[channel]));
+
+        // Adds the columns of each block
+        let left = _mm_castps_si128(_mm_shuffle_ps::<0b10_00_10_00>(low, high));
+        let right = _mm_castps_si128(_mm_shuffle_ps::<0b11_01_11_01>(low, high));
+        sums[channel] = _mm_add_epi32(left, right);
+    }
+
+    sums
+}
+
+/// Converts the full blocks of the rows in groups of 4, returning how many were converted
+#[target_feature(enable = "sse2")]
+pub(super) fn chroma_row(
+    c: &ForwardCoefficients,
+    top: &[u8],
+    bottom: &[u8],
+    u: &mut [u8],
+    v: &mut [u8],
+) -> usize {
+    let blocks = (top.len().min(bottom.len()) / 8).min(u.len()).min(v.len()) / 4 * 4;
+
+    for (((top, bottom), u), v) in top[..blocks * 8]
+        .chunks_exact(32)
+        .zip(bottom.chunks_exact(32))
+        .zip(u[..blocks].chunks_exact_mut(4))
+        .zip(v.chunks_exact_mut(4))
+    {
+        let sums = block_sums(top, bottom);
+        let u_values = weighted_sum(sums, [c.ub, c.ug, c.ur], BLOCK_OFFSET);
+        let v_values = weighted_sum(sums, [c.vb, c.vg, c.vr], BLOCK_OFFSET);
+
+        let bytes = narrow::<{ BLOCK_SHIFT as i32 }>(u_values, v_values);
+        u.copy_from_slice(&bytes[..4]);
+        v.copy_from_slice(&bytes[4..8]);
+    }
+
+    blocks
+}
+
+/// Widens 4 bytes to 32 bit lanes
+#[inline]
+#[target_feature(enable = "sse2")]
+fn widen(bytes: &[u8]) -> __m128i {
+    let zero = _mm_setzero_si128();
+    let bytes = _mm_cvtsi32_si128(i32::from_le_bytes(bytes.try_into().unwrap()));
+
+    _mm_unpacklo_epi16(_mm_unpacklo_epi8(bytes, zero), zero)
+}
+
+/// Clamps the values of 8 pixels to 0-255, as 16 bit lanes
+#[inline]
+#[target_feature(enable = "sse2")]
+fn clamp_words(low: __m128i, high: __m128i) -> __m128i {
+    let words = _mm_packs_epi32(
+        _mm_srai_epi32::<{ SHIFT as i32 }>(low),
+        _mm_srai_epi32::<{ SHIFT as i32 }>(high),
+    );
+
+    _mm_max_epi16(
+        _mm_min_epi16(words, _mm_set1_epi16(255)),
+        _mm_setzero_si128(),
+    )
+}
+
+/// Converts the pixels of the row in groups of 8, returning how many were converted
+#[target_feature(enable = "sse2")]
+pub(super) fn bgra_row(
+    c: &InverseCoefficients,
+    y: &[u8],
+    u: &[u8],
+    v: &[u8],
+    bgra: &mut [u8],
+) -> usize {
+    let pixels = y
+        .len()
+        .min(u.len() * 2)
+        .min(v.len() * 2)
+        .min(bgra.len() / 4)
+        / 8
+        * 8;
+
+    for (((y, u), v), bgra) in y[..pixels]
+        .chunks_exact(8)
+        .zip(u.chunks_exact(4))
+        .zip(v.chunks_exact(4))
+        .zip(bgra[..pixels * 4].chunks_exact_mut(32))
+    {
+        let u = _mm_sub_epi32(widen(u), _mm_set1_epi32(128));
+        let v = _mm_sub_epi32(widen(v), _mm_set1_epi32(128));
+
+        let rounding = _mm_set1_epi32(ROUNDING);
+        let chroma = [
+            _mm_add_epi32(mullo(u, _mm_set1_epi32(c.bu)), rounding),
+            _mm_sub_epi32(
+                _mm_sub_epi32(rounding, mullo(u, _mm_set1_epi32(c.gu))),
+                mullo(v, _mm_set1_epi32(c.gv)),
+            ),
+            _mm_add_epi32(mullo(v, _mm_set1_epi32(c.rv)), rounding),
+        ];
+
+        let luma = [widen(&y[..4]), widen(&y[4..])].map(|luma| {
+            mullo(
+                _mm_sub_epi32(luma, _mm_set1_epi32(c.y_offset)),
+                _mm_set1_epi32(c.y_scale),
+            )
+        });
+
+        // Each chroma sample is shared by two consecutive pixels
+        let mut channels = [_mm_setzero_si128(); 3];
+        for (channel, chroma) in channels.iter_mut().zip(chroma) {
+            *channel = clamp_words(
+                _mm_add_epi32(luma[0], _mm_unpacklo_epi32(chroma, chroma)),
+                _mm_add_epi32(luma[1], _mm_unpackhi_epi32(chroma, chroma)),
+            );
+        }
+
+        let [b, g, r] = channels;
+        let bg = _mm_or_si128(b, _mm_slli_epi16::<8>(g));
+        let ra = _mm_or_si128(r, _mm_set1_epi16(0xFF00u16 as i16));
+
+        bgra[..16].copy_from_slice(&to_bytes(_mm_unpacklo_epi16(bg, ra)));
+        bgra[16..].copy_from_slice(&to_bytes(_mm_unpackhi_epi16(bg, ra)));
+    }
+
+    pixels
+}
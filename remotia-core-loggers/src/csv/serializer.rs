@@ -3,7 +3,12 @@ use std::fs::File;
 use async_trait::async_trait;
 
 use csv::Writer;
-use remotia_core::{traits::FrameProcessor, types::FrameData};
+use log::warn;
+use remotia_core::{
+    error::ProcessorError,
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
 
 pub struct CSVFrameDataSerializer {
     writer: Writer<File>,
@@ -29,26 +34,46 @@ impl CSVFrameDataSerializer {
         self.values_to_log.push(value.to_string());
         self
     }
-}
 
-#[async_trait]
-impl FrameProcessor for CSVFrameDataSerializer {
-    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+    fn write_frame_data(&mut self, frame_data: &FrameData) -> Result<(), ProcessorError> {
+        let record = self
+            .values_to_log
+            .iter()
+            .map(|key| frame_data.try_get_value(key).map(|value| value.to_string()))
+            .collect::<Result<Vec<String>, _>>()?;
+
         if !self.columns_written {
             self.writer
                 .write_record(self.values_to_log.clone())
-                .unwrap();
+                .map_err(csv_error)?;
             self.columns_written = true;
         }
 
-        let record = self
-            .values_to_log
-            .iter()
-            .map(|key| format!("{}", frame_data.get(key)));
+        self.writer.write_record(record).map_err(csv_error)?;
+        self.writer.flush()?;
 
-        self.writer.write_record(record).unwrap();
-        self.writer.flush().unwrap();
+        Ok(())
+    }
+}
+
+fn csv_error(error: csv::Error) -> ProcessorError {
+    ProcessorError::Io(error.into())
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for CSVFrameDataSerializer {
+    async fn try_process(&mut self, frame_data: FrameData) -> ProcessorResult {
+        match self.write_frame_data(&frame_data) {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
+    }
+
+    async fn flush(&mut self) -> Vec<FrameData> {
+        if let Err(error) = self.writer.flush() {
+            warn!("Unable to flush CSV writer: {}", error);
+        }
 
-        Some(frame_data)
+        Vec::new()
     }
 }
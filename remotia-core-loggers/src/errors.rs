@@ -45,8 +45,8 @@ impl ConsoleDropReasonLogger {
 
     // Logging functions
     fn print_round_stats(&self) {
-        if self.header.is_some() {
-            info!("{}", self.header.as_ref().unwrap());
+        if let Some(header) = &self.header {
+            info!("{}", header);
         }
 
         let dropped_frames_count = self.logged_reasons.len() as u128;
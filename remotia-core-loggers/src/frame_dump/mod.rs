@@ -7,7 +7,11 @@ use std::{
 use async_trait::async_trait;
 
 use log::debug;
-use remotia_core::{traits::FrameProcessor, types::FrameData};
+use remotia_core::{
+    error::ProcessorError,
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
 
 pub struct RawFrameDumper {
     buffer_id: String,
@@ -31,21 +35,30 @@ impl RawFrameDumper {
         self.key = key.to_string();
         self
     }
-}
 
-#[async_trait]
-impl FrameProcessor for RawFrameDumper {
-    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
-        let frame_id = frame_data.get(&self.key);
-        let buffer = frame_data.get_writable_buffer_ref(&self.buffer_id).unwrap();
+    fn dump(&self, frame_data: &mut FrameData) -> Result<(), ProcessorError> {
+        let frame_id = frame_data.try_get(&self.key)?;
+        let buffer = frame_data
+            .get_writable_buffer_ref(&self.buffer_id)
+            .ok_or_else(|| ProcessorError::MissingBuffer(self.buffer_id.clone()))?;
 
         debug!("Dumping frame {}", frame_id);
 
         let mut file_path = self.folder.clone();
         file_path.push(format!("{}.bgra", frame_id));
-        let mut output_file = File::create(file_path.as_path()).unwrap();
-        output_file.write_all(buffer).unwrap();
+        let mut output_file = File::create(file_path.as_path())?;
+        output_file.write_all(buffer)?;
+
+        Ok(())
+    }
+}
 
-        Some(frame_data)
+#[async_trait]
+impl FallibleFrameProcessor for RawFrameDumper {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        match self.dump(&mut frame_data) {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
     }
 }
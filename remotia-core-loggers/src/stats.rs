@@ -2,7 +2,7 @@ use std::time::{Duration, Instant};
 
 use remotia_core::{
     traits::FrameProcessor,
-    types::FrameData,
+    types::{FrameData, StatValue},
 };
 
 use async_trait::async_trait;
@@ -48,8 +48,8 @@ impl ConsoleAverageStatsLogger {
 
     // Logging functions
     fn print_round_stats(&self) {
-        if self.header.is_some() {
-            info!("{}", self.header.as_ref().unwrap());
+        if let Some(header) = &self.header {
+            info!("{}", header);
         }
 
         let logged_frames_count = self.logged_frames.len() as u128;
@@ -62,14 +62,41 @@ impl ConsoleAverageStatsLogger {
         }
 
         self.values_to_log.iter().for_each(|value| {
-            let avg = self
+            let values: Vec<&StatValue> = self
                 .logged_frames
                 .iter()
-                .map(|frame| frame.get(value))
-                .sum::<u128>()
-                / logged_frames_count;
+                .filter_map(|frame| frame.try_get_value(value).ok())
+                .collect();
 
-            info!("Average {}: {}", value, avg);
+            if values.is_empty() {
+                info!("No {} values", value);
+                return;
+            }
+
+            let unsigned_values: Option<Vec<u128>> = values
+                .iter()
+                .map(|value| match value {
+                    StatValue::Unsigned(value) => Some(*value),
+                    _ => None,
+                })
+                .collect();
+
+            if let Some(unsigned_values) = unsigned_values {
+                let avg = unsigned_values.iter().sum::<u128>() / unsigned_values.len() as u128;
+                info!("Average {}: {}", value, avg);
+                return;
+            }
+
+            let numeric_values: Option<Vec<f64>> =
+                values.iter().map(|value| value.as_f64()).collect();
+
+            match numeric_values {
+                Some(numeric_values) => {
+                    let avg = numeric_values.iter().sum::<f64>() / numeric_values.len() as f64;
+                    info!("Average {}: {:.3}", value, avg);
+                }
+                None => info!("Last {}: {}", value, values.last().unwrap()),
+            }
         });
     }
 
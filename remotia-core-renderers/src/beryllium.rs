@@ -5,6 +5,7 @@ use beryllium::{
 };
 use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
 use remotia_core::{traits::FrameProcessor, types::FrameData};
+use remotia_core_codecs::pixel_format::{convert_packed, PixelFormat};
 use zstring::zstr;
 
 use async_trait::async_trait;
@@ -75,25 +76,19 @@ pub fn create_gl_window(width: i32, height: i32) -> GlWindow {
 }
 
 pub fn packed_bgr_to_packed_rgba(packed_bgr_buffer: &[u8], packed_rgba_buffer: &mut [u8]) {
-    let pixels_count = packed_rgba_buffer.len() / 4;
-
-    for i in 0..pixels_count {
-        packed_rgba_buffer[i * 4 + 2] = packed_bgr_buffer[i * 3];
-        packed_rgba_buffer[i * 4 + 1] = packed_bgr_buffer[i * 3 + 1];
-        packed_rgba_buffer[i * 4] = packed_bgr_buffer[i * 3 + 2];
-    }
+    convert_packed(
+        PixelFormat::BGR24,
+        PixelFormat::RGBA,
+        packed_bgr_buffer,
+        packed_rgba_buffer,
+    );
 }
 
 pub fn packed_bgra_to_packed_rgba(packed_bgra_buffer: &[u8], packed_rgba_buffer: &mut [u8]) {
-    let pixels_count = packed_rgba_buffer.len() / 4;
-
-    for i in 0..pixels_count {
-        // BGR -> RGB channels
-        packed_rgba_buffer[i * 4] = packed_bgra_buffer[i * 4 + 2];
-        packed_rgba_buffer[i * 4 + 1] = packed_bgra_buffer[i * 4 + 1];
-        packed_rgba_buffer[i * 4 + 2] = packed_bgra_buffer[i * 4];
-
-        // Alpha channel
-        packed_rgba_buffer[i * 4 + 3] = packed_bgra_buffer[i * 4 + 3];
-    }
+    convert_packed(
+        PixelFormat::BGRA,
+        PixelFormat::RGBA,
+        packed_bgra_buffer,
+        packed_rgba_buffer,
+    );
 }
@@ -1,6 +1,45 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub mod policy;
+pub mod transport;
+
+pub const TARGET_BITRATE_STAT: &str = "target_bitrate";
+pub const TARGET_FRAME_RATE_STAT: &str = "target_frame_rate";
+
+/// Report sent by the client to the server to drive the adaptation of the stream
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum FeedbackMessage {
-    HighFrameDelay(u128)
+    /// Delay of a frame exceeding the threshold of the reporter, in milliseconds
+    HighFrameDelay(u128),
+
+    /// Average delay between capture and reception, in milliseconds
+    FrameDelay(u128),
+
+    /// Fraction of the frames dropped before reaching the client
+    LossRate(f64),
+
+    /// Frames delivered per second
+    DecodedFrameRate(f64),
+
+    /// Frames waiting to be rendered on the client
+    BufferLevel(u128),
+}
+
+/// Encoding settings adapted by the server according to the received feedback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamSettings {
+    /// Bits per second
+    pub bitrate: u32,
+
+    /// Frames per second
+    pub frame_rate: u32,
+}
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        Self {
+            bitrate: 5_000_000,
+            frame_rate: 60,
+        }
+    }
 }
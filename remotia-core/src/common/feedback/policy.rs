@@ -0,0 +1,241 @@
+use log::debug;
+
+use super::{FeedbackMessage, StreamSettings};
+
+/// Strategy with which the server adapts the stream to the feedback of the client
+pub trait AdaptationPolicy: Send {
+    fn on_feedback(&mut self, message: &FeedbackMessage, settings: &mut StreamSettings);
+}
+
+/// Additive increase, multiplicative decrease of the bitrate.
+///
+/// The bitrate is cut on congestion (high delay or loss rate) and raised by a fixed step when
+/// the delay is back below the threshold. Once the minimum bitrate is reached, the frame rate
+/// is lowered instead, and it is restored first when the congestion is over.
+pub struct AIMDPolicy {
+    min_bitrate: u32,
+    max_bitrate: u32,
+    bitrate_step: u32,
+    decrease_factor: f64,
+
+    min_frame_rate: u32,
+    max_frame_rate: u32,
+    frame_rate_step: u32,
+
+    delay_threshold: u128,
+    loss_threshold: f64,
+}
+
+impl Default for AIMDPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AIMDPolicy {
+    pub fn new() -> Self {
+        Self {
+            min_bitrate: 500_000,
+            max_bitrate: 20_000_000,
+            bitrate_step: 250_000,
+            decrease_factor: 0.85,
+            min_frame_rate: 60,
+            max_frame_rate: 60,
+            frame_rate_step: 5,
+            delay_threshold: 100,
+            loss_threshold: 0.02,
+        }
+    }
+
+    pub fn bitrate_range(mut self, min_bitrate: u32, max_bitrate: u32) -> Self {
+        self.min_bitrate = min_bitrate;
+        self.max_bitrate = max_bitrate;
+        self
+    }
+
+    /// Bits per second added when the stream is not congested
+    pub fn bitrate_step(mut self, bitrate_step: u32) -> Self {
+        self.bitrate_step = bitrate_step;
+        self
+    }
+
+    /// Factor applied to the bitrate on congestion
+    pub fn decrease_factor(mut self, decrease_factor: f64) -> Self {
+        self.decrease_factor = decrease_factor;
+        self
+    }
+
+    /// Frame rates among which the policy may move once the bitrate is at its minimum. The
+    /// frame rate is never adapted by default.
+    pub fn frame_rate_range(mut self, min_frame_rate: u32, max_frame_rate: u32) -> Self {
+        self.min_frame_rate = min_frame_rate;
+        self.max_frame_rate = max_frame_rate;
+        self
+    }
+
+    pub fn frame_rate_step(mut self, frame_rate_step: u32) -> Self {
+        self.frame_rate_step = frame_rate_step;
+        self
+    }
+
+    /// Delay, in milliseconds, above which the stream is considered congested
+    pub fn delay_threshold(mut self, delay_threshold: u128) -> Self {
+        self.delay_threshold = delay_threshold;
+        self
+    }
+
+    /// Loss rate above which the stream is considered congested
+    pub fn loss_threshold(mut self, loss_threshold: f64) -> Self {
+        self.loss_threshold = loss_threshold;
+        self
+    }
+
+    fn decrease(&self, settings: &mut StreamSettings) {
+        if settings.bitrate > self.min_bitrate {
+            let bitrate = (settings.bitrate as f64 * self.decrease_factor) as u32;
+            settings.bitrate = bitrate.max(self.min_bitrate);
+        } else {
+            settings.frame_rate = settings
+                .frame_rate
+                .saturating_sub(self.frame_rate_step)
+                .max(self.min_frame_rate);
+        }
+    }
+
+    fn increase(&self, settings: &mut StreamSettings) {
+        if settings.frame_rate < self.max_frame_rate {
+            settings.frame_rate = settings
+                .frame_rate
+                .saturating_add(self.frame_rate_step)
+                .min(self.max_frame_rate);
+        } else {
+            settings.bitrate = settings
+                .bitrate
+                .saturating_add(self.bitrate_step)
+                .min(self.max_bitrate);
+        }
+    }
+}
+
+impl AdaptationPolicy for AIMDPolicy {
+    fn on_feedback(&mut self, message: &FeedbackMessage, settings: &mut StreamSettings) {
+        match *message {
+            FeedbackMessage::HighFrameDelay(delay) => {
+                debug!("High frame delay ({} ms)", delay);
+                self.decrease(settings);
+            }
+            FeedbackMessage::FrameDelay(delay) if delay > self.delay_threshold => {
+                debug!("Congestion detected (delay: {} ms)", delay);
+                self.decrease(settings);
+            }
+            FeedbackMessage::FrameDelay(_) => self.increase(settings),
+            FeedbackMessage::LossRate(loss_rate) if loss_rate > self.loss_threshold => {
+                debug!("Congestion detected (loss rate: {:.3})", loss_rate);
+                self.decrease(settings);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::feedback::{FeedbackMessage, StreamSettings};
+
+    use super::{AIMDPolicy, AdaptationPolicy};
+
+    fn settings(bitrate: u32, frame_rate: u32) -> StreamSettings {
+        StreamSettings {
+            bitrate,
+            frame_rate,
+        }
+    }
+
+    fn apply(policy: &mut AIMDPolicy, message: FeedbackMessage, settings: &mut StreamSettings) {
+        policy.on_feedback(&message, settings);
+    }
+
+    #[test]
+    fn congestion_cuts_the_bitrate_down_to_the_minimum() {
+        let mut policy = AIMDPolicy::new().bitrate_range(1_000_000, 10_000_000);
+        let mut current = settings(2_000_000, 60);
+
+        apply(&mut policy, FeedbackMessage::FrameDelay(150), &mut current);
+        assert_eq!(current, settings(1_700_000, 60));
+
+        apply(
+            &mut policy,
+            FeedbackMessage::HighFrameDelay(50),
+            &mut current,
+        );
+        assert_eq!(current, settings(1_445_000, 60));
+
+        apply(&mut policy, FeedbackMessage::LossRate(0.1), &mut current);
+        apply(&mut policy, FeedbackMessage::LossRate(0.1), &mut current);
+        apply(&mut policy, FeedbackMessage::LossRate(0.1), &mut current);
+        assert_eq!(current, settings(1_000_000, 60));
+
+        // Without a frame rate range, the frame rate is never lowered
+        apply(&mut policy, FeedbackMessage::FrameDelay(150), &mut current);
+        assert_eq!(current, settings(1_000_000, 60));
+    }
+
+    #[test]
+    fn frame_rate_is_lowered_at_the_minimum_bitrate_and_restored_first() {
+        let mut policy = AIMDPolicy::new()
+            .bitrate_range(1_000_000, 10_000_000)
+            .bitrate_step(500_000)
+            .frame_rate_range(20, 30)
+            .frame_rate_step(4);
+        let mut current = settings(1_000_000, 30);
+
+        let congestion = FeedbackMessage::FrameDelay(150);
+        apply(&mut policy, congestion, &mut current);
+        assert_eq!(current, settings(1_000_000, 26));
+        apply(&mut policy, congestion, &mut current);
+        apply(&mut policy, congestion, &mut current);
+        assert_eq!(current, settings(1_000_000, 20));
+
+        let recovery = FeedbackMessage::FrameDelay(50);
+        apply(&mut policy, recovery, &mut current);
+        assert_eq!(current, settings(1_000_000, 24));
+        apply(&mut policy, recovery, &mut current);
+        apply(&mut policy, recovery, &mut current);
+        assert_eq!(current, settings(1_000_000, 30));
+
+        apply(&mut policy, recovery, &mut current);
+        assert_eq!(current, settings(1_500_000, 30));
+    }
+
+    #[test]
+    fn increases_stop_at_the_maximum() {
+        let mut policy = AIMDPolicy::new()
+            .bitrate_range(1_000_000, u32::MAX)
+            .bitrate_step(u32::MAX);
+        let mut current = settings(u32::MAX - 1, 60);
+
+        apply(&mut policy, FeedbackMessage::FrameDelay(50), &mut current);
+        assert_eq!(current, settings(u32::MAX, 60));
+
+        let mut policy = AIMDPolicy::new().bitrate_range(1_000_000, 3_000_000);
+        let mut current = settings(2_900_000, 60);
+        apply(&mut policy, FeedbackMessage::FrameDelay(50), &mut current);
+        assert_eq!(current, settings(3_000_000, 60));
+    }
+
+    #[test]
+    fn other_messages_leave_the_settings_unchanged() {
+        let mut policy = AIMDPolicy::new();
+        let mut current = StreamSettings::default();
+
+        for message in [
+            FeedbackMessage::LossRate(0.01),
+            FeedbackMessage::DecodedFrameRate(30.0),
+            FeedbackMessage::BufferLevel(10),
+        ] {
+            apply(&mut policy, message, &mut current);
+        }
+
+        assert_eq!(current, StreamSettings::default());
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    io,
+    net::UdpSocket,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+};
+
+use super::FeedbackMessage;
+
+/// Largest serialized feedback message
+const MAX_MESSAGE_SIZE: usize = 64;
+
+/// Client end of a feedback transport
+pub trait FeedbackSender: Send {
+    fn send(&mut self, message: FeedbackMessage) -> io::Result<()>;
+}
+
+/// Server end of a feedback transport, polled by the pipeline without waiting
+pub trait FeedbackReceiver: Send {
+    /// Returns the next received message, if any
+    fn try_receive(&mut self) -> io::Result<Option<FeedbackMessage>>;
+}
+
+/// Feedback transport between pipelines running in the same process
+pub fn local_channel() -> (LocalFeedbackSender, LocalFeedbackReceiver) {
+    let (sender, receiver) = mpsc::channel();
+
+    (
+        LocalFeedbackSender { sender },
+        LocalFeedbackReceiver { receiver },
+    )
+}
+
+pub struct LocalFeedbackSender {
+    sender: Sender<FeedbackMessage>,
+}
+
+impl FeedbackSender for LocalFeedbackSender {
+    fn send(&mut self, message: FeedbackMessage) -> io::Result<()> {
+        self.sender
+            .send(message)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Feedback receiver dropped"))
+    }
+}
+
+pub struct LocalFeedbackReceiver {
+    receiver: Receiver<FeedbackMessage>,
+}
+
+impl FeedbackReceiver for LocalFeedbackReceiver {
+    fn try_receive(&mut self) -> io::Result<Option<FeedbackMessage>> {
+        match self.receiver.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Feedback sender dropped",
+            )),
+        }
+    }
+}
+
+/// Sends each feedback message in a UDP datagram
+pub struct UDPFeedbackSender {
+    socket: UdpSocket,
+}
+
+impl UDPFeedbackSender {
+    pub fn connect(remote_address: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(remote_address)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self { socket })
+    }
+}
+
+impl FeedbackSender for UDPFeedbackSender {
+    fn send(&mut self, message: FeedbackMessage) -> io::Result<()> {
+        let datagram = bincode::serialize(&message).unwrap();
+        self.socket.send(&datagram)?;
+
+        Ok(())
+    }
+}
+
+/// Receives the feedback messages sent by a [`UDPFeedbackSender`]
+pub struct UDPFeedbackReceiver {
+    socket: UdpSocket,
+}
+
+impl UDPFeedbackReceiver {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self { socket })
+    }
+}
+
+impl FeedbackReceiver for UDPFeedbackReceiver {
+    fn try_receive(&mut self) -> io::Result<Option<FeedbackMessage>> {
+        let mut datagram = [0; MAX_MESSAGE_SIZE];
+
+        let size = match self.socket.recv(&mut datagram) {
+            Ok(size) => size,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        bincode::deserialize(&datagram[..size])
+            .map(Some)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::common::feedback::FeedbackMessage;
+
+    use super::{local_channel, FeedbackReceiver, FeedbackSender};
+
+    #[test]
+    fn local_messages_are_received_in_order() {
+        let (mut sender, mut receiver) = local_channel();
+        assert_eq!(receiver.try_receive().unwrap(), None);
+
+        sender.send(FeedbackMessage::FrameDelay(10)).unwrap();
+        sender.send(FeedbackMessage::LossRate(0.5)).unwrap();
+
+        assert_eq!(
+            receiver.try_receive().unwrap(),
+            Some(FeedbackMessage::FrameDelay(10))
+        );
+        assert_eq!(
+            receiver.try_receive().unwrap(),
+            Some(FeedbackMessage::LossRate(0.5))
+        );
+        assert_eq!(receiver.try_receive().unwrap(), None);
+    }
+
+    #[test]
+    fn dropped_local_ends_break_the_pipe() {
+        let (mut sender, receiver) = local_channel();
+        drop(receiver);
+
+        let error = sender.send(FeedbackMessage::BufferLevel(1)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+
+        let (mut sender, mut receiver) = local_channel();
+        sender.send(FeedbackMessage::BufferLevel(1)).unwrap();
+        drop(sender);
+
+        // Messages sent before the disconnection are still delivered
+        assert_eq!(
+            receiver.try_receive().unwrap(),
+            Some(FeedbackMessage::BufferLevel(1))
+        );
+        let error = receiver.try_receive().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::sync::mpsc;
+
+pub mod proxy;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LossModel {
+    /// Each packet is lost independently with the given probability
+    Bernoulli(f64),
+
+    /// Two-state Markov chain producing bursts of losses. The state is updated before each
+    /// packet, then the packet is lost with the loss probability of the current state.
+    GilbertElliott {
+        good_to_bad: f64,
+        bad_to_good: f64,
+        good_loss: f64,
+        bad_loss: f64,
+    },
+}
+
+/// Impairments applied to each frame or datagram, in the spirit of `tc netem`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkImpairment {
+    delay: Duration,
+    jitter: Duration,
+    loss: Option<LossModel>,
+    duplication: f64,
+    reordering: f64,
+    bandwidth: Option<u64>,
+    seed: Option<u64>,
+}
+
+impl Default for NetworkImpairment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetworkImpairment {
+    pub fn new() -> Self {
+        Self {
+            delay: Duration::ZERO,
+            jitter: Duration::ZERO,
+            loss: None,
+            duplication: 0.0,
+            reordering: 0.0,
+            bandwidth: None,
+            seed: None,
+        }
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Maximum deviation from the delay, uniformly distributed. Jitter may reorder packets.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn loss(mut self, loss: LossModel) -> Self {
+        self.loss = Some(loss);
+        self
+    }
+
+    /// Probability of a packet being delivered twice
+    pub fn duplication(mut self, probability: f64) -> Self {
+        self.duplication = probability;
+        self
+    }
+
+    /// Probability of a packet skipping the delay, overtaking the ones sent before it
+    pub fn reordering(mut self, probability: f64) -> Self {
+        self.reordering = probability;
+        self
+    }
+
+    /// Link capacity in bits per second. Packets are queued while the link is busy.
+    pub fn bandwidth(mut self, bits_per_second: u64) -> Self {
+        self.bandwidth = Some(bits_per_second);
+        self
+    }
+
+    /// Seeds the random decisions, making them reproducible across runs
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// Stateful application of a [`NetworkImpairment`] to a sequence of packets
+pub struct ImpairmentModel {
+    impairment: NetworkImpairment,
+
+    rng: StdRng,
+    bad_state: bool,
+    link_busy_until: Option<Instant>,
+}
+
+impl ImpairmentModel {
+    pub fn new(impairment: NetworkImpairment) -> Self {
+        let rng = match impairment.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        Self {
+            impairment,
+            rng,
+            bad_state: false,
+            link_busy_until: None,
+        }
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.rng.gen::<f64>() < probability
+    }
+
+    fn is_lost(&mut self) -> bool {
+        match self.impairment.loss {
+            None => false,
+            Some(LossModel::Bernoulli(probability)) => self.chance(probability),
+            Some(LossModel::GilbertElliott {
+                good_to_bad,
+                bad_to_good,
+                good_loss,
+                bad_loss,
+            }) => {
+                self.bad_state = if self.bad_state {
+                    !self.chance(bad_to_good)
+                } else {
+                    self.chance(good_to_bad)
+                };
+
+                self.chance(if self.bad_state { bad_loss } else { good_loss })
+            }
+        }
+    }
+
+    /// Time at which a packet sent now leaves the bandwidth-limited link
+    fn transmission_end(&mut self, size: usize, now: Instant) -> Instant {
+        let bandwidth = match self.impairment.bandwidth {
+            Some(bandwidth) if bandwidth > 0 => bandwidth,
+            _ => return now,
+        };
+
+        let transmission_time = Duration::from_secs_f64((size * 8) as f64 / bandwidth as f64);
+        let start = self.link_busy_until.map_or(now, |busy_until| busy_until.max(now));
+        let end = start + transmission_time;
+
+        self.link_busy_until = Some(end);
+        end
+    }
+
+    fn propagation_delay(&mut self) -> Duration {
+        let delay = self.impairment.delay.as_secs_f64();
+        let jitter = self.impairment.jitter.as_secs_f64();
+
+        if jitter == 0.0 {
+            return self.impairment.delay;
+        }
+
+        Duration::from_secs_f64((delay + self.rng.gen_range(-jitter..=jitter)).max(0.0))
+    }
+
+    /// Returns the delivery times of a packet of the given size sent now: none if it is lost,
+    /// two if it is duplicated
+    pub fn schedule(&mut self, size: usize) -> Vec<Instant> {
+        self.schedule_at(size, Instant::now())
+    }
+
+    fn schedule_at(&mut self, size: usize, now: Instant) -> Vec<Instant> {
+        if self.is_lost() {
+            return Vec::new();
+        }
+
+        let transmission_end = self.transmission_end(size, now);
+
+        let copies = if self.chance(self.impairment.duplication) { 2 } else { 1 };
+
+        (0..copies)
+            .map(|_| {
+                if self.chance(self.impairment.reordering) {
+                    transmission_end
+                } else {
+                    transmission_end + self.propagation_delay()
+                }
+            })
+            .collect()
+    }
+}
+
+struct ScheduledDelivery<T> {
+    delivery_time: Instant,
+    sequence_number: u64,
+    item: T,
+}
+
+impl<T> ScheduledDelivery<T> {
+    fn key(&self) -> (Instant, u64) {
+        (self.delivery_time, self.sequence_number)
+    }
+}
+
+impl<T> PartialEq for ScheduledDelivery<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T> Eq for ScheduledDelivery<T> {}
+
+impl<T> PartialOrd for ScheduledDelivery<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for ScheduledDelivery<T> {
+    // Reversed, so that the heap pops the earliest delivery first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+/// Background task delivering items at their scheduled time. Items scheduled for the same
+/// time are delivered in order. Pending items are still delivered once dropped.
+pub(crate) struct DelayedDelivery<T> {
+    sender: mpsc::UnboundedSender<(Instant, T)>,
+}
+
+impl<T: Send + 'static> DelayedDelivery<T> {
+    pub(crate) fn spawn<F, Fut>(mut deliver: F) -> Self
+    where
+        F: FnMut(T) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<(Instant, T)>();
+
+        tokio::spawn(async move {
+            let mut scheduled = BinaryHeap::new();
+            let mut sequence_number = 0;
+            let mut closed = false;
+
+            loop {
+                let next_delivery_time = scheduled
+                    .peek()
+                    .map(|delivery: &ScheduledDelivery<T>| delivery.delivery_time);
+
+                if closed && next_delivery_time.is_none() {
+                    break;
+                }
+
+                let sleep = tokio::time::sleep_until(tokio::time::Instant::from_std(
+                    next_delivery_time.unwrap_or_else(Instant::now),
+                ));
+
+                tokio::select! {
+                    received = receiver.recv(), if !closed => match received {
+                        Some((delivery_time, item)) => {
+                            scheduled.push(ScheduledDelivery {
+                                delivery_time,
+                                sequence_number,
+                                item,
+                            });
+                            sequence_number += 1;
+                        }
+                        None => closed = true,
+                    },
+                    _ = sleep, if next_delivery_time.is_some() => {
+                        deliver(scheduled.pop().unwrap().item).await;
+                    }
+                }
+            }
+        });
+
+        Self { sender }
+    }
+
+    pub(crate) fn schedule(&self, delivery_time: Instant, item: T) {
+        // The task only stops once this sender is dropped
+        let _ = self.sender.send((delivery_time, item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{ImpairmentModel, LossModel, NetworkImpairment};
+
+    const PACKETS: usize = 100_000;
+
+    fn losses(loss: LossModel) -> Vec<bool> {
+        let mut model = ImpairmentModel::new(NetworkImpairment::new().loss(loss).seed(1));
+        let now = Instant::now();
+
+        (0..PACKETS)
+            .map(|_| model.schedule_at(1000, now).is_empty())
+            .collect()
+    }
+
+    fn loss_rate(losses: &[bool]) -> f64 {
+        losses.iter().filter(|lost| **lost).count() as f64 / losses.len() as f64
+    }
+
+    fn mean_burst_length(losses: &[bool]) -> f64 {
+        let bursts = losses.windows(2).filter(|pair| !pair[0] && pair[1]).count();
+
+        losses.iter().filter(|lost| **lost).count() as f64 / bursts as f64
+    }
+
+    #[test]
+    fn seeded_models_are_reproducible() {
+        let impairment = NetworkImpairment::new()
+            .delay(Duration::from_millis(20))
+            .jitter(Duration::from_millis(5))
+            .loss(LossModel::Bernoulli(0.1))
+            .duplication(0.1)
+            .reordering(0.1)
+            .bandwidth(1_000_000);
+
+        let schedules = |seed: u64| {
+            let mut model = ImpairmentModel::new(impairment.clone().seed(seed));
+            let now = Instant::now();
+
+            (0..1000)
+                .map(|_| {
+                    model
+                        .schedule_at(1000, now)
+                        .into_iter()
+                        .map(|delivery_time| delivery_time - now)
+                        .collect::<Vec<Duration>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(schedules(42), schedules(42));
+        assert_ne!(schedules(42), schedules(43));
+    }
+
+    #[test]
+    fn bernoulli_losses_match_the_probability() {
+        let losses = losses(LossModel::Bernoulli(0.2));
+
+        assert!((loss_rate(&losses) - 0.2).abs() < 0.01);
+        assert!(mean_burst_length(&losses) < 1.5);
+    }
+
+    #[test]
+    fn gilbert_elliott_losses_come_in_bursts() {
+        let losses = losses(LossModel::GilbertElliott {
+            good_to_bad: 0.05,
+            bad_to_good: 0.25,
+            good_loss: 0.0,
+            bad_loss: 1.0,
+        });
+
+        // Stationary probability of the bad state and mean time spent in it
+        assert!((loss_rate(&losses) - 0.05 / 0.3).abs() < 0.02);
+        assert!((mean_burst_length(&losses) - 4.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn jitter_stays_within_its_bounds() {
+        let mut model = ImpairmentModel::new(
+            NetworkImpairment::new()
+                .delay(Duration::from_millis(20))
+                .jitter(Duration::from_millis(5))
+                .seed(1),
+        );
+        let now = Instant::now();
+
+        let delays: Vec<Duration> = (0..10_000)
+            .map(|_| model.schedule_at(1000, now)[0] - now)
+            .collect();
+
+        let min = *delays.iter().min().unwrap();
+        let max = *delays.iter().max().unwrap();
+
+        assert!(min >= Duration::from_millis(15));
+        assert!(max <= Duration::from_millis(25));
+        assert!(min < Duration::from_millis(16));
+        assert!(max > Duration::from_millis(24));
+    }
+
+    #[test]
+    fn packets_are_queued_while_the_link_is_busy() {
+        // 125 bytes take one second at 1000 bit/s
+        let mut model = ImpairmentModel::new(NetworkImpairment::new().bandwidth(1000));
+        let now = Instant::now();
+
+        let deliveries: Vec<Instant> = (0..3).flat_map(|_| model.schedule_at(125, now)).collect();
+        assert_eq!(
+            deliveries,
+            vec![
+                now + Duration::from_secs(1),
+                now + Duration::from_secs(2),
+                now + Duration::from_secs(3)
+            ]
+        );
+
+        // Once the queue is drained, packets are transmitted right away
+        let later = now + Duration::from_secs(10);
+        assert_eq!(
+            model.schedule_at(125, later),
+            vec![later + Duration::from_secs(1)]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::{io, net::SocketAddr, sync::Arc};
+
+use log::debug;
+use tokio::{net::UdpSocket, task::JoinHandle};
+
+use super::{DelayedDelivery, ImpairmentModel, NetworkImpairment};
+
+/// In-process UDP proxy which relays the datagrams between a client and a remote endpoint,
+/// impairing them in both directions. The client is the last peer which sent a datagram to
+/// the proxy.
+pub struct NetworkEmulatorProxy {
+    local_address: SocketAddr,
+    remote_address: SocketAddr,
+
+    forward: NetworkImpairment,
+    backward: NetworkImpairment,
+}
+
+/// Running proxy, which is stopped when dropped
+pub struct NetworkEmulatorProxyHandle {
+    local_address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl NetworkEmulatorProxyHandle {
+    /// Address on which the proxy receives the datagrams of the client
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+}
+
+impl Drop for NetworkEmulatorProxyHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl NetworkEmulatorProxy {
+    pub fn new(local_address: SocketAddr, remote_address: SocketAddr) -> Self {
+        Self {
+            local_address,
+            remote_address,
+            forward: NetworkImpairment::new(),
+            backward: NetworkImpairment::new(),
+        }
+    }
+
+    /// Impairment applied to the datagrams going from the client to the remote endpoint
+    pub fn forward(mut self, impairment: NetworkImpairment) -> Self {
+        self.forward = impairment;
+        self
+    }
+
+    /// Impairment applied to the datagrams going from the remote endpoint to the client
+    pub fn backward(mut self, impairment: NetworkImpairment) -> Self {
+        self.backward = impairment;
+        self
+    }
+
+    pub async fn start(self) -> io::Result<NetworkEmulatorProxyHandle> {
+        let client_socket = Arc::new(UdpSocket::bind(self.local_address).await?);
+        let local_address = client_socket.local_addr()?;
+
+        let remote_socket = Arc::new(UdpSocket::bind(("0.0.0.0", 0)).await?);
+        remote_socket.connect(self.remote_address).await?;
+
+        let task = tokio::spawn(relay(
+            client_socket,
+            remote_socket,
+            ImpairmentModel::new(self.forward),
+            ImpairmentModel::new(self.backward),
+        ));
+
+        Ok(NetworkEmulatorProxyHandle {
+            local_address,
+            task,
+        })
+    }
+}
+
+async fn relay(
+    client_socket: Arc<UdpSocket>,
+    remote_socket: Arc<UdpSocket>,
+    mut forward: ImpairmentModel,
+    mut backward: ImpairmentModel,
+) {
+    let forward_socket = remote_socket.clone();
+    let forward_delivery = DelayedDelivery::spawn(move |datagram: Arc<Vec<u8>>| {
+        let socket = forward_socket.clone();
+        async move {
+            if let Err(error) = socket.send(&datagram).await {
+                debug!("Unable to relay datagram to remote: {}", error);
+            }
+        }
+    });
+
+    let backward_socket = client_socket.clone();
+    let backward_delivery =
+        DelayedDelivery::spawn(move |(datagram, address): (Arc<Vec<u8>>, SocketAddr)| {
+            let socket = backward_socket.clone();
+            async move {
+                if let Err(error) = socket.send_to(&datagram, address).await {
+                    debug!("Unable to relay datagram to client: {}", error);
+                }
+            }
+        });
+
+    let mut client_address = None;
+    let mut client_buffer = vec![0; u16::MAX as usize];
+    let mut remote_buffer = vec![0; u16::MAX as usize];
+
+    loop {
+        tokio::select! {
+            received = client_socket.recv_from(&mut client_buffer) => {
+                let (size, address) = match received {
+                    Ok(received) => received,
+                    Err(error) => {
+                        debug!("Unable to receive from client: {}", error);
+                        continue;
+                    }
+                };
+
+                client_address = Some(address);
+
+                let datagram = Arc::new(client_buffer[..size].to_vec());
+                for delivery_time in forward.schedule(size) {
+                    forward_delivery.schedule(delivery_time, datagram.clone());
+                }
+            }
+            received = remote_socket.recv(&mut remote_buffer) => {
+                let size = match received {
+                    Ok(size) => size,
+                    Err(error) => {
+                        debug!("Unable to receive from remote: {}", error);
+                        continue;
+                    }
+                };
+
+                let client_address = match client_address {
+                    Some(client_address) => client_address,
+                    None => {
+                        debug!("Discarding datagram from remote, no client is known yet");
+                        continue;
+                    }
+                };
+
+                let datagram = Arc::new(remote_buffer[..size].to_vec());
+                for delivery_time in backward.schedule(size) {
+                    backward_delivery.schedule(delivery_time, (datagram.clone(), client_address));
+                }
+            }
+        }
+    }
+}
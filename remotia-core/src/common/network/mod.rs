@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+pub mod impairment;
 pub mod remvsp;
+pub mod serialized;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FrameBody {
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use bytes::BytesMut;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{FrameData, StatValue};
+
+/// Stats and buffers of a frame DTO which are transmitted to the peer
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SerializedFrameData {
+    pub stats: HashMap<String, StatValue>,
+    pub buffers: HashMap<String, Vec<u8>>,
+}
+
+impl SerializedFrameData {
+    pub fn new(stats: HashMap<String, StatValue>) -> Self {
+        Self {
+            stats,
+            buffers: HashMap::new(),
+        }
+    }
+
+    pub fn insert_buffer(&mut self, key: &str, data: &[u8]) {
+        self.buffers.insert(key.to_string(), data.to_vec());
+    }
+
+    /// Merges the received stats and buffers into a frame DTO. Buffers which are already in
+    /// the DTO (e.g. borrowed from a pool) are overwritten from the start, otherwise they are
+    /// allocated. Returns the key of the first buffer which did not fit, if any.
+    pub fn restore(self, frame_data: &mut FrameData) -> Result<(), String> {
+        frame_data.merge_stats(self.stats);
+
+        for (key, data) in self.buffers {
+            match frame_data.get_writable_buffer_ref(&key) {
+                Some(buffer) if buffer.len() >= data.len() => {
+                    buffer[..data.len()].copy_from_slice(&data);
+                }
+                Some(_) => return Err(key),
+                None => frame_data.insert_writable_buffer(&key, BytesMut::from(&data[..])),
+            }
+        }
+
+        Ok(())
+    }
+}
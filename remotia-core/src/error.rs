@@ -24,6 +24,15 @@ pub enum DropReason {
     #[error("Stale frame")]
     StaleFrame,
 
+    #[error("Static frame")]
+    StaticFrame,
+
+    #[error("Frame rate limit")]
+    FrameRateLimit,
+
+    #[error("No new frame captured before the deadline")]
+    CaptureTimeout,
+
     #[error("Connection error")]
     ConnectionError,
 
@@ -41,4 +50,116 @@ pub enum DropReason {
 
     #[error("No available buffers")]
     NoAvailableBuffers,
+
+    #[error("Channel overflow")]
+    ChannelOverflow,
+
+    #[error("Processor error")]
+    ProcessorError,
+
+    #[error("Lost by the network emulator")]
+    EmulatedLoss,
+
+    #[error("Frame larger than its destination buffer")]
+    BufferOverflow,
+}
+
+#[derive(Error, Debug)]
+pub enum ProcessorError {
+    #[error("Missing buffer '{0}'")]
+    MissingBuffer(String),
+
+    #[error("Missing stat '{0}'")]
+    MissingStat(String),
+
+    #[error("Buffer '{key}' is too small ({found} < {required} bytes)")]
+    BufferTooSmall {
+        key: String,
+        required: usize,
+        found: usize,
+    },
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The processor has no more frames to produce, stopping its component whatever its
+    /// error policy
+    #[error("End of stream")]
+    EndOfStream,
+
+    #[error("{0}")]
+    Other(String),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StatError {
+    #[error("Missing stat '{0}'")]
+    Missing(String),
+
+    #[error("Stat '{key}' is not of type {expected} (found {found})")]
+    WrongType {
+        key: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+impl From<StatError> for ProcessorError {
+    fn from(error: StatError) -> Self {
+        match error {
+            StatError::Missing(key) => ProcessorError::MissingStat(key),
+            error => ProcessorError::Other(error.to_string()),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CodecError {
+    #[error("Invalid codec configuration: {0}")]
+    InvalidConfiguration(String),
+
+    #[error("Encoding failed: {0}")]
+    EncodingFailed(String),
+
+    #[error("Decoding failed: {0}")]
+    DecodingFailed(String),
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Parse error: {0}")]
+    Parse(String),
+
+    #[error("Unsupported definition format '{0}'")]
+    UnsupportedFormat(String),
+
+    #[error("Unknown processor '{0}'")]
+    UnknownProcessor(String),
+
+    #[error("Unknown pipeline '{0}'")]
+    UnknownPipeline(String),
+
+    #[error("Duplicate pipeline '{0}'")]
+    DuplicatePipeline(String),
+
+    #[error("Pipeline '{0}' has no components")]
+    EmptyPipeline(String),
+
+    #[error("Pipeline '{0}' is not feedable")]
+    NotFeedable(String),
+
+    #[error("Cyclic references between pipelines {0:?}")]
+    CyclicPipelines(Vec<String>),
+
+    #[error("Missing parameter '{0}'")]
+    MissingParameter(String),
+
+    #[error("Parameter '{key}' is not of type {expected}")]
+    InvalidParameter { key: String, expected: &'static str },
+
+    #[error("Unsupported conversion: {0}")]
+    UnsupportedConversion(String),
 }
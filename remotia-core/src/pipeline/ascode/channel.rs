@@ -0,0 +1,408 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::{
+    error::{ConfigError, DropReason},
+    types::FrameData,
+};
+
+/// Behaviour of a link between two components when its buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackpressurePolicy {
+    /// Wait until the receiving component pulls a frame
+    #[default]
+    Block,
+
+    /// Discard the oldest queued frame to make room for the new one
+    DropOldest,
+
+    /// Discard the frame which is being sent
+    DropNewest,
+}
+
+struct ChannelState {
+    queue: VecDeque<FrameData>,
+
+    /// Frames discarded by the policy without a drop destination, delivered before the queued
+    /// ones so that they can be routed and logged without waiting for room
+    discarded: VecDeque<FrameData>,
+
+    senders_count: usize,
+    receiver_alive: bool,
+}
+
+struct Shared {
+    state: Mutex<ChannelState>,
+
+    /// Frames discarded by the policy since the creation of the link
+    discarded_count: Arc<AtomicU64>,
+
+    readable: Notify,
+    writable: Notify,
+
+    capacity: usize,
+    policy: BackpressurePolicy,
+
+    drop_destination: Option<ChannelSender>,
+}
+
+pub struct ChannelSender {
+    shared: Arc<Shared>,
+}
+
+pub struct ChannelReceiver {
+    shared: Arc<Shared>,
+}
+
+/// Creates a link which holds at most `capacity` frames, or an unbounded one. Frames discarded
+/// by the policy are marked with [`DropReason::ChannelOverflow`] and enqueued regardless of
+/// any capacity, so that they can reach the loggers without stalling the link: to the drop
+/// destination if one is provided, otherwise to the receiver of the link itself, ahead of the
+/// queued frames.
+pub(crate) fn channel(
+    capacity: Option<usize>,
+    policy: BackpressurePolicy,
+    drop_destination: Option<ChannelSender>,
+) -> Result<(ChannelSender, ChannelReceiver), ConfigError> {
+    if capacity == Some(0) {
+        return Err(ConfigError::InvalidParameter {
+            key: "capacity".to_string(),
+            expected: "positive integer",
+        });
+    }
+
+    let shared = Arc::new(Shared {
+        state: Mutex::new(ChannelState {
+            queue: VecDeque::new(),
+            discarded: VecDeque::new(),
+            senders_count: 1,
+            receiver_alive: true,
+        }),
+        discarded_count: Arc::new(AtomicU64::new(0)),
+        readable: Notify::new(),
+        writable: Notify::new(),
+        capacity: capacity.unwrap_or(usize::MAX),
+        policy,
+        drop_destination,
+    });
+
+    Ok((
+        ChannelSender {
+            shared: shared.clone(),
+        },
+        ChannelReceiver { shared },
+    ))
+}
+
+impl ChannelSender {
+    /// Enqueues a frame according to the policy of the link. The frame is given back if the
+    /// receiving side has been closed.
+    pub async fn send(&self, frame_data: FrameData) -> Result<(), FrameData> {
+        let mut frame_data = Some(frame_data);
+
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+
+                if !state.receiver_alive {
+                    drop(state);
+
+                    // Wake up any other sender waiting for room
+                    self.shared.writable.notify_one();
+                    return Err(frame_data.unwrap());
+                }
+
+                if state.queue.len() < self.shared.capacity {
+                    state.queue.push_back(frame_data.take().unwrap());
+                    drop(state);
+
+                    self.shared.readable.notify_one();
+                    return Ok(());
+                }
+
+                match self.shared.policy {
+                    BackpressurePolicy::Block => {}
+                    BackpressurePolicy::DropOldest => {
+                        let oldest_frame = state.queue.pop_front().unwrap();
+                        state.queue.push_back(frame_data.take().unwrap());
+                        drop(state);
+
+                        self.discard(oldest_frame);
+                        return Ok(());
+                    }
+                    BackpressurePolicy::DropNewest => {
+                        drop(state);
+
+                        self.discard(frame_data.take().unwrap());
+                        return Ok(());
+                    }
+                }
+            }
+
+            debug!("Channel is full, waiting for the receiver...");
+            self.shared.writable.notified().await;
+        }
+    }
+
+    fn discard(&self, mut frame_data: FrameData) {
+        debug!("Discarding frame due to full channel");
+        frame_data.set_drop_reason(Some(DropReason::ChannelOverflow));
+
+        // Warn about the first overflows only, sustained ones would flood the log
+        let discarded_count = self.shared.discarded_count.fetch_add(1, Ordering::Relaxed) + 1;
+        if discarded_count.is_power_of_two() {
+            warn!("Channel overflow, {} frames discarded so far", discarded_count);
+        }
+
+        match self.shared.drop_destination.as_ref() {
+            Some(destination) => destination.force_send(frame_data),
+            None => self.force_send(frame_data),
+        }
+    }
+
+    /// Number of frames discarded by the policy of the link so far
+    pub fn discarded_count(&self) -> u64 {
+        self.shared.discarded_count.load(Ordering::Relaxed)
+    }
+
+    /// Enqueues a discarded frame regardless of the capacity of the link
+    fn force_send(&self, frame_data: FrameData) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        if !state.receiver_alive {
+            debug!("Receiver is closed, discarding frame");
+            return;
+        }
+
+        state.discarded.push_back(frame_data);
+        drop(state);
+
+        self.shared.readable.notify_one();
+    }
+}
+
+impl Clone for ChannelSender {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders_count += 1;
+
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for ChannelSender {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders_count -= 1;
+
+        if state.senders_count == 0 {
+            drop(state);
+            self.shared.readable.notify_one();
+        }
+    }
+}
+
+impl ChannelReceiver {
+    /// Pulls the next frame, or `None` once every sender has been dropped and the queue is empty
+    pub async fn recv(&mut self) -> Option<FrameData> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+
+                if let Some(frame_data) = state.discarded.pop_front() {
+                    return Some(frame_data);
+                }
+
+                if let Some(frame_data) = state.queue.pop_front() {
+                    drop(state);
+
+                    self.shared.writable.notify_one();
+                    return Some(frame_data);
+                }
+
+                if state.senders_count == 0 {
+                    return None;
+                }
+            }
+
+            self.shared.readable.notified().await;
+        }
+    }
+
+    /// Shared counter of the frames discarded by the policy of the link
+    pub(crate) fn discarded_counter(&self) -> Arc<AtomicU64> {
+        self.shared.discarded_count.clone()
+    }
+}
+
+impl Drop for ChannelReceiver {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receiver_alive = false;
+        self.shared.writable.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use tokio::time::timeout;
+
+    use crate::{
+        error::{ConfigError, DropReason},
+        types::FrameData,
+    };
+
+    use super::{channel, BackpressurePolicy, ChannelReceiver, ChannelSender};
+
+    const WAIT: Duration = Duration::from_millis(50);
+
+    fn frame(index: u128) -> FrameData {
+        let mut frame_data = FrameData::default();
+        frame_data.set("index", index);
+        frame_data
+    }
+
+    fn bounded(capacity: usize, policy: BackpressurePolicy) -> (ChannelSender, ChannelReceiver) {
+        channel(Some(capacity), policy, None).unwrap()
+    }
+
+    /// Index and drop reason of the next frame
+    async fn recv(receiver: &mut ChannelReceiver) -> (u128, Option<DropReason>) {
+        let frame_data = receiver.recv().await.unwrap();
+        (frame_data.get("index"), frame_data.get_drop_reason())
+    }
+
+    #[test]
+    fn zero_capacity_is_rejected() {
+        assert!(matches!(
+            channel(Some(0), BackpressurePolicy::Block, None),
+            Err(ConfigError::InvalidParameter { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_a_free_slot() {
+        let (sender, mut receiver) = bounded(1, BackpressurePolicy::Block);
+        sender.send(frame(0)).await.unwrap();
+
+        let blocked_sender = sender.clone();
+        let mut blocked_send = tokio::spawn(async move { blocked_sender.send(frame(1)).await });
+        assert!(timeout(WAIT, &mut blocked_send).await.is_err());
+
+        assert_eq!(recv(&mut receiver).await, (0, None));
+        assert!(blocked_send.await.unwrap().is_ok());
+        assert_eq!(recv(&mut receiver).await, (1, None));
+
+        assert_eq!(sender.discarded_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_discards_the_queued_frame() {
+        let (sender, mut receiver) = bounded(2, BackpressurePolicy::DropOldest);
+        for index in 0..4 {
+            sender.send(frame(index)).await.unwrap();
+        }
+
+        // Discarded frames are delivered first, marked with their drop reason
+        assert_eq!(
+            recv(&mut receiver).await,
+            (0, Some(DropReason::ChannelOverflow))
+        );
+        assert_eq!(
+            recv(&mut receiver).await,
+            (1, Some(DropReason::ChannelOverflow))
+        );
+        assert_eq!(recv(&mut receiver).await, (2, None));
+        assert_eq!(recv(&mut receiver).await, (3, None));
+
+        assert_eq!(sender.discarded_count(), 2);
+        assert_eq!(receiver.discarded_counter().load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn drop_newest_discards_the_sent_frame() {
+        let (sender, mut receiver) = bounded(2, BackpressurePolicy::DropNewest);
+        for index in 0..5 {
+            sender.send(frame(index)).await.unwrap();
+        }
+
+        for index in 2..5 {
+            assert_eq!(
+                recv(&mut receiver).await,
+                (index, Some(DropReason::ChannelOverflow))
+            );
+        }
+        assert_eq!(recv(&mut receiver).await, (0, None));
+        assert_eq!(recv(&mut receiver).await, (1, None));
+
+        assert_eq!(sender.discarded_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn discarded_frames_reach_the_drop_destination() {
+        let (destination, mut destination_receiver) =
+            channel(Some(1), BackpressurePolicy::Block, None).unwrap();
+        let (sender, mut receiver) =
+            channel(Some(1), BackpressurePolicy::DropNewest, Some(destination)).unwrap();
+
+        // The destination takes discarded frames even when full
+        for index in 0..3 {
+            sender.send(frame(index)).await.unwrap();
+        }
+
+        assert_eq!(recv(&mut receiver).await, (0, None));
+        assert_eq!(
+            recv(&mut destination_receiver).await,
+            (1, Some(DropReason::ChannelOverflow))
+        );
+        assert_eq!(
+            recv(&mut destination_receiver).await,
+            (2, Some(DropReason::ChannelOverflow))
+        );
+
+        drop(sender);
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn closing_the_receiver_wakes_blocked_senders() {
+        let (sender, receiver) = bounded(1, BackpressurePolicy::Block);
+        sender.send(frame(0)).await.unwrap();
+
+        let blocked_send = tokio::spawn(async move { sender.send(frame(1)).await });
+        tokio::time::sleep(WAIT).await;
+        drop(receiver);
+
+        let rejected_frame = timeout(WAIT, blocked_send)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(rejected_frame.get("index"), 1);
+    }
+
+    #[tokio::test]
+    async fn closing_the_senders_wakes_the_receiver() {
+        let (sender, mut receiver) = bounded(1, BackpressurePolicy::Block);
+        let other_sender = sender.clone();
+
+        let pending_recv = tokio::spawn(async move { receiver.recv().await.is_none() });
+        tokio::time::sleep(WAIT).await;
+        drop(sender);
+        drop(other_sender);
+
+        assert!(timeout(WAIT, pending_recv).await.unwrap().unwrap());
+    }
+}
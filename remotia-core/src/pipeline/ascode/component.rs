@@ -1,12 +1,26 @@
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, task::JoinHandle};
 
-use log::{debug, info};
-use tokio::{
-    sync::mpsc::{UnboundedReceiver, UnboundedSender},
-    task::JoinHandle,
+use crate::{
+    error::{ConfigError, DropReason, ProcessorError},
+    traits::FrameProcessor,
+    types::FrameData,
 };
 
-use crate::{traits::FrameProcessor, types::FrameData};
+use super::{
+    channel::{self, BackpressurePolicy, ChannelReceiver, ChannelSender},
+    feeder::AscodePipelineFeeder,
+    AscodePipeline,
+};
 
 macro_rules! tagged {
     ($self:ident, $msg:tt) => {{
@@ -14,11 +28,52 @@ macro_rules! tagged {
     }}
 }
 
+/// Reason for which a component stopped pulling frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The upstream channel has been closed, or a processor reached the end of its stream, and
+    /// all the pending frames have been flushed
+    EndOfStream,
+
+    /// The pipeline has been asked to stop through its handle
+    Stopped,
+
+    /// The downstream component is not receiving frames anymore
+    DownstreamClosed,
+
+    /// One of the processors returned an error and the component follows the `Stop` policy
+    ProcessorError(String),
+
+    /// One of the processors panicked
+    Panicked(String),
+}
+
+/// Handling of the errors returned by fallible processors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// Mark the frame with [`DropReason::ProcessorError`] and keep processing it
+    #[default]
+    Drop,
+
+    /// Ignore the error and hand the frame to the next processor
+    Skip,
+
+    /// Discard the frame and stop the component, which in turn stops the pipeline
+    Stop,
+}
+
 pub struct Component {
     processors: Vec<Box<dyn FrameProcessor + Send>>,
 
-    receiver: Option<UnboundedReceiver<FrameData>>,
-    sender: Option<UnboundedSender<FrameData>>,
+    receiver: Option<ChannelReceiver>,
+    sender: Option<ChannelSender>,
+
+    capacity: Option<usize>,
+    backpressure_policy: BackpressurePolicy,
+    drop_feeder: Option<AscodePipelineFeeder>,
+
+    error_policy: ErrorPolicy,
 
     tag: Option<String>
 }
@@ -31,6 +86,10 @@ impl Component {
             processors: Vec::new(),
             receiver: None,
             sender: None,
+            capacity: None,
+            backpressure_policy: BackpressurePolicy::default(),
+            drop_feeder: None,
+            error_policy: ErrorPolicy::default(),
             tag: None
         }
     }
@@ -40,66 +99,519 @@ impl Component {
         self
     }
 
+    pub fn append_boxed(mut self, processor: Box<dyn FrameProcessor + Send>) -> Self {
+        self.processors.push(processor);
+        self
+    }
+
     pub fn tag(mut self, tag: &str) -> Self {
         self.tag = Some(tag.to_string());
         self
     }
 
+    /// Limits the amount of frames which can be queued before this component.
+    /// The input channel is unbounded by default.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    pub fn backpressure(mut self, policy: BackpressurePolicy) -> Self {
+        self.backpressure_policy = policy;
+        self
+    }
+
+    /// Feeds the frames discarded by the input channel to another pipeline, e.g. to log them and
+    /// redeem their pooled buffers. By default discarded frames are handed to this component
+    /// ahead of the queued ones, marked with [`DropReason::ChannelOverflow`].
+    pub fn on_drop(mut self, destination_pipeline: &AscodePipeline) -> Self {
+        self.drop_feeder = Some(destination_pipeline.get_feeder());
+        self
+    }
+
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Number of frames discarded so far by the backpressure policy of the input channel
+    pub fn discarded_count(&self) -> u64 {
+        self.discarded_counter()
+            .map_or(0, |counter| counter.load(Ordering::Relaxed))
+    }
+
     //////////////////////
     // Internal methods //
     //////////////////////
 
-    pub(crate) fn set_sender(&mut self, sender: UnboundedSender<FrameData>) {
+    pub(crate) fn get_tag(&self) -> Option<String> {
+        self.tag.clone()
+    }
+
+    pub(crate) fn set_sender(&mut self, sender: ChannelSender) {
         self.sender = Some(sender);
     }
 
-    pub(crate) fn set_receiver(&mut self, receiver: UnboundedReceiver<FrameData>) {
+    pub(crate) fn set_receiver(&mut self, receiver: ChannelReceiver) {
         self.receiver = Some(receiver);
     }
 
-    pub(crate) fn launch(mut self) -> JoinHandle<()> {
+    pub(crate) fn create_input_channel(&mut self) -> Result<ChannelSender, ConfigError> {
+        let drop_destination = self.drop_feeder.as_ref().map(|feeder| feeder.get_sender());
+        let (sender, receiver) =
+            channel::channel(self.capacity, self.backpressure_policy, drop_destination)?;
+
+        self.set_receiver(receiver);
+
+        Ok(sender)
+    }
+
+    pub(crate) fn discarded_counter(&self) -> Option<Arc<AtomicU64>> {
+        self.receiver.as_ref().map(|receiver| receiver.discarded_counter())
+    }
+
+    /// Spawns the component loop. Only the head of a pipeline is given a stop receiver,
+    /// the following components terminate as soon as the end of the stream reaches them.
+    pub(crate) fn launch(mut self, mut stop: Option<watch::Receiver<bool>>) -> JoinHandle<ExitReason> {
         tokio::spawn(async move {
-            loop {
-                let mut frame_data = if self.receiver.is_some() {
-                    Some(
-                        self.receiver
-                            .as_mut()
-                            .unwrap()
-                            .recv()
-                            .await
-                            .expect(tagged!(self, "Receive channel closed")),
-                    )
-                } else {
-                    debug!("No receiver registered, allocating an empty frame DTO");
-                    Some(FrameData::default())
+            let reason = loop {
+                let frame_data = match self.pull(&mut stop).await {
+                    Ok(frame_data) => frame_data,
+                    Err(reason) => break reason,
                 };
 
-                debug!("Received frame data: {}", frame_data.as_ref().unwrap());
+                if let Err(reason) = self.forward(frame_data, 0).await {
+                    break reason;
+                }
+            };
 
-                for processor in &mut self.processors {
-                    frame_data = processor.process(frame_data.unwrap()).await;
+            let exit_msg = format!("Exiting ({:?})", reason);
+            info!("{}", tagged!(self, exit_msg));
 
-                    if frame_data.is_none() {
-                        break;
-                    }
+            if reason != ExitReason::DownstreamClosed {
+                self.flush().await;
+            }
+
+            reason
+        })
+    }
+
+    async fn pull(&mut self, stop: &mut Option<watch::Receiver<bool>>) -> Result<FrameData, ExitReason> {
+        if let Some(stop) = stop {
+            if *stop.borrow() {
+                return Err(ExitReason::Stopped);
+            }
+        }
+
+        let receiver = match self.receiver.as_mut() {
+            Some(receiver) => receiver,
+            None => {
+                debug!("No receiver registered, allocating an empty frame DTO");
+                return Ok(FrameData::default());
+            }
+        };
+
+        let frame_data = match stop {
+            Some(stop) => tokio::select! {
+                frame_data = receiver.recv() => frame_data,
+                _ = stop_requested(stop) => return Err(ExitReason::Stopped),
+            },
+            None => receiver.recv().await,
+        };
+
+        let frame_data = frame_data.ok_or(ExitReason::EndOfStream)?;
+        debug!("Received frame data: {}", frame_data);
+
+        Ok(frame_data)
+    }
+
+    /// Runs the processors starting from the one at index `first` and sends the result to the
+    /// next component, if any
+    async fn forward(&mut self, frame_data: FrameData, first: usize) -> Result<(), ExitReason> {
+        let mut frame_data = Some(frame_data);
+
+        for processor in self.processors.iter_mut().skip(first) {
+            frame_data = match processor.try_process(frame_data.unwrap()).await {
+                Ok(frame_data) => frame_data,
+                Err((_, ProcessorError::EndOfStream)) => {
+                    debug!("{}", tagged!(self, "End of stream reached"));
+                    return Err(ExitReason::EndOfStream);
                 }
+                Err((mut frame_data, error)) => {
+                    let error_msg = format!("Processor error ({:?} policy): {}", self.error_policy, error);
+                    warn!("{}", tagged!(self, error_msg));
 
-                if self.sender.is_some() {
-                    if let Some(frame_data) = frame_data {
-                        debug!("Sending frame data: {}", frame_data);
-                        if self.sender.as_mut().unwrap().send(frame_data).is_err() {
-                            panic!("{}", tagged!(self, "Error while sending frame data"));
+                    match self.error_policy {
+                        ErrorPolicy::Drop => {
+                            frame_data.set_drop_reason(Some(DropReason::ProcessorError));
+                            Some(frame_data)
                         }
+                        ErrorPolicy::Skip => Some(frame_data),
+                        ErrorPolicy::Stop => return Err(ExitReason::ProcessorError(error.to_string())),
                     }
                 }
+            };
+
+            if frame_data.is_none() {
+                break;
             }
-        })
+        }
+
+        if let (Some(sender), Some(frame_data)) = (self.sender.as_ref(), frame_data) {
+            debug!("Sending frame data: {}", frame_data);
+            if sender.send(frame_data).await.is_err() {
+                debug!("{}", tagged!(self, "Send channel closed"));
+                return Err(ExitReason::DownstreamClosed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flushes the processors in order, so that the frames released by each processor are
+    /// still handled by the ones that follow it
+    async fn flush(&mut self) {
+        for i in 0..self.processors.len() {
+            let flushed_frames = self.processors[i].flush().await;
+
+            for frame_data in flushed_frames {
+                if self.forward(frame_data, i + 1).await.is_err() {
+                    return;
+                }
+            }
+        }
     }
 }
 
+async fn stop_requested(stop: &mut watch::Receiver<bool>) {
+    while !*stop.borrow() {
+        if stop.changed().await.is_err() {
+            // The handle has been dropped, the pipeline cannot be stopped anymore
+            futures::future::pending::<()>().await;
+        }
+    }
+}
 
 impl Default for Component {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use tokio::sync::oneshot;
+
+    use crate::{
+        error::{DropReason, ProcessorError},
+        pipeline::ascode::{channel::BackpressurePolicy, handle::ComponentExit, AscodePipeline},
+        processors::frame_reorder::TimestampBasedFrameReorderingBuffer,
+        traits::{FallibleFrameProcessor, FrameProcessor, ProcessorResult},
+        types::FrameData,
+    };
+
+    use super::{Component, ErrorPolicy, ExitReason};
+
+    /// Emits a frame for each timestamp, then either ends the stream or idles until stopped
+    struct Source {
+        timestamps: Vec<u128>,
+        end_of_stream: bool,
+        exhausted: Option<oneshot::Sender<()>>,
+    }
+
+    impl Source {
+        fn new(timestamps: &[u128], end_of_stream: bool) -> (Self, oneshot::Receiver<()>) {
+            let (sender, receiver) = oneshot::channel();
+            let source = Self {
+                timestamps: timestamps.iter().rev().copied().collect(),
+                end_of_stream,
+                exhausted: Some(sender),
+            };
+
+            (source, receiver)
+        }
+    }
+
+    #[async_trait]
+    impl FallibleFrameProcessor for Source {
+        async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+
+            match self.timestamps.pop() {
+                Some(timestamp) => {
+                    frame_data.set("timestamp", timestamp);
+                    Ok(Some(frame_data))
+                }
+                None => {
+                    if let Some(exhausted) = self.exhausted.take() {
+                        exhausted.send(()).ok();
+                    }
+
+                    match self.end_of_stream {
+                        true => Err((frame_data, ProcessorError::EndOfStream)),
+                        false => Ok(None),
+                    }
+                }
+            }
+        }
+    }
+
+    struct Collector(Arc<Mutex<Vec<u128>>>);
+
+    #[async_trait]
+    impl FrameProcessor for Collector {
+        async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+            self.0.lock().unwrap().push(frame_data.get("timestamp"));
+            Some(frame_data)
+        }
+    }
+
+    /// Fails on the frames with an odd timestamp
+    struct FailingOnOdd;
+
+    #[async_trait]
+    impl FallibleFrameProcessor for FailingOnOdd {
+        async fn try_process(&mut self, frame_data: FrameData) -> ProcessorResult {
+            match frame_data.get("timestamp") % 2 {
+                0 => Ok(Some(frame_data)),
+                _ => Err((
+                    frame_data,
+                    ProcessorError::Other("Odd timestamp".to_string()),
+                )),
+            }
+        }
+    }
+
+    type ReceivedFrames = Arc<Mutex<Vec<(u128, Option<DropReason>)>>>;
+
+    struct DropReasonCollector(ReceivedFrames);
+
+    #[async_trait]
+    impl FrameProcessor for DropReasonCollector {
+        async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+            self.0
+                .lock()
+                .unwrap()
+                .push((frame_data.get("timestamp"), frame_data.get_drop_reason()));
+            Some(frame_data)
+        }
+    }
+
+    /// Runs three frames through a failing processor followed by a collector in the same
+    /// component, then through a collector in the next one
+    async fn run_with_policy(
+        policy: Option<ErrorPolicy>,
+    ) -> (Vec<ExitReason>, ReceivedFrames, ReceivedFrames) {
+        let (source, _) = Source::new(&[1, 2, 3], true);
+        let (same_component, next_component) =
+            (ReceivedFrames::default(), ReceivedFrames::default());
+
+        let mut failing_component = Component::new()
+            .append(FailingOnOdd)
+            .append(DropReasonCollector(same_component.clone()));
+        if let Some(policy) = policy {
+            failing_component = failing_component.error_policy(policy);
+        }
+
+        let exits = AscodePipeline::new()
+            .link(Component::new().append(source))
+            .link(failing_component)
+            .link(Component::new().append(DropReasonCollector(next_component.clone())))
+            .bind()
+            .run()
+            .join()
+            .await;
+
+        (reasons(exits), same_component, next_component)
+    }
+
+    /// Counts the frames discarded by the input channel, slowly handling the other ones
+    struct SlowDropCounter(Arc<Mutex<u64>>);
+
+    #[async_trait]
+    impl FrameProcessor for SlowDropCounter {
+        async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+            match frame_data.get_drop_reason() {
+                Some(DropReason::ChannelOverflow) => *self.0.lock().unwrap() += 1,
+                _ => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+
+            Some(frame_data)
+        }
+    }
+
+    struct Panicking;
+
+    #[async_trait]
+    impl FrameProcessor for Panicking {
+        async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+            if frame_data.has("timestamp") {
+                panic!("Test panic");
+            }
+
+            Some(frame_data)
+        }
+    }
+
+    fn reasons(exits: Vec<ComponentExit>) -> Vec<ExitReason> {
+        exits.into_iter().map(|exit| exit.reason).collect()
+    }
+
+    #[tokio::test]
+    async fn stopping_the_head_flushes_held_frames_in_order() {
+        let (source, exhausted) = Source::new(&[3, 1, 2], false);
+        let collected = Arc::new(Mutex::new(Vec::new()));
+
+        let handle = AscodePipeline::new()
+            .link(Component::new().append(source))
+            .link(
+                Component::new().append(TimestampBasedFrameReorderingBuffer::new(
+                    "timestamp",
+                    u128::MAX,
+                )),
+            )
+            .link(Component::new().append(Collector(collected.clone())))
+            .bind()
+            .run();
+
+        exhausted.await.unwrap();
+        assert!(collected.lock().unwrap().is_empty());
+
+        handle.stop();
+        let exits = handle.join().await;
+
+        assert_eq!(
+            reasons(exits),
+            [
+                ExitReason::Stopped,
+                ExitReason::EndOfStream,
+                ExitReason::EndOfStream
+            ]
+        );
+        assert_eq!(*collected.lock().unwrap(), [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn end_of_stream_reaches_every_component() {
+        let (source, _) = Source::new(&[1, 2], true);
+        let collected = Arc::new(Mutex::new(Vec::new()));
+
+        let exits = AscodePipeline::new()
+            .link(Component::new().append(source))
+            .link(Component::new().append(Collector(collected.clone())))
+            .bind()
+            .run()
+            .join()
+            .await;
+
+        assert_eq!(
+            reasons(exits),
+            [ExitReason::EndOfStream, ExitReason::EndOfStream]
+        );
+        assert_eq!(*collected.lock().unwrap(), [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn drop_policy_marks_failed_frames_by_default() {
+        let marked_frames = [
+            (1, Some(DropReason::ProcessorError)),
+            (2, None),
+            (3, Some(DropReason::ProcessorError)),
+        ];
+
+        for policy in [None, Some(ErrorPolicy::Drop)] {
+            let (exits, same_component, next_component) = run_with_policy(policy).await;
+
+            assert_eq!(exits, vec![ExitReason::EndOfStream; 3]);
+            assert_eq!(*same_component.lock().unwrap(), marked_frames);
+            assert_eq!(*next_component.lock().unwrap(), marked_frames);
+        }
+    }
+
+    #[tokio::test]
+    async fn skip_policy_ignores_errors() {
+        let (exits, same_component, next_component) =
+            run_with_policy(Some(ErrorPolicy::Skip)).await;
+        let unmarked_frames = [(1, None), (2, None), (3, None)];
+
+        assert_eq!(exits, vec![ExitReason::EndOfStream; 3]);
+        assert_eq!(*same_component.lock().unwrap(), unmarked_frames);
+        assert_eq!(*next_component.lock().unwrap(), unmarked_frames);
+    }
+
+    #[tokio::test]
+    async fn stop_policy_stops_on_the_first_error() {
+        let (exits, same_component, next_component) =
+            run_with_policy(Some(ErrorPolicy::Stop)).await;
+
+        assert_eq!(
+            exits[1],
+            ExitReason::ProcessorError("Odd timestamp".to_string())
+        );
+        assert_eq!(exits[2], ExitReason::EndOfStream);
+        assert!(same_component.lock().unwrap().is_empty());
+        assert!(next_component.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn discarded_frames_are_counted_and_forwarded() {
+        let timestamps: Vec<u128> = (0..50).collect();
+        let (source, _) = Source::new(&timestamps, true);
+        let dropped_frames = Arc::new(Mutex::new(0));
+
+        let exits = AscodePipeline::new()
+            .link(Component::new().append(source))
+            .link(
+                Component::new()
+                    .capacity(1)
+                    .backpressure(BackpressurePolicy::DropNewest)
+                    .append(SlowDropCounter(dropped_frames.clone())),
+            )
+            .bind()
+            .run()
+            .join()
+            .await;
+
+        let dropped_frames = *dropped_frames.lock().unwrap();
+        assert!(dropped_frames > 0);
+        assert_eq!(exits[0].discarded_count, 0);
+        assert_eq!(exits[1].discarded_count, dropped_frames);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity")]
+    fn zero_capacity_is_rejected() {
+        AscodePipeline::new()
+            .link(Component::new())
+            .link(Component::new().capacity(0))
+            .bind();
+    }
+
+    #[tokio::test]
+    async fn panicking_processor_closes_the_upstream_components() {
+        // Enough frames for the source to still be sending when the next component panics
+        let timestamps: Vec<u128> = (0..10_000).collect();
+        let (source, _) = Source::new(&timestamps, false);
+
+        let exits = AscodePipeline::new()
+            .link(Component::new().tag("source").append(source))
+            .link(Component::new().tag("panicking").append(Panicking))
+            .bind()
+            .run()
+            .join()
+            .await;
+
+        assert_eq!(exits[0].component_tag.as_deref(), Some("source"));
+        assert_eq!(exits[0].reason, ExitReason::DownstreamClosed);
+        assert_eq!(
+            exits[1].reason,
+            ExitReason::Panicked("Test panic".to_string())
+        );
+    }
+}
@@ -1,19 +1,28 @@
-use tokio::sync::mpsc::UnboundedSender;
+use log::debug;
 
 use crate::types::FrameData;
 
+use super::channel::ChannelSender;
+
+#[derive(Clone)]
 pub struct AscodePipelineFeeder {
-    sender: UnboundedSender<FrameData>
+    sender: ChannelSender
 }
 
 impl AscodePipelineFeeder {
-    pub fn new(sender: UnboundedSender<FrameData>) -> Self {
+    pub fn new(sender: ChannelSender) -> Self {
         Self {
             sender
         }
     }
 
-    pub fn feed(&self, frame_data: FrameData) {
-        self.sender.send(frame_data).unwrap();
+    pub async fn feed(&self, frame_data: FrameData) {
+        if self.sender.send(frame_data).await.is_err() {
+            debug!("Destination pipeline is not running, discarding frame");
+        }
     }
-}
\ No newline at end of file
+
+    pub(crate) fn get_sender(&self) -> ChannelSender {
+        self.sender.clone()
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use log::info;
+use tokio::{sync::watch, task::JoinHandle};
+
+use super::component::ExitReason;
+
+/// Outcome of a component of a pipeline which has been joined
+#[derive(Debug, Clone)]
+pub struct ComponentExit {
+    pub pipeline_tag: String,
+    pub component_index: usize,
+    pub component_tag: Option<String>,
+    pub reason: ExitReason,
+
+    /// Frames discarded by the backpressure policy of the input channel of the component
+    pub discarded_count: u64,
+}
+
+/// Running component, as tracked by the handle
+pub(crate) struct LaunchedComponent {
+    pub(crate) tag: Option<String>,
+    pub(crate) handle: JoinHandle<ExitReason>,
+    pub(crate) discarded_counter: Option<Arc<AtomicU64>>,
+}
+
+impl LaunchedComponent {
+    fn discarded_count(&self) -> u64 {
+        self.discarded_counter
+            .as_ref()
+            .map_or(0, |counter| counter.load(Ordering::Relaxed))
+    }
+}
+
+/// Cloneable trigger which asks a running pipeline to stop. The head component stops pulling
+/// frames and the end of the stream is propagated through the rest of the pipeline.
+#[derive(Clone)]
+pub struct AscodePipelineStopper {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl AscodePipelineStopper {
+    pub(crate) fn new(sender: watch::Sender<bool>) -> Self {
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn stop(&self) {
+        // Sending fails only if the head component has already exited
+        self.sender.send(true).ok();
+    }
+}
+
+pub struct AscodePipelineHandle {
+    tag: String,
+    stopper: AscodePipelineStopper,
+    components: Vec<LaunchedComponent>,
+}
+
+impl AscodePipelineHandle {
+    pub(crate) fn new(
+        tag: &str,
+        stopper: AscodePipelineStopper,
+        components: Vec<LaunchedComponent>,
+    ) -> Self {
+        Self {
+            tag: tag.to_string(),
+            stopper,
+            components,
+        }
+    }
+
+    pub fn stopper(&self) -> AscodePipelineStopper {
+        self.stopper.clone()
+    }
+
+    pub fn stop(&self) {
+        info!("[{}] Stopping...", self.tag);
+        self.stopper.stop();
+    }
+
+    /// Frames discarded so far by the backpressure policy of the input channel of each
+    /// component, in pipeline order
+    pub fn discarded_counts(&self) -> Vec<u64> {
+        self.components
+            .iter()
+            .map(LaunchedComponent::discarded_count)
+            .collect()
+    }
+
+    /// Waits for every component of the pipeline to exit
+    pub async fn join(self) -> Vec<ComponentExit> {
+        let mut exits = Vec::new();
+
+        for (component_index, mut component) in self.components.into_iter().enumerate() {
+            let reason = match (&mut component.handle).await {
+                Ok(reason) => reason,
+                Err(error) => ExitReason::Panicked(panic_message(error)),
+            };
+            let discarded_count = component.discarded_count();
+
+            exits.push(ComponentExit {
+                pipeline_tag: self.tag.clone(),
+                component_index,
+                component_tag: component.tag,
+                reason,
+                discarded_count,
+            });
+        }
+
+        exits
+    }
+}
+
+fn panic_message(error: tokio::task::JoinError) -> String {
+    if !error.is_panic() {
+        return error.to_string();
+    }
+
+    let payload = error.into_panic();
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
@@ -1,16 +1,18 @@
 use log::info;
-use tokio::{sync::mpsc::{self, UnboundedSender}, task::JoinHandle};
+use tokio::sync::watch;
 
-use crate::types::FrameData;
+use crate::error::ConfigError;
 
-use self::{component::Component, feeder::AscodePipelineFeeder};
+use self::{channel::ChannelSender, component::Component, feeder::AscodePipelineFeeder, handle::{AscodePipelineHandle, AscodePipelineStopper, LaunchedComponent}};
 
+pub mod channel;
 pub mod component;
 pub mod feeder;
+pub mod handle;
 
 pub struct AscodePipeline {
     components: Vec<Component>,
-    feeding_sender: Option<UnboundedSender<FrameData>>,
+    feeding_sender: Option<ChannelSender>,
 
     tag: String,
 
@@ -34,54 +36,74 @@ impl AscodePipeline {
         self
     }
 
+    pub fn is_feedable(&self) -> bool {
+        self.feeding_sender.is_some()
+    }
+
     pub fn get_feeder(&self) -> AscodePipelineFeeder {
         let sender = self.feeding_sender.as_ref().unwrap().clone();
         AscodePipelineFeeder::new(sender)
     }
 
-    pub fn run(self) -> Vec<JoinHandle<()>> {
+    pub fn run(self) -> AscodePipelineHandle {
         info!("[{}] Launching threads...", self.tag);
         if !self.bound {
             panic!("[{}] Called 'run' before binding the pipeline", self.tag);
         }
 
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        let mut stop_receiver = Some(stop_receiver);
+
         let mut handles = Vec::new();
 
         for component in self.components {
-            let handle = component.launch();
-            handles.push(handle);
+            let tag = component.get_tag();
+            let discarded_counter = component.discarded_counter();
+            let handle = component.launch(stop_receiver.take());
+
+            handles.push(LaunchedComponent {
+                tag,
+                handle,
+                discarded_counter,
+            });
         }
 
-        handles
+        AscodePipelineHandle::new(&self.tag, AscodePipelineStopper::new(stop_sender), handles)
     }
 
-    pub fn bind(mut self) -> Self {
+    /// Panics if a component is misconfigured, see [`AscodePipeline::try_bind`]
+    pub fn bind(self) -> Self {
+        self.try_bind()
+            .unwrap_or_else(|error| panic!("Unable to bind the pipeline: {}", error))
+    }
+
+    pub fn try_bind(mut self) -> Result<Self, ConfigError> {
         info!("[{}] Binding channels...", self.tag);
 
         for i in 0..self.components.len()-1 {
-            let (sender, receiver) = mpsc::unbounded_channel::<FrameData>();
+            let dst_component = self.components.get_mut(i + 1).unwrap();
+            let sender = dst_component.create_input_channel()?;
 
             let src_component = self.components.get_mut(i).unwrap();
             src_component.set_sender(sender);
-
-            let dst_component = self.components.get_mut(i + 1).unwrap();
-            dst_component.set_receiver(receiver);
         }
 
         self.bound = true;
 
-        self
+        Ok(self)
     }
 
-    pub fn feedable(mut self) -> Self {
-        let head = self.components.get_mut(0).unwrap();
-
-        let (sender, receiver) = mpsc::unbounded_channel::<FrameData>();
-        self.feeding_sender = Some(sender);
+    /// Panics if the head component is misconfigured, see [`AscodePipeline::try_feedable`]
+    pub fn feedable(self) -> Self {
+        self.try_feedable()
+            .unwrap_or_else(|error| panic!("Unable to make the pipeline feedable: {}", error))
+    }
 
-        head.set_receiver(receiver);
+    pub fn try_feedable(mut self) -> Result<Self, ConfigError> {
+        let head = self.components.get_mut(0).unwrap();
+        self.feeding_sender = Some(head.create_input_channel()?);
 
-        self
+        Ok(self)
     }
 
     pub fn tag(mut self, tag: &str) -> Self {
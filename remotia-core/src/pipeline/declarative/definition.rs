@@ -0,0 +1,340 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+
+use crate::{
+    error::ConfigError,
+    pipeline::ascode::{channel::BackpressurePolicy, component::ErrorPolicy},
+};
+
+/// Topology of a set of pipelines, as described in a TOML or YAML file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PipelinesDefinition {
+    pub pipelines: Vec<PipelineDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PipelineDefinition {
+    pub tag: String,
+
+    /// Whether other pipelines can feed frames to this one, e.g. through a switch
+    #[serde(default)]
+    pub feedable: bool,
+
+    pub components: Vec<ComponentDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComponentDefinition {
+    pub tag: Option<String>,
+
+    pub capacity: Option<usize>,
+
+    #[serde(default)]
+    pub backpressure: BackpressurePolicy,
+
+    /// Tag of the pipeline receiving the frames discarded by the input channel
+    pub on_drop: Option<String>,
+
+    #[serde(default)]
+    pub error_policy: ErrorPolicy,
+
+    pub processors: Vec<ProcessorDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProcessorDefinition {
+    /// Name under which the processor factory is registered
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    #[serde(flatten)]
+    pub params: ProcessorParams,
+}
+
+/// Value of a processor parameter. Deserialized through a visitor rather than an untagged
+/// enum, as flattened untagged values are not reliably matched across formats.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    List(Vec<ParamValue>),
+}
+
+struct ParamValueVisitor;
+
+impl<'de> Visitor<'de> for ParamValueVisitor {
+    type Value = ParamValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a boolean, a number, a string or a list of them")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<ParamValue, E> {
+        Ok(ParamValue::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<ParamValue, E> {
+        Ok(ParamValue::Integer(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<ParamValue, E> {
+        i64::try_from(value)
+            .map(ParamValue::Integer)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<ParamValue, E> {
+        Ok(ParamValue::Float(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<ParamValue, E> {
+        Ok(ParamValue::Text(value.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<ParamValue, E> {
+        Ok(ParamValue::Text(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ParamValue, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+
+        Ok(ParamValue::List(values))
+    }
+}
+
+impl<'de> Deserialize<'de> for ParamValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ParamValueVisitor)
+    }
+}
+
+/// Parameters of a processor, with typed accessors for the factories
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct ProcessorParams(HashMap<String, ParamValue>);
+
+impl PipelinesDefinition {
+    pub fn from_toml_str(definition: &str) -> Result<Self, ConfigError> {
+        toml::from_str(definition).map_err(|error| ConfigError::Parse(error.to_string()))
+    }
+
+    pub fn from_yaml_str(definition: &str) -> Result<Self, ConfigError> {
+        serde_yaml::from_str(definition).map_err(|error| ConfigError::Parse(error.to_string()))
+    }
+
+    /// Loads a definition, choosing the format from the file extension
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let definition = std::fs::read_to_string(path)?;
+
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("");
+
+        match extension {
+            "toml" => Self::from_toml_str(&definition),
+            "yaml" | "yml" => Self::from_yaml_str(&definition),
+            _ => Err(ConfigError::UnsupportedFormat(extension.to_string())),
+        }
+    }
+}
+
+impl ProcessorParams {
+    pub fn has(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    pub fn get(&self, key: &str) -> Result<&ParamValue, ConfigError> {
+        self.0
+            .get(key)
+            .ok_or_else(|| ConfigError::MissingParameter(key.to_string()))
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<bool, ConfigError> {
+        match self.get(key)? {
+            ParamValue::Bool(value) => Ok(*value),
+            _ => Err(invalid_parameter(key, "bool")),
+        }
+    }
+
+    pub fn get_u64(&self, key: &str) -> Result<u64, ConfigError> {
+        match self.get(key)? {
+            ParamValue::Integer(value) if *value >= 0 => Ok(*value as u64),
+            _ => Err(invalid_parameter(key, "unsigned integer")),
+        }
+    }
+
+    pub fn get_f64(&self, key: &str) -> Result<f64, ConfigError> {
+        match self.get(key)? {
+            ParamValue::Integer(value) => Ok(*value as f64),
+            ParamValue::Float(value) => Ok(*value),
+            _ => Err(invalid_parameter(key, "float")),
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<&str, ConfigError> {
+        match self.get(key)? {
+            ParamValue::Text(value) => Ok(value),
+            _ => Err(invalid_parameter(key, "string")),
+        }
+    }
+
+    pub fn get_str_list(&self, key: &str) -> Result<Vec<&str>, ConfigError> {
+        match self.get(key)? {
+            ParamValue::List(values) => values
+                .iter()
+                .map(|value| match value {
+                    ParamValue::Text(value) => Ok(value.as_str()),
+                    _ => Err(invalid_parameter(key, "list of strings")),
+                })
+                .collect(),
+            _ => Err(invalid_parameter(key, "list of strings")),
+        }
+    }
+}
+
+fn invalid_parameter(key: &str, expected: &'static str) -> ConfigError {
+    ConfigError::InvalidParameter {
+        key: key.to_string(),
+        expected,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::ConfigError,
+        pipeline::ascode::{channel::BackpressurePolicy, component::ErrorPolicy},
+    };
+
+    use super::{ParamValue, PipelinesDefinition};
+
+    fn example_path(extension: &str) -> String {
+        format!(
+            "{}/examples/pipelines.{}",
+            env!("CARGO_MANIFEST_DIR"),
+            extension
+        )
+    }
+
+    #[test]
+    fn example_configs_describe_the_same_topology() {
+        let toml_definition = PipelinesDefinition::from_file(example_path("toml")).unwrap();
+        let yaml_definition = PipelinesDefinition::from_file(example_path("yaml")).unwrap();
+        assert_eq!(toml_definition, yaml_definition);
+
+        let tags: Vec<&str> = toml_definition
+            .pipelines
+            .iter()
+            .map(|pipeline| pipeline.tag.as_str())
+            .collect();
+        assert_eq!(tags, ["dropped", "delivered", "source"]);
+
+        let network = &toml_definition.pipelines[2].components[1];
+        assert_eq!(network.capacity, Some(4));
+        assert_eq!(network.backpressure, BackpressurePolicy::DropNewest);
+        assert_eq!(network.error_policy, ErrorPolicy::Skip);
+
+        let emulator = &network.processors[0];
+        assert_eq!(emulator.kind, "network_emulator");
+        assert_eq!(emulator.params.get_str("destination").unwrap(), "delivered");
+        assert_eq!(emulator.params.get_f64("delay").unwrap(), 20.0);
+        assert_eq!(emulator.params.get_u64("bandwidth").unwrap(), 20_000_000);
+        assert!(!emulator.params.has("type"));
+    }
+
+    #[test]
+    fn flattened_parameters_keep_their_type_in_both_formats() {
+        let toml_definition = PipelinesDefinition::from_toml_str(
+            r#"
+            [[pipelines]]
+            tag = "main"
+
+            [[pipelines.components]]
+
+            [[pipelines.components.processors]]
+            type = "test"
+            flag = true
+            count = 3
+            offset = -4
+            ratio = 0.5
+            name = "test"
+            keys = ["a", "b"]
+            "#,
+        )
+        .unwrap();
+
+        let yaml_definition = PipelinesDefinition::from_yaml_str(
+            "
+            pipelines:
+              - tag: main
+                components:
+                  - processors:
+                      - type: test
+                        flag: true
+                        count: 3
+                        offset: -4
+                        ratio: 0.5
+                        name: test
+                        keys: [a, b]
+            ",
+        )
+        .unwrap();
+
+        for definition in [toml_definition, yaml_definition] {
+            let params = &definition.pipelines[0].components[0].processors[0].params;
+
+            assert_eq!(params.get("flag").unwrap(), &ParamValue::Bool(true));
+            assert_eq!(params.get("count").unwrap(), &ParamValue::Integer(3));
+            assert_eq!(params.get("offset").unwrap(), &ParamValue::Integer(-4));
+            assert_eq!(params.get("ratio").unwrap(), &ParamValue::Float(0.5));
+            assert_eq!(
+                params.get("name").unwrap(),
+                &ParamValue::Text("test".to_string())
+            );
+            assert_eq!(params.get_str_list("keys").unwrap(), ["a", "b"]);
+        }
+    }
+
+    #[test]
+    fn unsupported_parameters_are_rejected() {
+        let definition = "
+            pipelines:
+              - tag: main
+                components:
+                  - processors:
+                      - type: test
+                        count: 18446744073709551615
+            ";
+
+        assert!(matches!(
+            PipelinesDefinition::from_yaml_str(definition),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_extensions_are_rejected() {
+        let path = std::env::temp_dir().join("remotia_pipelines.json");
+        std::fs::write(&path, "{}").unwrap();
+
+        assert!(matches!(
+            PipelinesDefinition::from_file(&path),
+            Err(ConfigError::UnsupportedFormat(extension)) if extension == "json"
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
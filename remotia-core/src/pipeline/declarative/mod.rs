@@ -0,0 +1,367 @@
+use std::collections::{HashMap, HashSet};
+
+use log::debug;
+
+use crate::error::ConfigError;
+
+use self::{
+    definition::{PipelineDefinition, PipelinesDefinition},
+    registry::{BuildContext, ProcessorRegistry},
+};
+
+use super::ascode::{component::Component, AscodePipeline};
+
+pub mod definition;
+pub mod registry;
+
+/// Instantiates the pipelines of a definition, indexed by tag. The returned pipelines are
+/// bound and ready to be run. See `examples/pipelines.toml` for a complete definition.
+pub fn build_pipelines(
+    definition: &PipelinesDefinition,
+    registry: &ProcessorRegistry,
+) -> Result<HashMap<String, AscodePipeline>, ConfigError> {
+    let mut defined_pipelines = HashSet::new();
+    for pipeline_definition in &definition.pipelines {
+        if !defined_pipelines.insert(pipeline_definition.tag.clone()) {
+            return Err(ConfigError::DuplicatePipeline(pipeline_definition.tag.clone()));
+        }
+    }
+
+    let mut built_pipelines = HashMap::new();
+    let mut pending: Vec<&PipelineDefinition> = definition.pipelines.iter().collect();
+
+    // Pipelines referencing other ones are deferred until their destinations have been built
+    while !pending.is_empty() {
+        let mut deferred = Vec::new();
+
+        for pipeline_definition in &pending {
+            let context = BuildContext {
+                built_pipelines: &built_pipelines,
+                defined_pipelines: &defined_pipelines,
+            };
+
+            match build_pipeline(pipeline_definition, registry, &context) {
+                Ok(pipeline) => {
+                    built_pipelines.insert(pipeline_definition.tag.clone(), pipeline);
+                }
+                Err(ConfigError::UnknownPipeline(tag)) if context.is_pending(&tag) => {
+                    debug!("Deferring pipeline '{}' (waiting for '{}')", pipeline_definition.tag, tag);
+                    deferred.push(*pipeline_definition);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        if deferred.len() == pending.len() {
+            let tags = deferred.iter().map(|definition| definition.tag.clone()).collect();
+            return Err(ConfigError::CyclicPipelines(tags));
+        }
+
+        pending = deferred;
+    }
+
+    Ok(built_pipelines)
+}
+
+fn build_pipeline(
+    definition: &PipelineDefinition,
+    registry: &ProcessorRegistry,
+    context: &BuildContext,
+) -> Result<AscodePipeline, ConfigError> {
+    if definition.components.is_empty() {
+        return Err(ConfigError::EmptyPipeline(definition.tag.clone()));
+    }
+
+    let mut pipeline = AscodePipeline::new().tag(&definition.tag);
+
+    for component_definition in &definition.components {
+        let mut component = Component::new()
+            .backpressure(component_definition.backpressure)
+            .error_policy(component_definition.error_policy);
+
+        if let Some(tag) = &component_definition.tag {
+            component = component.tag(tag);
+        }
+
+        if let Some(capacity) = component_definition.capacity {
+            component = component.capacity(capacity);
+        }
+
+        if let Some(drop_pipeline) = &component_definition.on_drop {
+            component = component.on_drop(context.pipeline(drop_pipeline)?);
+        }
+
+        for processor_definition in &component_definition.processors {
+            let processor = registry.create(
+                &processor_definition.kind,
+                &processor_definition.params,
+                context,
+            )?;
+            component = component.append_boxed(processor);
+        }
+
+        pipeline = pipeline.link(component);
+    }
+
+    if definition.feedable {
+        pipeline = pipeline.try_feedable()?;
+    }
+
+    pipeline.try_bind()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ConfigError;
+
+    use super::{build_pipelines, definition::PipelinesDefinition, registry::ProcessorRegistry};
+
+    fn example_path(extension: &str) -> String {
+        format!(
+            "{}/examples/pipelines.{}",
+            env!("CARGO_MANIFEST_DIR"),
+            extension
+        )
+    }
+
+    fn build(definition: &str) -> Result<Vec<String>, ConfigError> {
+        let definition = PipelinesDefinition::from_yaml_str(definition)?;
+        let pipelines = build_pipelines(&definition, &ProcessorRegistry::default())?;
+
+        let mut tags: Vec<String> = pipelines.into_keys().collect();
+        tags.sort();
+        Ok(tags)
+    }
+
+    #[tokio::test]
+    async fn example_config_is_built() {
+        for extension in ["toml", "yaml"] {
+            let definition = PipelinesDefinition::from_file(example_path(extension)).unwrap();
+            let pipelines = build_pipelines(&definition, &ProcessorRegistry::default()).unwrap();
+
+            assert_eq!(pipelines.len(), 3);
+            assert!(pipelines["delivered"].is_feedable());
+            assert!(pipelines["dropped"].is_feedable());
+            assert!(!pipelines["source"].is_feedable());
+        }
+    }
+
+    #[tokio::test]
+    async fn pipelines_referencing_later_ones_are_deferred() {
+        let tags = build(
+            "
+            pipelines:
+              - tag: source
+                components:
+                  - processors:
+                      - type: ticker
+                        interval: 10
+                      - type: switch
+                        destination: sink
+              - tag: sink
+                feedable: true
+                components:
+                  - processors:
+                      - type: key_checker
+                        key: timestamp
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(tags, ["sink", "source"]);
+    }
+
+    #[test]
+    fn unknown_processors_are_reported() {
+        let result = build(
+            "
+            pipelines:
+              - tag: main
+                components:
+                  - processors:
+                      - type: teleporter
+            ",
+        );
+
+        assert!(matches!(result, Err(ConfigError::UnknownProcessor(name)) if name == "teleporter"));
+    }
+
+    #[test]
+    fn missing_parameters_are_reported() {
+        let result = build(
+            "
+            pipelines:
+              - tag: main
+                components:
+                  - processors:
+                      - type: key_checker
+            ",
+        );
+
+        assert!(matches!(result, Err(ConfigError::MissingParameter(key)) if key == "key"));
+    }
+
+    #[test]
+    fn mistyped_parameters_are_reported() {
+        let result = build(
+            "
+            pipelines:
+              - tag: main
+                components:
+                  - processors:
+                      - type: key_checker
+                        key: 3
+            ",
+        );
+
+        assert!(matches!(
+            result,
+            Err(ConfigError::InvalidParameter { key, expected: "string" }) if key == "key"
+        ));
+    }
+
+    #[test]
+    fn dangling_references_are_reported() {
+        let result = build(
+            "
+            pipelines:
+              - tag: main
+                components:
+                  - processors:
+                      - type: switch
+                        destination: nowhere
+            ",
+        );
+
+        assert!(matches!(result, Err(ConfigError::UnknownPipeline(tag)) if tag == "nowhere"));
+    }
+
+    #[test]
+    fn references_to_unfeedable_pipelines_are_reported() {
+        let result = build(
+            "
+            pipelines:
+              - tag: sink
+                components:
+                  - processors:
+                      - type: key_checker
+                        key: timestamp
+              - tag: main
+                components:
+                  - processors:
+                      - type: switch
+                        destination: sink
+            ",
+        );
+
+        assert!(matches!(result, Err(ConfigError::NotFeedable(tag)) if tag == "sink"));
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let result = build(
+            "
+            pipelines:
+              - tag: first
+                feedable: true
+                components:
+                  - processors:
+                      - type: switch
+                        destination: second
+              - tag: second
+                feedable: true
+                components:
+                  - on_drop: first
+                    processors:
+                      - type: key_checker
+                        key: timestamp
+              - tag: independent
+                components:
+                  - processors:
+                      - type: key_checker
+                        key: timestamp
+            ",
+        );
+
+        assert!(matches!(
+            result,
+            Err(ConfigError::CyclicPipelines(tags)) if tags == ["first", "second"]
+        ));
+    }
+
+    #[test]
+    fn duplicate_and_empty_pipelines_are_reported() {
+        let duplicate = build(
+            "
+            pipelines:
+              - tag: main
+                components: []
+              - tag: main
+                components: []
+            ",
+        );
+        assert!(matches!(duplicate, Err(ConfigError::DuplicatePipeline(tag)) if tag == "main"));
+
+        let empty = build(
+            "
+            pipelines:
+              - tag: main
+                components: []
+            ",
+        );
+        assert!(matches!(empty, Err(ConfigError::EmptyPipeline(tag)) if tag == "main"));
+    }
+
+    #[test]
+    fn zero_capacities_are_reported() {
+        let result = build(
+            "
+            pipelines:
+              - tag: main
+                components:
+                  - processors:
+                      - type: key_checker
+                        key: timestamp
+                  - capacity: 0
+                    processors:
+                      - type: key_checker
+                        key: timestamp
+            ",
+        );
+
+        assert!(matches!(
+            result,
+            Err(ConfigError::InvalidParameter { key, .. }) if key == "capacity"
+        ));
+    }
+
+    #[test]
+    fn unrepresentable_delays_are_reported() {
+        for jitter in ["1.0e300", ".inf"] {
+            let result = build(&format!(
+                "
+                pipelines:
+                  - tag: delivered
+                    feedable: true
+                    components:
+                      - processors:
+                          - type: key_checker
+                            key: capture_timestamp
+                  - tag: main
+                    components:
+                      - processors:
+                          - type: network_emulator
+                            destination: delivered
+                            buffer: encoded_frame_buffer
+                            jitter: {}
+                ",
+                jitter
+            ));
+
+            assert!(matches!(
+                result,
+                Err(ConfigError::InvalidParameter { key, .. }) if key == "jitter"
+            ));
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use crate::{
+    common::network::impairment::{LossModel, NetworkImpairment},
+    error::ConfigError,
+    pipeline::ascode::AscodePipeline,
+    processors::{
+        clone_switch::CloneSwitch,
+        debug::{network_emulator::NetworkEmulatorSwitch, random_dropper::RandomFrameDropper},
+        error_switch::OnErrorSwitch,
+        frame_drop::{threshold::ThresholdBasedFrameDropper, timestamp::TimestampBasedFrameDropper},
+        frame_reorder::TimestampBasedFrameReorderingBuffer,
+        key_check::KeyChecker,
+        pool_switch::{DepoolingSwitch, PoolingSwitch},
+        switch::Switch,
+        ticker::Ticker,
+    },
+    traits::FrameProcessor,
+};
+
+use super::definition::ProcessorParams;
+
+pub type BoxedFrameProcessor = Box<dyn FrameProcessor + Send>;
+
+type ProcessorFactory =
+    Box<dyn Fn(&ProcessorParams, &BuildContext) -> Result<BoxedFrameProcessor, ConfigError>>;
+
+/// Pipelines which have already been built, to be referenced by switches
+pub struct BuildContext<'a> {
+    pub(crate) built_pipelines: &'a HashMap<String, AscodePipeline>,
+    pub(crate) defined_pipelines: &'a HashSet<String>,
+}
+
+impl<'a> BuildContext<'a> {
+    /// Looks up a feedable pipeline. Pipelines which are defined but not built yet are
+    /// reported as unknown, so that the builder can retry once they are available.
+    pub fn pipeline(&self, tag: &str) -> Result<&'a AscodePipeline, ConfigError> {
+        let pipeline = self
+            .built_pipelines
+            .get(tag)
+            .ok_or_else(|| ConfigError::UnknownPipeline(tag.to_string()))?;
+
+        if !pipeline.is_feedable() {
+            return Err(ConfigError::NotFeedable(tag.to_string()));
+        }
+
+        Ok(pipeline)
+    }
+
+    pub(crate) fn is_pending(&self, tag: &str) -> bool {
+        self.defined_pipelines.contains(tag) && !self.built_pipelines.contains_key(tag)
+    }
+}
+
+/// Reads the optional impairment parameters. Times are in milliseconds and bandwidth in bits
+/// per second. Burst losses are enabled by "good_to_bad" and "bad_to_good".
+fn network_impairment(params: &ProcessorParams) -> Result<NetworkImpairment, ConfigError> {
+    let optional_f64 = |key: &str, default: f64| {
+        if params.has(key) {
+            params.get_f64(key)
+        } else {
+            Ok(default)
+        }
+    };
+
+    let optional_millis = |key: &str| {
+        Duration::try_from_secs_f64(optional_f64(key, 0.0)?.max(0.0) / 1000.0).map_err(|_| {
+            ConfigError::InvalidParameter {
+                key: key.to_string(),
+                expected: "duration in milliseconds",
+            }
+        })
+    };
+
+    let mut impairment = NetworkImpairment::new()
+        .delay(optional_millis("delay")?)
+        .jitter(optional_millis("jitter")?)
+        .duplication(optional_f64("duplication", 0.0)?)
+        .reordering(optional_f64("reordering", 0.0)?);
+
+    if params.has("good_to_bad") {
+        impairment = impairment.loss(LossModel::GilbertElliott {
+            good_to_bad: params.get_f64("good_to_bad")?,
+            bad_to_good: params.get_f64("bad_to_good")?,
+            good_loss: optional_f64("good_loss", 0.0)?,
+            bad_loss: optional_f64("bad_loss", 1.0)?,
+        });
+    } else if params.has("loss") {
+        impairment = impairment.loss(LossModel::Bernoulli(params.get_f64("loss")?));
+    }
+
+    if params.has("bandwidth") {
+        impairment = impairment.bandwidth(params.get_u64("bandwidth")?);
+    }
+
+    if params.has("seed") {
+        impairment = impairment.seed(params.get_u64("seed")?);
+    }
+
+    Ok(impairment)
+}
+
+/// Named processor factories used to instantiate the processors of a definition
+pub struct ProcessorRegistry {
+    factories: HashMap<String, ProcessorFactory>,
+}
+
+impl ProcessorRegistry {
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Registry including the processors of remotia-core
+    pub fn with_core_processors() -> Self {
+        Self::new()
+            .register("ticker", |params, _| {
+                Ok(Box::new(Ticker::new(params.get_u64("interval")?)))
+            })
+            .register("switch", |params, context| {
+                let destination = context.pipeline(params.get_str("destination")?)?;
+                Ok(Box::new(Switch::new(destination)))
+            })
+            .register("clone_switch", |params, context| {
+                let destination = context.pipeline(params.get_str("destination")?)?;
+                Ok(Box::new(CloneSwitch::new(destination)))
+            })
+            .register("error_switch", |params, context| {
+                let destination = context.pipeline(params.get_str("destination")?)?;
+                Ok(Box::new(OnErrorSwitch::new(destination)))
+            })
+            .register("pooling_switch", |params, context| {
+                let mut switch = PoolingSwitch::new();
+                for (key, tag) in params.get_str_list("pipelines")?.into_iter().enumerate() {
+                    switch = switch.entry(key as u128, context.pipeline(tag)?);
+                }
+                Ok(Box::new(switch))
+            })
+            .register("depooling_switch", |params, context| {
+                let mut switch = DepoolingSwitch::new();
+                for (key, tag) in params.get_str_list("pipelines")?.into_iter().enumerate() {
+                    switch = switch.entry(key as u128, context.pipeline(tag)?);
+                }
+                Ok(Box::new(switch))
+            })
+            .register("key_checker", |params, _| {
+                Ok(Box::new(KeyChecker::new(params.get_str("key")?)))
+            })
+            .register("threshold_frame_dropper", |params, _| {
+                Ok(Box::new(ThresholdBasedFrameDropper::new(
+                    params.get_str("stat")?,
+                    params.get_u64("threshold")? as u128,
+                )))
+            })
+            .register("timestamp_frame_dropper", |params, _| {
+                Ok(Box::new(TimestampBasedFrameDropper::new(params.get_str("stat")?)))
+            })
+            .register("timestamp_frame_reordering_buffer", |params, _| {
+                Ok(Box::new(TimestampBasedFrameReorderingBuffer::new(
+                    params.get_str("stat")?,
+                    params.get_u64("delay")? as u128,
+                )))
+            })
+            .register("random_frame_dropper", |params, _| {
+                Ok(Box::new(RandomFrameDropper::new(params.get_f64("probability")? as f32)))
+            })
+            .register("network_emulator", |params, context| {
+                let destination = context.pipeline(params.get_str("destination")?)?;
+
+                let mut emulator =
+                    NetworkEmulatorSwitch::new(destination, network_impairment(params)?);
+                if params.has("buffer") {
+                    emulator = emulator.buffer_id(params.get_str("buffer")?);
+                }
+                if params.has("size_stat") {
+                    emulator = emulator.size_stat(params.get_str("size_stat")?);
+                }
+
+                Ok(Box::new(emulator))
+            })
+    }
+
+    pub fn register<F>(mut self, name: &str, factory: F) -> Self
+    where
+        F: 'static + Fn(&ProcessorParams, &BuildContext) -> Result<BoxedFrameProcessor, ConfigError>,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
+        self
+    }
+
+    pub fn create(
+        &self,
+        name: &str,
+        params: &ProcessorParams,
+        context: &BuildContext,
+    ) -> Result<BoxedFrameProcessor, ConfigError> {
+        let factory = self
+            .factories
+            .get(name)
+            .ok_or_else(|| ConfigError::UnknownProcessor(name.to_string()))?;
+
+        factory(params, context)
+    }
+}
+
+impl Default for ProcessorRegistry {
+    fn default() -> Self {
+        Self::with_core_processors()
+    }
+}
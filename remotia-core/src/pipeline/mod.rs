@@ -1 +1,2 @@
 pub mod ascode;
+pub mod declarative;
@@ -18,7 +18,7 @@ impl CloneSwitch {
 #[async_trait]
 impl FrameProcessor for CloneSwitch {
     async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
-        self.feeder.feed(frame_data.clone());
+        self.feeder.feed(frame_data.clone()).await;
         Some(frame_data)
     }
 }
@@ -1 +1,2 @@
+pub mod network_emulator;
 pub mod random_dropper;
\ No newline at end of file
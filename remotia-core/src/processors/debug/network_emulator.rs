@@ -0,0 +1,101 @@
+use std::time::Instant;
+
+use async_trait::async_trait;
+use log::debug;
+
+use crate::{
+    common::network::impairment::{DelayedDelivery, ImpairmentModel, NetworkImpairment},
+    error::DropReason,
+    pipeline::ascode::{feeder::AscodePipelineFeeder, AscodePipeline},
+    traits::FrameProcessor,
+    types::FrameData,
+};
+
+/// Feeds the frames to the destination pipeline as if they had crossed an impaired network.
+/// Frames are delivered in the background after their delay, possibly duplicated or out of
+/// order, until all of them are delivered even if the source pipeline stops. Lost frames are
+/// delivered immediately, marked with [`DropReason::EmulatedLoss`].
+pub struct NetworkEmulatorSwitch {
+    feeder: AscodePipelineFeeder,
+    model: ImpairmentModel,
+    delivery: Option<DelayedDelivery<FrameData>>,
+
+    buffer_id: String,
+    size_stat_id: Option<String>,
+}
+
+impl NetworkEmulatorSwitch {
+    pub fn new(destination_pipeline: &AscodePipeline, impairment: NetworkImpairment) -> Self {
+        Self {
+            feeder: destination_pipeline.get_feeder(),
+            model: ImpairmentModel::new(impairment),
+            delivery: None,
+            buffer_id: "encoded_frame_buffer".to_string(),
+            size_stat_id: None,
+        }
+    }
+
+    /// Buffer whose size is accounted for by the bandwidth cap
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat holding the amount of meaningful bytes of the buffer. The whole buffer is
+    /// accounted for if not set.
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = Some(stat_id.to_string());
+        self
+    }
+
+    fn deliver_later(&mut self, frame_data: FrameData, delivery_time: Instant) {
+        let feeder = &self.feeder;
+        let delivery = self.delivery.get_or_insert_with(|| {
+            let feeder = feeder.clone();
+            DelayedDelivery::spawn(move |frame_data| {
+                let feeder = feeder.clone();
+                async move { feeder.feed(frame_data).await }
+            })
+        });
+
+        delivery.schedule(delivery_time, frame_data);
+    }
+
+    fn frame_size(&self, frame_data: &mut FrameData) -> usize {
+        if let Some(size) = self
+            .size_stat_id
+            .as_ref()
+            .and_then(|stat_id| frame_data.try_get(stat_id).ok())
+        {
+            return size as usize;
+        }
+
+        frame_data
+            .get_writable_buffer_ref(&self.buffer_id)
+            .map_or(0, |buffer| buffer.len())
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for NetworkEmulatorSwitch {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let frame_size = self.frame_size(&mut frame_data);
+        let mut delivery_times = self.model.schedule(frame_size);
+
+        if delivery_times.is_empty() {
+            debug!("Emulating the loss of a frame");
+            frame_data.set_drop_reason(Some(DropReason::EmulatedLoss));
+            self.feeder.feed(frame_data).await;
+            return None;
+        }
+
+        // Duplicates are delivered as copies of the frame DTO
+        let last_delivery_time = delivery_times.pop().unwrap();
+        for delivery_time in delivery_times {
+            self.deliver_later(frame_data.clone(), delivery_time);
+        }
+        self.deliver_later(frame_data, last_delivery_time);
+
+        None
+    }
+}
@@ -22,7 +22,7 @@ impl FrameProcessor for OnErrorSwitch {
 
         if frame_data.get_drop_reason().is_some() {
             debug!("Feeding frame");
-            self.feeder.feed(frame_data);
+            self.feeder.feed(frame_data).await;
             None
         } else {
             Some(frame_data)
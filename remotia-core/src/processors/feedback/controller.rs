@@ -0,0 +1,242 @@
+use std::time::Instant;
+
+use async_trait::async_trait;
+use log::debug;
+
+use crate::{
+    common::feedback::{
+        policy::AdaptationPolicy, transport::FeedbackReceiver, StreamSettings, TARGET_BITRATE_STAT,
+        TARGET_FRAME_RATE_STAT,
+    },
+    error::DropReason,
+    traits::FrameProcessor,
+    types::FrameData,
+};
+
+/// Fraction of a frame interval by which a frame may arrive early and still be forwarded
+const FRAME_RATE_TOLERANCE: f64 = 0.1;
+
+/// Server-side processor adapting the stream settings to the feedback of the client through
+/// a pluggable policy. The current settings are stored in the "target_bitrate" and
+/// "target_frame_rate" stats of each frame, to be applied by the processors which support
+/// them (e.g. the encoders).
+///
+/// When the frame rate is limited, frames exceeding the target one are marked with
+/// [`DropReason::FrameRateLimit`].
+pub struct AdaptiveController {
+    receiver: Box<dyn FeedbackReceiver>,
+    policy: Box<dyn AdaptationPolicy>,
+
+    settings: StreamSettings,
+    limit_frame_rate: bool,
+
+    /// Whether the last poll failed, not to log the same failure on each frame
+    receiver_failed: bool,
+
+    frame_credit: f64,
+    last_frame_time: Option<Instant>,
+}
+
+impl AdaptiveController {
+    pub fn new<R, P>(receiver: R, policy: P) -> Self
+    where
+        R: FeedbackReceiver + 'static,
+        P: AdaptationPolicy + 'static,
+    {
+        Self {
+            receiver: Box::new(receiver),
+            policy: Box::new(policy),
+            settings: StreamSettings::default(),
+            limit_frame_rate: false,
+            receiver_failed: false,
+            frame_credit: 1.0,
+            last_frame_time: None,
+        }
+    }
+
+    pub fn initial_settings(mut self, settings: StreamSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn limit_frame_rate(mut self, limit_frame_rate: bool) -> Self {
+        self.limit_frame_rate = limit_frame_rate;
+        self
+    }
+
+    fn poll_feedback(&mut self) {
+        loop {
+            let message = match self.receiver.try_receive() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(error) => {
+                    if !self.receiver_failed {
+                        debug!("Unable to receive feedback: {}", error);
+                    }
+
+                    self.receiver_failed = true;
+                    break;
+                }
+            };
+            self.receiver_failed = false;
+
+            let previous_settings = self.settings;
+            self.policy.on_feedback(&message, &mut self.settings);
+
+            if self.settings != previous_settings {
+                debug!(
+                    "Stream settings adapted to {:?} on {:?}",
+                    self.settings, message
+                );
+            }
+        }
+    }
+
+    /// Whether the frame fits in the target frame rate, accounting the elapsed time as
+    /// credit for new frames
+    fn within_frame_rate(&mut self) -> bool {
+        self.within_frame_rate_at(Instant::now())
+    }
+
+    fn within_frame_rate_at(&mut self, now: Instant) -> bool {
+        if let Some(last_frame_time) = self.last_frame_time {
+            let elapsed = now.duration_since(last_frame_time).as_secs_f64();
+            self.frame_credit =
+                (self.frame_credit + elapsed * self.settings.frame_rate as f64).min(1.0);
+        }
+        self.last_frame_time = Some(now);
+
+        if self.frame_credit < 1.0 - FRAME_RATE_TOLERANCE {
+            return false;
+        }
+
+        self.frame_credit -= 1.0;
+        true
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for AdaptiveController {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        self.poll_feedback();
+
+        frame_data.set(TARGET_BITRATE_STAT, self.settings.bitrate as u128);
+        frame_data.set(TARGET_FRAME_RATE_STAT, self.settings.frame_rate as u128);
+
+        if self.limit_frame_rate && !self.within_frame_rate() {
+            debug!(
+                "Dropping frame exceeding the target frame rate ({} fps)",
+                self.settings.frame_rate
+            );
+            frame_data.set_drop_reason(Some(DropReason::FrameRateLimit));
+        }
+
+        Some(frame_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        common::feedback::{
+            policy::AIMDPolicy,
+            transport::{local_channel, FeedbackSender},
+            FeedbackMessage, StreamSettings, TARGET_BITRATE_STAT, TARGET_FRAME_RATE_STAT,
+        },
+        error::DropReason,
+        traits::FrameProcessor,
+        types::FrameData,
+    };
+
+    use super::AdaptiveController;
+
+    fn controller(frame_rate: u32) -> AdaptiveController {
+        let (_, receiver) = local_channel();
+
+        AdaptiveController::new(receiver, AIMDPolicy::new())
+            .initial_settings(StreamSettings {
+                bitrate: 1_000_000,
+                frame_rate,
+            })
+            .limit_frame_rate(true)
+    }
+
+    /// Whether each frame, arriving at the given milliseconds, fits in the frame rate
+    fn forwarded(controller: &mut AdaptiveController, arrivals: &[u64]) -> Vec<bool> {
+        let start = Instant::now();
+
+        arrivals
+            .iter()
+            .map(|millis| controller.within_frame_rate_at(start + Duration::from_millis(*millis)))
+            .collect()
+    }
+
+    #[test]
+    fn frames_faster_than_the_frame_rate_are_dropped() {
+        let mut controller = controller(10);
+
+        assert_eq!(
+            forwarded(&mut controller, &[0, 50, 100, 150, 200, 250]),
+            vec![true, false, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn slightly_early_frames_are_forwarded() {
+        let mut controller = controller(10);
+
+        assert_eq!(
+            forwarded(&mut controller, &[0, 95, 200, 285, 300]),
+            vec![true, true, true, false, true]
+        );
+    }
+
+    #[test]
+    fn idle_time_does_not_allow_bursts() {
+        let mut controller = controller(10);
+
+        assert_eq!(
+            forwarded(&mut controller, &[0, 5_000, 5_001, 5_002, 5_100]),
+            vec![true, true, false, false, true]
+        );
+    }
+
+    #[tokio::test]
+    async fn feedback_is_applied_to_the_following_frames() {
+        let (mut sender, receiver) = local_channel();
+        let mut controller =
+            AdaptiveController::new(receiver, AIMDPolicy::new()).initial_settings(StreamSettings {
+                bitrate: 1_000_000,
+                frame_rate: 30,
+            });
+
+        let frame_data = controller.process(FrameData::default()).await.unwrap();
+        assert_eq!(frame_data.get(TARGET_BITRATE_STAT), 1_000_000);
+        assert_eq!(frame_data.get(TARGET_FRAME_RATE_STAT), 30);
+
+        sender.send(FeedbackMessage::FrameDelay(500)).unwrap();
+        sender.send(FeedbackMessage::FrameDelay(500)).unwrap();
+
+        let frame_data = controller.process(FrameData::default()).await.unwrap();
+        assert_eq!(frame_data.get(TARGET_BITRATE_STAT), 722_500);
+        assert_eq!(frame_data.get_drop_reason(), None);
+
+        // A disconnected client leaves the settings as they are
+        drop(sender);
+        let frame_data = controller.process(FrameData::default()).await.unwrap();
+        assert_eq!(frame_data.get(TARGET_BITRATE_STAT), 722_500);
+    }
+
+    #[tokio::test]
+    async fn frames_over_the_limit_are_marked() {
+        let mut controller = controller(1);
+
+        let first = controller.process(FrameData::default()).await.unwrap();
+        assert_eq!(first.get_drop_reason(), None);
+
+        let second = controller.process(FrameData::default()).await.unwrap();
+        assert_eq!(second.get_drop_reason(), Some(DropReason::FrameRateLimit));
+    }
+}
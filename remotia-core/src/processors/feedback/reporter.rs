@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use log::debug;
+
+use crate::{
+    common::{
+        feedback::{transport::FeedbackSender, FeedbackMessage},
+        helpers::time::now_timestamp,
+    },
+    traits::FrameProcessor,
+    types::FrameData,
+};
+
+/// Client-side processor periodically reporting the average frame delay, the loss rate, the
+/// delivered frame rate and optionally the buffer level to the server.
+///
+/// It must be placed before any processor discarding the dropped frames, which are accounted
+/// as lost. The delay is measured against the "capture_timestamp" stat, so the clocks of the
+/// two peers are assumed to be synchronized.
+pub struct FeedbackReporter {
+    sender: Box<dyn FeedbackSender>,
+
+    timestamp_stat_id: String,
+    buffer_level_stat_id: Option<String>,
+
+    report_interval: Duration,
+    delay_threshold: Option<u128>,
+
+    interval_start: Instant,
+    delivered_frames: u128,
+    lost_frames: u128,
+    total_delay: u128,
+    high_delay_reported: bool,
+    buffer_level: Option<u128>,
+}
+
+impl FeedbackReporter {
+    pub fn new<S: FeedbackSender + 'static>(sender: S) -> Self {
+        Self {
+            sender: Box::new(sender),
+            timestamp_stat_id: "capture_timestamp".to_string(),
+            buffer_level_stat_id: None,
+            report_interval: Duration::from_secs(1),
+            delay_threshold: None,
+            interval_start: Instant::now(),
+            delivered_frames: 0,
+            lost_frames: 0,
+            total_delay: 0,
+            high_delay_reported: false,
+            buffer_level: None,
+        }
+    }
+
+    pub fn timestamp_stat(mut self, stat_id: &str) -> Self {
+        self.timestamp_stat_id = stat_id.to_string();
+        self
+    }
+
+    /// Stat holding the number of frames waiting to be rendered
+    pub fn buffer_level_stat(mut self, stat_id: &str) -> Self {
+        self.buffer_level_stat_id = Some(stat_id.to_string());
+        self
+    }
+
+    pub fn report_interval(mut self, report_interval: Duration) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+
+    /// Delay, in milliseconds, above which a frame is immediately reported, at most once per
+    /// report interval
+    pub fn delay_threshold(mut self, delay_threshold: u128) -> Self {
+        self.delay_threshold = Some(delay_threshold);
+        self
+    }
+
+    fn send(&mut self, message: FeedbackMessage) {
+        if let Err(error) = self.sender.send(message) {
+            debug!("Unable to send feedback: {}", error);
+        }
+    }
+
+    fn account(&mut self, frame_data: &FrameData) {
+        if frame_data.get_drop_reason().is_some() {
+            self.lost_frames += 1;
+            return;
+        }
+
+        if let Some(stat_id) = &self.buffer_level_stat_id {
+            self.buffer_level = frame_data.try_get(stat_id).ok().or(self.buffer_level);
+        }
+
+        let capture_timestamp = match frame_data.try_get(&self.timestamp_stat_id) {
+            Ok(capture_timestamp) => capture_timestamp,
+            Err(error) => {
+                debug!("Unable to measure the frame delay: {}", error);
+                return;
+            }
+        };
+
+        let delay = now_timestamp().saturating_sub(capture_timestamp);
+        self.delivered_frames += 1;
+        self.total_delay += delay;
+
+        match self.delay_threshold {
+            Some(threshold) if delay > threshold && !self.high_delay_reported => {
+                self.high_delay_reported = true;
+                self.send(FeedbackMessage::HighFrameDelay(delay));
+            }
+            _ => {}
+        }
+    }
+
+    fn report(&mut self) {
+        let elapsed = self.interval_start.elapsed();
+        let total_frames = self.delivered_frames + self.lost_frames;
+
+        if let Some(delay) = self.total_delay.checked_div(self.delivered_frames) {
+            self.send(FeedbackMessage::FrameDelay(delay));
+        }
+
+        if total_frames > 0 {
+            self.send(FeedbackMessage::LossRate(
+                self.lost_frames as f64 / total_frames as f64,
+            ));
+        }
+
+        self.send(FeedbackMessage::DecodedFrameRate(
+            self.delivered_frames as f64 / elapsed.as_secs_f64(),
+        ));
+
+        if let Some(buffer_level) = self.buffer_level {
+            self.send(FeedbackMessage::BufferLevel(buffer_level));
+        }
+
+        self.interval_start = Instant::now();
+        self.delivered_frames = 0;
+        self.lost_frames = 0;
+        self.total_delay = 0;
+        self.high_delay_reported = false;
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for FeedbackReporter {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        self.account(&frame_data);
+
+        if self.interval_start.elapsed() >= self.report_interval {
+            self.report();
+        }
+
+        Some(frame_data)
+    }
+}
@@ -56,7 +56,7 @@ impl FrameProcessor for TimestampBasedFrameReorderingBuffer {
             });
 
         // Check if it's possible to release a frame
-        let head = self.held_frames.get(0).unwrap();
+        let head = self.held_frames.front().unwrap();
         let head_diff = now_timestamp() - self.frame_stat(head);
         if head_diff >= self.delay {
             self.last_release_timestamp = frame_timestamp;
@@ -65,4 +65,9 @@ impl FrameProcessor for TimestampBasedFrameReorderingBuffer {
 
         None
     }
+
+    async fn flush(&mut self) -> Vec<FrameData> {
+        debug!("Releasing {} held frames", self.held_frames.len());
+        self.held_frames.drain(..).collect()
+    }
 }
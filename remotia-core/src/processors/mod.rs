@@ -7,3 +7,5 @@ pub mod pool_switch;
 pub mod key_check;
 pub mod clone_switch;
 pub mod debug;
+pub mod network;
+pub mod feedback;
@@ -0,0 +1,3 @@
+pub mod remvsp;
+pub mod srt;
+pub mod tcp;
@@ -0,0 +1,523 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use log::debug;
+use tokio::net::UdpSocket;
+
+use crate::{
+    common::network::remvsp::{RemVSPFrameFragment, RemVSPFrameHeader},
+    error::DropReason,
+    traits::FrameProcessor,
+    types::FrameData,
+};
+
+struct PartialFrame {
+    header: RemVSPFrameHeader,
+    fragments: Vec<Option<Vec<u8>>>,
+    received_fragments: usize,
+    first_reception: Instant,
+}
+
+impl PartialFrame {
+    fn new(header: RemVSPFrameHeader) -> Self {
+        Self {
+            header,
+            fragments: vec![None; header.frame_fragments_count as usize],
+            received_fragments: 0,
+            first_reception: Instant::now(),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.received_fragments == self.fragments.len()
+    }
+
+    fn assemble(self) -> Vec<u8> {
+        self.fragments.into_iter().flatten().flatten().collect()
+    }
+}
+
+/// Reassembles the frames sent by a [`super::sender::RemVSPFrameSender`]. Fragments may be
+/// received out of order or more than once. Each call pulls datagrams until a frame is
+/// complete, incomplete frames time out or no datagram is received for a whole frame timeout.
+///
+/// Timed out frames are reported together, with the timestamp of the oldest one and their
+/// number in the "timed_out_frames" stat. Fragments received later for them are ignored.
+/// Frames which do not fit an already allocated buffer are marked with
+/// [`DropReason::BufferOverflow`].
+pub struct RemVSPFrameReceiver {
+    socket: UdpSocket,
+
+    frame_timeout: Duration,
+
+    buffer_id: String,
+    size_stat_id: String,
+    timestamp_stat_id: String,
+    timed_out_frames_stat_id: String,
+
+    partial_frames: HashMap<u128, PartialFrame>,
+
+    /// Frames up to this timestamp have been delivered or given up
+    last_stale_timestamp: Option<u128>,
+
+    /// Frames which timed out after the last stale one, while an older frame is still pending
+    expired_timestamps: HashSet<u128>,
+
+    datagram_buffer: Vec<u8>,
+}
+
+impl RemVSPFrameReceiver {
+    pub fn new(socket: UdpSocket) -> Self {
+        Self {
+            socket,
+            frame_timeout: Duration::from_millis(100),
+            buffer_id: "encoded_frame_buffer".to_string(),
+            size_stat_id: "encoded_size".to_string(),
+            timestamp_stat_id: "capture_timestamp".to_string(),
+            timed_out_frames_stat_id: "timed_out_frames".to_string(),
+            partial_frames: HashMap::new(),
+            last_stale_timestamp: None,
+            expired_timestamps: HashSet::new(),
+            datagram_buffer: vec![0; u16::MAX as usize],
+        }
+    }
+
+    pub fn frame_timeout(mut self, frame_timeout: Duration) -> Self {
+        self.frame_timeout = frame_timeout;
+        self
+    }
+
+    /// Buffer in which the frame is written. The buffer is allocated if not already present
+    /// in the frame DTO, e.g. by a buffers pool.
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat in which the size of the received frame is stored
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = stat_id.to_string();
+        self
+    }
+
+    pub fn timestamp_stat(mut self, stat_id: &str) -> Self {
+        self.timestamp_stat_id = stat_id.to_string();
+        self
+    }
+
+    /// Stat in which the number of frames reported as timed out is stored
+    pub fn timed_out_frames_stat(mut self, stat_id: &str) -> Self {
+        self.timed_out_frames_stat_id = stat_id.to_string();
+        self
+    }
+
+    fn is_stale(&self, capture_timestamp: u128) -> bool {
+        matches!(self.last_stale_timestamp, Some(last) if capture_timestamp <= last)
+            || self.expired_timestamps.contains(&capture_timestamp)
+    }
+
+    /// Removes all the incomplete frames which exceeded the timeout, returning their timestamps
+    /// in ascending order
+    fn pop_expired_frames(&mut self) -> Vec<u128> {
+        let mut expired_timestamps: Vec<u128> = self
+            .partial_frames
+            .iter()
+            .filter(|(_, frame)| frame.first_reception.elapsed() >= self.frame_timeout)
+            .map(|(timestamp, _)| *timestamp)
+            .collect();
+
+        expired_timestamps.sort_unstable();
+
+        for timestamp in &expired_timestamps {
+            self.partial_frames.remove(timestamp);
+            self.expired_timestamps.insert(*timestamp);
+        }
+
+        self.prune_expired_timestamps();
+
+        expired_timestamps
+    }
+
+    /// Treats as stale the expired frames which are older than every pending frame, so that
+    /// only the ones a pending frame may still precede have to be remembered
+    fn prune_expired_timestamps(&mut self) {
+        let oldest_pending = self.partial_frames.keys().min().copied();
+        let newest_prunable = self
+            .expired_timestamps
+            .iter()
+            .filter(|timestamp| oldest_pending.is_none_or(|oldest| **timestamp < oldest))
+            .max()
+            .copied();
+
+        if let Some(newest_prunable) = newest_prunable {
+            self.expired_timestamps
+                .retain(|timestamp| *timestamp > newest_prunable);
+            self.last_stale_timestamp = self.last_stale_timestamp.max(Some(newest_prunable));
+        }
+    }
+
+    /// Stores a fragment and returns the timestamp of its frame if it is now complete
+    fn store_fragment(&mut self, fragment: RemVSPFrameFragment) -> Result<Option<u128>, DropReason> {
+        let header = fragment.frame_header;
+        let fragments_count = header.frame_fragments_count as usize;
+        let fragment_id = fragment.fragment_id as usize;
+
+        if fragments_count == 0
+            || fragment_id >= fragments_count
+            || fragment.data.len() > header.fragment_size as usize
+        {
+            return Err(DropReason::InvalidPacketHeader);
+        }
+
+        if self.is_stale(header.capture_timestamp) {
+            debug!("Ignoring fragment of stale frame {}", header.capture_timestamp);
+            return Ok(None);
+        }
+
+        let partial_frame = self
+            .partial_frames
+            .entry(header.capture_timestamp)
+            .or_insert_with(|| PartialFrame::new(header));
+
+        if partial_frame.header.frame_fragments_count != header.frame_fragments_count {
+            return Err(DropReason::InvalidPacketHeader);
+        }
+
+        let slot = &mut partial_frame.fragments[fragment_id];
+        if slot.is_some() {
+            debug!("Ignoring duplicate fragment {} of frame {}", fragment_id, header.capture_timestamp);
+            return Ok(None);
+        }
+
+        *slot = Some(fragment.data);
+        partial_frame.received_fragments += 1;
+
+        if partial_frame.is_complete() {
+            Ok(Some(header.capture_timestamp))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn deliver(&mut self, capture_timestamp: u128, frame_data: &mut FrameData) {
+        let partial_frame = self.partial_frames.remove(&capture_timestamp).unwrap();
+        let frame = partial_frame.assemble();
+
+        // Older incomplete frames cannot be rendered anymore
+        self.partial_frames
+            .retain(|timestamp, _| *timestamp > capture_timestamp);
+        self.expired_timestamps
+            .retain(|timestamp| *timestamp > capture_timestamp);
+        self.last_stale_timestamp = Some(capture_timestamp);
+
+        debug!("Received frame {} ({} bytes)", capture_timestamp, frame.len());
+
+        match frame_data.get_writable_buffer_ref(&self.buffer_id) {
+            Some(buffer) if buffer.len() >= frame.len() => {
+                buffer[..frame.len()].copy_from_slice(&frame);
+            }
+            Some(_) => {
+                debug!("Frame {} exceeds the '{}' buffer", capture_timestamp, self.buffer_id);
+                frame_data.set_drop_reason(Some(DropReason::BufferOverflow));
+            }
+            None => {
+                frame_data.insert_writable_buffer(&self.buffer_id, BytesMut::from(&frame[..]));
+            }
+        }
+
+        frame_data.set(&self.timestamp_stat_id, capture_timestamp);
+        frame_data.set(&self.size_stat_id, frame.len() as u128);
+    }
+
+    async fn receive_frame(&mut self, frame_data: &mut FrameData) {
+        loop {
+            let expired_timestamps = self.pop_expired_frames();
+            if let Some(oldest_timestamp) = expired_timestamps.first() {
+                debug!("Frames {:?} timed out", expired_timestamps);
+                frame_data.set(&self.timestamp_stat_id, *oldest_timestamp);
+                frame_data.set(
+                    &self.timed_out_frames_stat_id,
+                    expired_timestamps.len() as u128,
+                );
+                frame_data.set_drop_reason(Some(DropReason::Timeout));
+                return;
+            }
+
+            let received = tokio::time::timeout(
+                self.frame_timeout,
+                self.socket.recv_from(&mut self.datagram_buffer),
+            )
+            .await;
+
+            let datagram_size = match received {
+                Ok(Ok((datagram_size, _))) => datagram_size,
+                Ok(Err(error)) => {
+                    debug!("Socket error: {}", error);
+                    frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+                    return;
+                }
+                Err(_) => {
+                    if self.partial_frames.is_empty() {
+                        frame_data.set_drop_reason(Some(DropReason::NoCompleteFrames));
+                        return;
+                    }
+
+                    continue;
+                }
+            };
+
+            let fragment: RemVSPFrameFragment =
+                match bincode::deserialize(&self.datagram_buffer[..datagram_size]) {
+                    Ok(fragment) => fragment,
+                    Err(error) => {
+                        debug!("Invalid datagram: {}", error);
+                        frame_data.set_drop_reason(Some(DropReason::InvalidPacket));
+                        return;
+                    }
+                };
+
+            match self.store_fragment(fragment) {
+                Ok(Some(capture_timestamp)) => {
+                    self.deliver(capture_timestamp, frame_data);
+                    return;
+                }
+                Ok(None) => {}
+                Err(drop_reason) => {
+                    frame_data.set_drop_reason(Some(drop_reason));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for RemVSPFrameReceiver {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        self.receive_frame(&mut frame_data).await;
+        Some(frame_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::BytesMut;
+    use tokio::net::UdpSocket;
+
+    use super::RemVSPFrameReceiver;
+    use crate::{
+        common::network::remvsp::{RemVSPFrameFragment, RemVSPFrameHeader},
+        error::DropReason,
+        processors::network::remvsp::sender::RemVSPFrameSender,
+        traits::FrameProcessor,
+        types::FrameData,
+    };
+
+    const FRAGMENT_SIZE: usize = 4;
+
+    async fn loopback() -> (UdpSocket, RemVSPFrameReceiver) {
+        let sender_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let receiver_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender_socket
+            .connect(receiver_socket.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let receiver =
+            RemVSPFrameReceiver::new(receiver_socket).frame_timeout(Duration::from_millis(50));
+
+        (sender_socket, receiver)
+    }
+
+    fn fragments(capture_timestamp: u128, frame: &[u8]) -> Vec<Vec<u8>> {
+        let chunks: Vec<&[u8]> = frame.chunks(FRAGMENT_SIZE).collect();
+        let frame_header = RemVSPFrameHeader {
+            frame_fragments_count: chunks.len() as u16,
+            fragment_size: FRAGMENT_SIZE as u16,
+            capture_timestamp,
+        };
+
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(fragment_id, data)| {
+                bincode::serialize(&RemVSPFrameFragment {
+                    frame_header,
+                    fragment_id: fragment_id as u16,
+                    data: data.to_vec(),
+                })
+                .unwrap()
+            })
+            .collect()
+    }
+
+    async fn send_all(socket: &UdpSocket, datagrams: &[&Vec<u8>]) {
+        for datagram in datagrams {
+            socket.send(datagram).await.unwrap();
+        }
+    }
+
+    async fn receive(receiver: &mut RemVSPFrameReceiver) -> FrameData {
+        receiver.process(FrameData::default()).await.unwrap()
+    }
+
+    fn received_frame(frame_data: &mut FrameData) -> Vec<u8> {
+        let size = frame_data.get("encoded_size") as usize;
+        frame_data
+            .extract_writable_buffer("encoded_frame_buffer")
+            .unwrap()[..size]
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn sent_frames_are_received() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let (_, mut receiver) = loopback().await;
+        let peer_address = receiver.socket.local_addr().unwrap();
+        let mut sender = RemVSPFrameSender::new(socket, peer_address).mtu(128);
+
+        let frame: Vec<u8> = (0..=255).collect();
+        let mut frame_data = FrameData::default();
+        frame_data.set("capture_timestamp", 7);
+        frame_data.insert_writable_buffer("encoded_frame_buffer", BytesMut::from(&frame[..]));
+        sender.process(frame_data).await.unwrap();
+
+        let mut frame_data = receive(&mut receiver).await;
+        assert_eq!(frame_data.get_drop_reason(), None);
+        assert_eq!(frame_data.get("capture_timestamp"), 7);
+        assert_eq!(received_frame(&mut frame_data), frame);
+    }
+
+    #[tokio::test]
+    async fn reordered_and_duplicate_fragments_are_reassembled() {
+        let (socket, mut receiver) = loopback().await;
+
+        let frame: Vec<u8> = (0..10).collect();
+        let datagrams = fragments(1, &frame);
+        send_all(
+            &socket,
+            &[&datagrams[2], &datagrams[0], &datagrams[2], &datagrams[1]],
+        )
+        .await;
+
+        let mut frame_data = receive(&mut receiver).await;
+        assert_eq!(frame_data.get_drop_reason(), None);
+        assert_eq!(frame_data.get("capture_timestamp"), 1);
+        assert_eq!(received_frame(&mut frame_data), frame);
+    }
+
+    #[tokio::test]
+    async fn interleaved_frames_are_reassembled() {
+        let (socket, mut receiver) = loopback().await;
+
+        let first_frame = vec![1; 8];
+        let second_frame = vec![2; 8];
+        let first = fragments(1, &first_frame);
+        let second = fragments(2, &second_frame);
+        send_all(&socket, &[&first[1], &second[0], &first[0], &second[1]]).await;
+
+        let mut frame_data = receive(&mut receiver).await;
+        assert_eq!(frame_data.get("capture_timestamp"), 1);
+        assert_eq!(received_frame(&mut frame_data), first_frame);
+
+        let mut frame_data = receive(&mut receiver).await;
+        assert_eq!(frame_data.get("capture_timestamp"), 2);
+        assert_eq!(received_frame(&mut frame_data), second_frame);
+    }
+
+    #[tokio::test]
+    async fn frames_with_lost_fragments_time_out_together() {
+        let (socket, mut receiver) = loopback().await;
+
+        let first = fragments(1, &[1; 8]);
+        let second = fragments(2, &[2; 8]);
+        send_all(&socket, &[&first[0], &second[1]]).await;
+
+        let frame_data = receive(&mut receiver).await;
+        assert_eq!(frame_data.get_drop_reason(), Some(DropReason::Timeout));
+        assert_eq!(frame_data.get("capture_timestamp"), 1);
+        assert_eq!(frame_data.get("timed_out_frames"), 2);
+
+        let frame_data = receive(&mut receiver).await;
+        assert_eq!(
+            frame_data.get_drop_reason(),
+            Some(DropReason::NoCompleteFrames)
+        );
+    }
+
+    #[tokio::test]
+    async fn late_fragments_of_timed_out_frames_are_ignored() {
+        let (socket, mut receiver) = loopback().await;
+
+        let frame = vec![3; 8];
+        let datagrams = fragments(1, &frame);
+        send_all(&socket, &[&datagrams[0]]).await;
+
+        let frame_data = receive(&mut receiver).await;
+        assert_eq!(frame_data.get_drop_reason(), Some(DropReason::Timeout));
+
+        // The missing fragment would complete the frame, which has already been given up
+        send_all(&socket, &[&datagrams[1]]).await;
+
+        let frame_data = receive(&mut receiver).await;
+        assert_eq!(
+            frame_data.get_drop_reason(),
+            Some(DropReason::NoCompleteFrames)
+        );
+        assert!(!frame_data.has("capture_timestamp"));
+    }
+
+    #[tokio::test]
+    async fn expired_frames_older_than_any_pending_one_are_forgotten() {
+        let (socket, receiver) = loopback().await;
+        let mut receiver = receiver.timed_out_frames_stat("lost_frames");
+
+        for capture_timestamp in 1..=3 {
+            let datagrams = fragments(capture_timestamp, &[4; 8]);
+            send_all(&socket, &[&datagrams[0]]).await;
+
+            let frame_data = receive(&mut receiver).await;
+            assert_eq!(frame_data.get_drop_reason(), Some(DropReason::Timeout));
+            assert_eq!(frame_data.get("capture_timestamp"), capture_timestamp);
+            assert_eq!(frame_data.get("lost_frames"), 1);
+            assert!(!frame_data.has("timed_out_frames"));
+
+            assert!(receiver.expired_timestamps.is_empty());
+            assert_eq!(receiver.last_stale_timestamp, Some(capture_timestamp));
+        }
+
+        // Older frames are stale too, even if none of their fragments was ever received
+        let datagrams = fragments(2, &[5; 4]);
+        send_all(&socket, &[&datagrams[0]]).await;
+
+        let frame_data = receive(&mut receiver).await;
+        assert_eq!(
+            frame_data.get_drop_reason(),
+            Some(DropReason::NoCompleteFrames)
+        );
+    }
+
+    #[tokio::test]
+    async fn frames_larger_than_the_buffer_overflow() {
+        let (socket, mut receiver) = loopback().await;
+
+        let datagrams = fragments(1, &[6; 8]);
+        send_all(&socket, &[&datagrams[0], &datagrams[1]]).await;
+
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer("encoded_frame_buffer", BytesMut::from(&[0; 4][..]));
+        let frame_data = receiver.process(frame_data).await.unwrap();
+
+        assert_eq!(
+            frame_data.get_drop_reason(),
+            Some(DropReason::BufferOverflow)
+        );
+        assert_eq!(frame_data.get("encoded_size"), 8);
+    }
+}
@@ -0,0 +1,149 @@
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use log::debug;
+use tokio::net::UdpSocket;
+
+use crate::{
+    common::network::remvsp::{RemVSPFrameFragment, RemVSPFrameHeader},
+    error::ProcessorError,
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+/// Splits a frame buffer into RemVSP fragments which fit into a single UDP datagram
+pub struct RemVSPFrameSender {
+    socket: UdpSocket,
+    peer_address: SocketAddr,
+
+    mtu: usize,
+
+    buffer_id: String,
+    size_stat_id: Option<String>,
+    timestamp_stat_id: String,
+}
+
+impl RemVSPFrameSender {
+    pub fn new(socket: UdpSocket, peer_address: SocketAddr) -> Self {
+        Self {
+            socket,
+            peer_address,
+            mtu: 1400,
+            buffer_id: "encoded_frame_buffer".to_string(),
+            size_stat_id: None,
+            timestamp_stat_id: "capture_timestamp".to_string(),
+        }
+    }
+
+    /// Maximum size of each datagram, including the fragment header
+    pub fn mtu(mut self, mtu: usize) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat holding the amount of meaningful bytes of the buffer. The whole buffer is sent if
+    /// not set.
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = Some(stat_id.to_string());
+        self
+    }
+
+    pub fn timestamp_stat(mut self, stat_id: &str) -> Self {
+        self.timestamp_stat_id = stat_id.to_string();
+        self
+    }
+
+    fn fragment_size(&self) -> Result<usize, ProcessorError> {
+        let empty_fragment = RemVSPFrameFragment {
+            frame_header: RemVSPFrameHeader {
+                frame_fragments_count: 0,
+                fragment_size: 0,
+                capture_timestamp: 0,
+            },
+            fragment_id: 0,
+            data: Vec::new(),
+        };
+
+        let header_size = bincode::serialized_size(&empty_fragment).unwrap() as usize;
+
+        if self.mtu <= header_size {
+            return Err(ProcessorError::Other(format!(
+                "MTU {} is too small for the {} bytes fragment header",
+                self.mtu, header_size
+            )));
+        }
+
+        Ok((self.mtu - header_size).min(u16::MAX as usize))
+    }
+
+    async fn send_frame(&self, frame_data: &mut FrameData) -> Result<(), ProcessorError> {
+        let capture_timestamp = frame_data.try_get(&self.timestamp_stat_id)?;
+
+        let frame_size = match &self.size_stat_id {
+            Some(size_stat_id) => Some(frame_data.try_get(size_stat_id)? as usize),
+            None => None,
+        };
+
+        let buffer = frame_data
+            .get_writable_buffer_ref(&self.buffer_id)
+            .ok_or_else(|| ProcessorError::MissingBuffer(self.buffer_id.clone()))?;
+
+        let frame_size = frame_size.unwrap_or(buffer.len());
+
+        let payload = buffer.get(..frame_size).ok_or_else(|| {
+            ProcessorError::Other(format!("Frame size {} exceeds the buffer", frame_size))
+        })?;
+
+        let fragment_size = self.fragment_size()?;
+        let fragments_count = frame_size.div_ceil(fragment_size).max(1);
+
+        if fragments_count > u16::MAX as usize {
+            return Err(ProcessorError::Other(format!(
+                "Frame of {} bytes needs too many fragments ({})",
+                frame_size, fragments_count
+            )));
+        }
+
+        let frame_header = RemVSPFrameHeader {
+            frame_fragments_count: fragments_count as u16,
+            fragment_size: fragment_size as u16,
+            capture_timestamp,
+        };
+
+        debug!(
+            "Sending frame {} ({} bytes, {} fragments)",
+            capture_timestamp, frame_size, fragments_count
+        );
+
+        for fragment_id in 0..fragments_count {
+            let start = fragment_id * fragment_size;
+            let end = (start + fragment_size).min(frame_size);
+
+            let fragment = RemVSPFrameFragment {
+                frame_header,
+                fragment_id: fragment_id as u16,
+                data: payload[start..end].to_vec(),
+            };
+
+            let datagram = bincode::serialize(&fragment).unwrap();
+            self.socket.send_to(&datagram, self.peer_address).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for RemVSPFrameSender {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        match self.send_frame(&mut frame_data).await {
+            Ok(()) => Ok(Some(frame_data)),
+            Err(error) => Err((frame_data, error)),
+        }
+    }
+}
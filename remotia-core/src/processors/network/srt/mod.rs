@@ -0,0 +1,129 @@
+use std::{io, panic::AssertUnwindSafe, time::Duration};
+
+use futures::FutureExt;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use srt_tokio::{options::ByteCount, SrtSocket};
+
+pub mod receiver;
+pub mod sender;
+
+/// Live mode SRT messages cannot exceed a single packet payload (1316 bytes), hence frames
+/// are split in chunks of this size, leaving room for the chunk header
+pub(crate) const SRT_CHUNK_SIZE: usize = 1280;
+
+const SRT_BUFFER_SIZE: u64 = 8192 * 1500;
+const UDP_BUFFER_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Portion of a serialized frame transmitted as a single SRT message
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SRTFrameChunk {
+    pub frame_id: u32,
+    pub chunk_id: u32,
+    pub chunks_count: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SRTEndpoint {
+    /// Connect to a listener at the given address
+    Caller(String),
+
+    /// Wait for a caller on the given local port
+    Listener(u16),
+}
+
+/// Lazily (re)established SRT connection shared by the sender and the receiver
+pub(crate) struct SRTConnection {
+    endpoint: SRTEndpoint,
+
+    latency: Duration,
+    passphrase: Option<String>,
+    stream_id: Option<String>,
+    connection_timeout: Duration,
+
+    socket: Option<SrtSocket>,
+}
+
+impl SRTConnection {
+    pub(crate) fn new(endpoint: SRTEndpoint) -> Self {
+        Self {
+            endpoint,
+            latency: Duration::from_millis(120),
+            passphrase: None,
+            stream_id: None,
+            connection_timeout: Duration::from_secs(1),
+            socket: None,
+        }
+    }
+
+    pub(crate) fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
+
+    pub(crate) fn set_passphrase(&mut self, passphrase: &str) {
+        self.passphrase = Some(passphrase.to_string());
+    }
+
+    pub(crate) fn set_stream_id(&mut self, stream_id: &str) {
+        self.stream_id = Some(stream_id.to_string());
+    }
+
+    pub(crate) fn set_connection_timeout(&mut self, connection_timeout: Duration) {
+        self.connection_timeout = connection_timeout;
+    }
+
+    /// Returns the connected socket, connecting first if needed
+    pub(crate) async fn socket(&mut self) -> io::Result<&mut SrtSocket> {
+        if self.socket.is_none() {
+            let socket = tokio::time::timeout(self.connection_timeout, self.connect())
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "SRT connection timeout"))??;
+
+            info!("SRT connection established ({:?})", self.endpoint);
+            self.socket = Some(socket);
+        }
+
+        Ok(self.socket.as_mut().unwrap())
+    }
+
+    /// Drops the current socket, so that the next frame triggers a reconnection
+    pub(crate) fn disconnect(&mut self) {
+        if self.socket.take().is_some() {
+            debug!("SRT connection lost ({:?})", self.endpoint);
+        }
+    }
+
+    async fn connect(&self) -> io::Result<SrtSocket> {
+        // Default buffers (~35 packets for SRT, 64KB for UDP) cannot hold a single large frame,
+        // making the sender discard the oldest chunks
+        let mut builder = SrtSocket::builder().latency(self.latency).set(|options| {
+            options.sender.buffer_size = ByteCount(SRT_BUFFER_SIZE);
+            options.connect.udp_send_buffer_size = ByteCount(UDP_BUFFER_SIZE);
+            options.connect.udp_recv_buffer_size = ByteCount(UDP_BUFFER_SIZE);
+        });
+
+        if let Some(passphrase) = &self.passphrase {
+            builder = builder.encryption(16, passphrase.as_str());
+        }
+
+        match &self.endpoint {
+            SRTEndpoint::Caller(address) => {
+                builder
+                    .call(address.as_str(), self.stream_id.as_deref())
+                    .await
+            }
+            // srt-tokio panics instead of failing when it rejects a caller, e.g. on a
+            // passphrase mismatch
+            SRTEndpoint::Listener(port) => AssertUnwindSafe(builder.listen_on(*port))
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| {
+                    Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "SRT caller rejected",
+                    ))
+                }),
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use log::debug;
+
+use crate::{
+    common::network::serialized::SerializedFrameData, error::DropReason, traits::FrameProcessor,
+    types::FrameData,
+};
+
+use super::{SRTConnection, SRTEndpoint, SRTFrameChunk};
+
+/// Frame whose chunks are being received. SRT delivers messages in order, so a frame is
+/// discarded as soon as one of its chunks is missing.
+struct PartialFrame {
+    frame_id: u32,
+    chunks_count: u32,
+    next_chunk_id: u32,
+    data: Vec<u8>,
+}
+
+/// Receives the frames sent by a [`super::sender::SRTFrameSender`], restoring both the buffer
+/// and the stats. Frames are marked with [`DropReason::ConnectionError`] while the link is down.
+pub struct SRTFrameReceiver {
+    connection: SRTConnection,
+
+    receive_timeout: Duration,
+
+    partial_frame: Option<PartialFrame>,
+}
+
+impl SRTFrameReceiver {
+    fn new(endpoint: SRTEndpoint) -> Self {
+        Self {
+            connection: SRTConnection::new(endpoint),
+            receive_timeout: Duration::from_secs(1),
+            partial_frame: None,
+        }
+    }
+
+    pub fn caller(remote_address: &str) -> Self {
+        Self::new(SRTEndpoint::Caller(remote_address.to_string()))
+    }
+
+    pub fn listener(port: u16) -> Self {
+        Self::new(SRTEndpoint::Listener(port))
+    }
+
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.connection.set_latency(latency);
+        self
+    }
+
+    /// Enables AES-128 encryption. The passphrase must be 10 to 79 characters long.
+    pub fn passphrase(mut self, passphrase: &str) -> Self {
+        self.connection.set_passphrase(passphrase);
+        self
+    }
+
+    pub fn stream_id(mut self, stream_id: &str) -> Self {
+        self.connection.set_stream_id(stream_id);
+        self
+    }
+
+    pub fn connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection.set_connection_timeout(connection_timeout);
+        self
+    }
+
+    /// Maximum time to wait for a frame on an established connection
+    pub fn receive_timeout(mut self, receive_timeout: Duration) -> Self {
+        self.receive_timeout = receive_timeout;
+        self
+    }
+
+    async fn receive_chunk(&mut self, deadline: Instant) -> Result<SRTFrameChunk, DropReason> {
+        let socket = match self.connection.socket().await {
+            Ok(socket) => socket,
+            Err(error) => {
+                debug!("Unable to connect: {}", error);
+                return Err(DropReason::ConnectionError);
+            }
+        };
+
+        let timeout = deadline.saturating_duration_since(Instant::now());
+
+        let message = match tokio::time::timeout(timeout, socket.try_next()).await {
+            Ok(Ok(Some((_, message)))) => message,
+            Ok(Ok(None)) | Ok(Err(_)) => {
+                self.partial_frame = None;
+                self.connection.disconnect();
+                return Err(DropReason::ConnectionError);
+            }
+            Err(_) => return Err(DropReason::Timeout),
+        };
+
+        bincode::deserialize(&message).map_err(|error| {
+            debug!("Invalid chunk: {}", error);
+            DropReason::InvalidPacket
+        })
+    }
+
+    /// Stores a chunk and returns the whole serialized frame if it is now complete
+    fn store_chunk(&mut self, chunk: SRTFrameChunk) -> Option<Vec<u8>> {
+        if chunk.chunk_id == 0 {
+            if let Some(partial_frame) = self.partial_frame.take() {
+                debug!("Discarding incomplete frame {}", partial_frame.frame_id);
+            }
+
+            self.partial_frame = Some(PartialFrame {
+                frame_id: chunk.frame_id,
+                chunks_count: chunk.chunks_count,
+                next_chunk_id: 0,
+                data: Vec::new(),
+            });
+        }
+
+        let partial_frame = self.partial_frame.as_mut()?;
+
+        if partial_frame.frame_id != chunk.frame_id
+            || partial_frame.chunks_count != chunk.chunks_count
+            || partial_frame.next_chunk_id != chunk.chunk_id
+        {
+            debug!("Missing chunks of frame {}", partial_frame.frame_id);
+            self.partial_frame = None;
+            return None;
+        }
+
+        partial_frame.data.extend_from_slice(&chunk.data);
+        partial_frame.next_chunk_id += 1;
+
+        if partial_frame.next_chunk_id == partial_frame.chunks_count {
+            self.partial_frame.take().map(|partial_frame| partial_frame.data)
+        } else {
+            None
+        }
+    }
+
+    async fn receive_message(&mut self) -> Result<Vec<u8>, DropReason> {
+        let deadline = Instant::now() + self.receive_timeout;
+
+        loop {
+            let chunk = self.receive_chunk(deadline).await?;
+
+            if let Some(message) = self.store_chunk(chunk) {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for SRTFrameReceiver {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let message = match self.receive_message().await {
+            Ok(message) => message,
+            Err(drop_reason) => {
+                frame_data.set_drop_reason(Some(drop_reason));
+                return Some(frame_data);
+            }
+        };
+
+        let drop_reason = match bincode::deserialize::<SerializedFrameData>(&message) {
+            Ok(serialized_frame_data) => match serialized_frame_data.restore(&mut frame_data) {
+                Ok(()) => None,
+                Err(key) => {
+                    debug!("Received '{}' buffer does not fit", key);
+                    Some(DropReason::BufferOverflow)
+                }
+            },
+            Err(error) => {
+                debug!("Invalid message: {}", error);
+                Some(DropReason::InvalidPacket)
+            }
+        };
+
+        frame_data.set_drop_reason(drop_reason);
+
+        Some(frame_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::UdpSocket,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use bytes::BytesMut;
+
+    use super::SRTFrameReceiver;
+    use crate::{
+        error::DropReason,
+        processors::network::srt::{sender::SRTFrameSender, SRT_CHUNK_SIZE},
+        traits::FrameProcessor,
+        types::FrameData,
+    };
+
+    const PASSPHRASE: &str = "loopback passphrase";
+
+    fn free_port() -> u16 {
+        UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn listener(port: u16) -> SRTFrameReceiver {
+        SRTFrameReceiver::listener(port)
+            .latency(Duration::from_millis(20))
+            .connection_timeout(Duration::from_millis(500))
+            .receive_timeout(Duration::from_millis(500))
+    }
+
+    fn caller(port: u16) -> SRTFrameSender {
+        SRTFrameSender::caller(&format!("127.0.0.1:{}", port))
+            .latency(Duration::from_millis(20))
+            .connection_timeout(Duration::from_millis(500))
+    }
+
+    fn frame(capture_timestamp: u128, size: usize) -> FrameData {
+        let payload: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+
+        let mut frame_data = FrameData::default();
+        frame_data.set("capture_timestamp", capture_timestamp);
+        frame_data.insert_writable_buffer("encoded_frame_buffer", BytesMut::from(&payload[..]));
+        frame_data
+    }
+
+    /// Keeps receiving until a frame is delivered or the attempts are over
+    async fn receive(receiver: &mut SRTFrameReceiver, attempts: usize) -> FrameData {
+        let mut frame_data = FrameData::default();
+        for _ in 0..attempts {
+            frame_data = receiver.process(FrameData::default()).await.unwrap();
+            if frame_data.get_drop_reason().is_none() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        frame_data
+    }
+
+    #[tokio::test]
+    async fn frames_larger_than_a_chunk_are_reassembled() {
+        let port = free_port();
+        let mut receiver = listener(port);
+        let mut sender = caller(port);
+
+        let size = SRT_CHUNK_SIZE * 3 + 17;
+        assert_eq!(sender.split(&vec![0; size]).len(), 4);
+
+        let receiving = tokio::spawn(async move { receive(&mut receiver, 5).await });
+        let sent = sender.process(frame(42, size)).await.unwrap();
+        assert_eq!(sent.get_drop_reason(), None);
+
+        let mut received = receiving.await.unwrap();
+        let expected = frame(42, size)
+            .extract_writable_buffer("encoded_frame_buffer")
+            .unwrap();
+
+        assert_eq!(received.get_drop_reason(), None);
+        assert_eq!(received.get("capture_timestamp"), 42);
+        assert_eq!(
+            received.extract_writable_buffer("encoded_frame_buffer"),
+            Some(expected)
+        );
+    }
+
+    #[tokio::test]
+    async fn sender_reconnects_after_the_receiver_restarts() {
+        let port = free_port();
+        let mut sender = caller(port);
+
+        let mut receiver = listener(port);
+        let receiving = tokio::spawn(async move {
+            let frame_data = receive(&mut receiver, 5).await;
+            (receiver, frame_data)
+        });
+        sender.process(frame(1, 64)).await.unwrap();
+        let (receiver, received) = receiving.await.unwrap();
+        assert_eq!(received.get("capture_timestamp"), 1);
+
+        drop(receiver);
+
+        let mut receiver = listener(port);
+        let finished = Arc::new(AtomicBool::new(false));
+        let receiving = tokio::spawn({
+            let finished = finished.clone();
+            async move {
+                let frame_data = receive(&mut receiver, 100).await;
+                finished.store(true, Ordering::SeqCst);
+                frame_data
+            }
+        });
+
+        // Frames are dropped until the sender notices the restart and connects again
+        let mut capture_timestamp = 2;
+        while !finished.load(Ordering::SeqCst) && capture_timestamp < 200 {
+            sender.process(frame(capture_timestamp, 64)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            capture_timestamp += 1;
+        }
+
+        let received = receiving.await.unwrap();
+        assert_eq!(received.get_drop_reason(), None);
+        assert!(received.get("capture_timestamp") > 1);
+    }
+
+    #[tokio::test]
+    async fn wrong_passphrases_are_rejected() {
+        let port = free_port();
+        let mut receiver = listener(port).passphrase(PASSPHRASE);
+        let mut sender = caller(port).passphrase("not the loopback passphrase");
+
+        let receiving =
+            tokio::spawn(async move { receiver.process(FrameData::default()).await.unwrap() });
+        let sent = sender.process(frame(1, 64)).await.unwrap();
+        let received = receiving.await.unwrap();
+
+        assert_eq!(sent.get_drop_reason(), Some(DropReason::ConnectionError));
+        assert_eq!(
+            received.get_drop_reason(),
+            Some(DropReason::ConnectionError)
+        );
+        assert!(!received.has("capture_timestamp"));
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::SinkExt;
+use log::debug;
+
+use crate::{
+    common::network::serialized::SerializedFrameData,
+    error::{DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use super::{SRTConnection, SRTEndpoint, SRTFrameChunk, SRT_CHUNK_SIZE};
+
+/// Sends a frame buffer together with all the stats of the frame, split in as many SRT
+/// messages as needed. Frames are marked with [`DropReason::ConnectionError`] while the link is down.
+pub struct SRTFrameSender {
+    connection: SRTConnection,
+
+    buffer_id: String,
+    size_stat_id: Option<String>,
+
+    next_frame_id: u32,
+}
+
+impl SRTFrameSender {
+    fn new(endpoint: SRTEndpoint) -> Self {
+        Self {
+            connection: SRTConnection::new(endpoint),
+            buffer_id: "encoded_frame_buffer".to_string(),
+            size_stat_id: None,
+            next_frame_id: 0,
+        }
+    }
+
+    pub fn caller(remote_address: &str) -> Self {
+        Self::new(SRTEndpoint::Caller(remote_address.to_string()))
+    }
+
+    pub fn listener(port: u16) -> Self {
+        Self::new(SRTEndpoint::Listener(port))
+    }
+
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.connection.set_latency(latency);
+        self
+    }
+
+    /// Enables AES-128 encryption. The passphrase must be 10 to 79 characters long.
+    pub fn passphrase(mut self, passphrase: &str) -> Self {
+        self.connection.set_passphrase(passphrase);
+        self
+    }
+
+    pub fn stream_id(mut self, stream_id: &str) -> Self {
+        self.connection.set_stream_id(stream_id);
+        self
+    }
+
+    pub fn connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection.set_connection_timeout(connection_timeout);
+        self
+    }
+
+    pub fn buffer_id(mut self, buffer_id: &str) -> Self {
+        self.buffer_id = buffer_id.to_string();
+        self
+    }
+
+    /// Stat holding the amount of meaningful bytes of the buffer. The whole buffer is sent if
+    /// not set.
+    pub fn size_stat(mut self, stat_id: &str) -> Self {
+        self.size_stat_id = Some(stat_id.to_string());
+        self
+    }
+
+    fn serialize(&self, frame_data: &mut FrameData) -> Result<Vec<u8>, ProcessorError> {
+        let mut serialized_frame_data = SerializedFrameData::new(frame_data.get_stats().clone());
+
+        let frame_size = match &self.size_stat_id {
+            Some(size_stat_id) => Some(frame_data.try_get(size_stat_id)? as usize),
+            None => None,
+        };
+
+        let buffer = frame_data
+            .get_writable_buffer_ref(&self.buffer_id)
+            .ok_or_else(|| ProcessorError::MissingBuffer(self.buffer_id.clone()))?;
+
+        let frame_size = frame_size.unwrap_or(buffer.len());
+
+        let payload = buffer.get(..frame_size).ok_or_else(|| {
+            ProcessorError::Other(format!("Frame size {} exceeds the buffer", frame_size))
+        })?;
+        serialized_frame_data.insert_buffer(&self.buffer_id, payload);
+
+        Ok(bincode::serialize(&serialized_frame_data).unwrap())
+    }
+
+    pub(super) fn split(&mut self, message: &[u8]) -> Vec<Bytes> {
+        let frame_id = self.next_frame_id;
+        self.next_frame_id = self.next_frame_id.wrapping_add(1);
+
+        let chunks_count = message.len().div_ceil(SRT_CHUNK_SIZE).max(1) as u32;
+
+        (0..chunks_count)
+            .map(|chunk_id| {
+                let start = chunk_id as usize * SRT_CHUNK_SIZE;
+                let end = (start + SRT_CHUNK_SIZE).min(message.len());
+
+                let chunk = SRTFrameChunk {
+                    frame_id,
+                    chunk_id,
+                    chunks_count,
+                    data: message[start..end].to_vec(),
+                };
+
+                Bytes::from(bincode::serialize(&chunk).unwrap())
+            })
+            .collect()
+    }
+
+    async fn send_chunks(&mut self, chunks: Vec<Bytes>) -> std::io::Result<()> {
+        let socket = self.connection.socket().await?;
+
+        for chunk in chunks {
+            socket.send((Instant::now(), chunk)).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for SRTFrameSender {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let message = match self.serialize(&mut frame_data) {
+            Ok(message) => message,
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        let chunks = self.split(&message);
+
+        if let Err(error) = self.send_chunks(chunks).await {
+            debug!("Unable to send frame: {}", error);
+            self.connection.disconnect();
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Ok(Some(frame_data))
+    }
+}
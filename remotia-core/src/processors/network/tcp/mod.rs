@@ -0,0 +1,157 @@
+use std::{io, time::Duration};
+
+use log::{debug, info};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+};
+
+#[cfg(feature = "tls")]
+use openssl::ssl::{Ssl, SslAcceptor, SslConnector};
+#[cfg(feature = "tls")]
+use std::pin::Pin;
+#[cfg(feature = "tls")]
+use tokio_openssl::SslStream;
+
+pub mod receiver;
+pub mod sender;
+
+/// Size of the length prefix which precedes each serialized frame
+pub(crate) const TCP_LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Default size above which serialized frames are refused by both ends
+pub(crate) const TCP_MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum TCPEndpoint {
+    /// Connect to a listener at the given address
+    Caller(String),
+
+    /// Wait for a caller on the given local port
+    Listener(u16),
+}
+
+pub(crate) trait TCPStream: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> TCPStream for T {}
+
+#[cfg(feature = "tls")]
+enum TCPSecurity {
+    Connector(SslConnector, String),
+    Acceptor(SslAcceptor),
+}
+
+/// Lazily (re)established TCP connection shared by the sender and the receiver
+pub(crate) struct TCPConnection {
+    endpoint: TCPEndpoint,
+
+    connection_timeout: Duration,
+
+    #[cfg(feature = "tls")]
+    security: Option<TCPSecurity>,
+
+    listener: Option<TcpListener>,
+    stream: Option<Box<dyn TCPStream>>,
+}
+
+impl TCPConnection {
+    pub(crate) fn new(endpoint: TCPEndpoint) -> Self {
+        Self {
+            endpoint,
+            connection_timeout: Duration::from_secs(1),
+            #[cfg(feature = "tls")]
+            security: None,
+            listener: None,
+            stream: None,
+        }
+    }
+
+    pub(crate) fn set_connection_timeout(&mut self, connection_timeout: Duration) {
+        self.connection_timeout = connection_timeout;
+    }
+
+    #[cfg(feature = "tls")]
+    pub(crate) fn set_tls_connector(&mut self, connector: SslConnector, domain: &str) {
+        self.security = Some(TCPSecurity::Connector(connector, domain.to_string()));
+    }
+
+    #[cfg(feature = "tls")]
+    pub(crate) fn set_tls_acceptor(&mut self, acceptor: SslAcceptor) {
+        self.security = Some(TCPSecurity::Acceptor(acceptor));
+    }
+
+    /// Returns the connected stream, connecting first if needed
+    pub(crate) async fn stream(&mut self) -> io::Result<&mut Box<dyn TCPStream>> {
+        if self.stream.is_none() {
+            let connection_timeout = self.connection_timeout;
+            let stream = tokio::time::timeout(connection_timeout, self.connect())
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "TCP connection timeout"))??;
+
+            info!("TCP connection established ({:?})", self.endpoint);
+            self.stream = Some(stream);
+        }
+
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    /// Drops the current stream, so that the next frame triggers a reconnection
+    pub(crate) fn disconnect(&mut self) {
+        if self.stream.take().is_some() {
+            debug!("TCP connection lost ({:?})", self.endpoint);
+        }
+    }
+
+    async fn connect(&mut self) -> io::Result<Box<dyn TCPStream>> {
+        let stream = match &self.endpoint {
+            TCPEndpoint::Caller(address) => TcpStream::connect(address.as_str()).await?,
+            TCPEndpoint::Listener(port) => {
+                if self.listener.is_none() {
+                    self.listener = Some(TcpListener::bind(("0.0.0.0", *port)).await?);
+                }
+
+                let (stream, peer_address) = self.listener.as_ref().unwrap().accept().await?;
+                debug!("Accepted TCP connection from {}", peer_address);
+                stream
+            }
+        };
+
+        stream.set_nodelay(true)?;
+
+        self.secure(stream).await
+    }
+
+    #[cfg(feature = "tls")]
+    async fn secure(&self, stream: TcpStream) -> io::Result<Box<dyn TCPStream>> {
+        let (ssl, accept) = match &self.security {
+            None => return Ok(Box::new(stream)),
+            Some(TCPSecurity::Connector(connector, domain)) => {
+                let ssl = connector
+                    .configure()
+                    .and_then(|configuration| configuration.into_ssl(domain))
+                    .map_err(io::Error::other)?;
+
+                (ssl, false)
+            }
+            Some(TCPSecurity::Acceptor(acceptor)) => {
+                (Ssl::new(acceptor.context()).map_err(io::Error::other)?, true)
+            }
+        };
+
+        let mut stream = SslStream::new(ssl, stream).map_err(io::Error::other)?;
+
+        let handshake = if accept {
+            Pin::new(&mut stream).accept().await
+        } else {
+            Pin::new(&mut stream).connect().await
+        };
+
+        handshake.map_err(io::Error::other)?;
+
+        Ok(Box::new(stream))
+    }
+
+    #[cfg(not(feature = "tls"))]
+    async fn secure(&self, stream: TcpStream) -> io::Result<Box<dyn TCPStream>> {
+        Ok(Box::new(stream))
+    }
+}
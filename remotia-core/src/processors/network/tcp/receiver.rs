@@ -0,0 +1,373 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::{Buf, BytesMut};
+use log::debug;
+use tokio::io::AsyncReadExt;
+
+#[cfg(feature = "tls")]
+use openssl::ssl::{SslAcceptor, SslConnector};
+
+use crate::{
+    common::network::serialized::SerializedFrameData, error::DropReason, traits::FrameProcessor,
+    types::FrameData,
+};
+
+use super::{TCPConnection, TCPEndpoint, TCP_LENGTH_PREFIX_SIZE, TCP_MAX_FRAME_SIZE};
+
+/// Receives the frames sent by a [`super::sender::TCPFrameSender`], restoring the transmitted
+/// buffers and stats. Frames are marked with [`DropReason::ConnectionError`] while the link
+/// is down.
+pub struct TCPFrameReceiver {
+    connection: TCPConnection,
+
+    receive_timeout: Duration,
+    max_frame_size: usize,
+
+    // Bytes received so far, kept across timeouts so that the stream stays aligned
+    pending: BytesMut,
+}
+
+impl TCPFrameReceiver {
+    fn new(endpoint: TCPEndpoint) -> Self {
+        Self {
+            connection: TCPConnection::new(endpoint),
+            receive_timeout: Duration::from_secs(1),
+            max_frame_size: TCP_MAX_FRAME_SIZE,
+            pending: BytesMut::new(),
+        }
+    }
+
+    pub fn caller(remote_address: &str) -> Self {
+        Self::new(TCPEndpoint::Caller(remote_address.to_string()))
+    }
+
+    pub fn listener(port: u16) -> Self {
+        Self::new(TCPEndpoint::Listener(port))
+    }
+
+    pub fn connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection.set_connection_timeout(connection_timeout);
+        self
+    }
+
+    /// Secures the connection established by a caller, verifying the peer against the domain
+    #[cfg(feature = "tls")]
+    pub fn tls_connector(mut self, connector: SslConnector, domain: &str) -> Self {
+        self.connection.set_tls_connector(connector, domain);
+        self
+    }
+
+    /// Secures the connections accepted by a listener
+    #[cfg(feature = "tls")]
+    pub fn tls_acceptor(mut self, acceptor: SslAcceptor) -> Self {
+        self.connection.set_tls_acceptor(acceptor);
+        self
+    }
+
+    /// Maximum time to wait for a frame on an established connection
+    pub fn receive_timeout(mut self, receive_timeout: Duration) -> Self {
+        self.receive_timeout = receive_timeout;
+        self
+    }
+
+    /// Frames announcing a bigger size are considered corrupted and reset the connection.
+    /// Defaults to 64 MiB, the limit enforced by the sender must not exceed it.
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    fn disconnect(&mut self) {
+        self.pending.clear();
+        self.connection.disconnect();
+    }
+
+    /// Extracts a whole message from the received bytes, if any
+    fn pop_message(&mut self) -> Result<Option<BytesMut>, DropReason> {
+        if self.pending.len() < TCP_LENGTH_PREFIX_SIZE {
+            return Ok(None);
+        }
+
+        let mut length_prefix = [0; TCP_LENGTH_PREFIX_SIZE];
+        length_prefix.copy_from_slice(&self.pending[..TCP_LENGTH_PREFIX_SIZE]);
+        let message_size = u32::from_be_bytes(length_prefix) as usize;
+
+        if message_size > self.max_frame_size {
+            debug!("Invalid frame size: {}", message_size);
+            self.disconnect();
+            return Err(DropReason::InvalidPacketHeader);
+        }
+
+        if self.pending.len() < TCP_LENGTH_PREFIX_SIZE + message_size {
+            return Ok(None);
+        }
+
+        self.pending.advance(TCP_LENGTH_PREFIX_SIZE);
+        Ok(Some(self.pending.split_to(message_size)))
+    }
+
+    async fn receive_message(&mut self) -> Result<BytesMut, DropReason> {
+        let deadline = tokio::time::Instant::now() + self.receive_timeout;
+
+        loop {
+            if let Some(message) = self.pop_message()? {
+                return Ok(message);
+            }
+
+            let stream = match self.connection.stream().await {
+                Ok(stream) => stream,
+                Err(error) => {
+                    debug!("Unable to connect: {}", error);
+                    return Err(DropReason::ConnectionError);
+                }
+            };
+
+            match tokio::time::timeout_at(deadline, stream.read_buf(&mut self.pending)).await {
+                Ok(Ok(0)) | Ok(Err(_)) => {
+                    self.disconnect();
+                    return Err(DropReason::ConnectionError);
+                }
+                Ok(Ok(_)) => {}
+                Err(_) => return Err(DropReason::Timeout),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for TCPFrameReceiver {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let message = match self.receive_message().await {
+            Ok(message) => message,
+            Err(drop_reason) => {
+                frame_data.set_drop_reason(Some(drop_reason));
+                return Some(frame_data);
+            }
+        };
+
+        let drop_reason = match bincode::deserialize::<SerializedFrameData>(&message) {
+            Ok(serialized_frame_data) => match serialized_frame_data.restore(&mut frame_data) {
+                Ok(()) => None,
+                Err(key) => {
+                    debug!("Received '{}' buffer does not fit", key);
+                    Some(DropReason::BufferOverflow)
+                }
+            },
+            Err(error) => {
+                debug!("Invalid message: {}", error);
+                Some(DropReason::InvalidPacket)
+            }
+        };
+
+        frame_data.set_drop_reason(drop_reason);
+
+        Some(frame_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, time::Duration};
+
+    use bytes::BytesMut;
+
+    use super::TCPFrameReceiver;
+    use crate::{
+        error::{DropReason, ProcessorError},
+        processors::network::tcp::sender::TCPFrameSender,
+        traits::FrameProcessor,
+        types::FrameData,
+    };
+
+    fn free_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn listener(port: u16) -> TCPFrameReceiver {
+        TCPFrameReceiver::listener(port)
+            .connection_timeout(Duration::from_millis(500))
+            .receive_timeout(Duration::from_millis(500))
+    }
+
+    fn caller(port: u16) -> TCPFrameSender {
+        TCPFrameSender::caller(&format!("127.0.0.1:{}", port))
+            .connection_timeout(Duration::from_millis(500))
+    }
+
+    fn frame(capture_timestamp: u128, size: usize) -> FrameData {
+        let payload: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+
+        let mut frame_data = FrameData::default();
+        frame_data.set("capture_timestamp", capture_timestamp);
+        frame_data.set("encoded_size", size as u128 / 2);
+        frame_data.insert_writable_buffer("encoded_frame_buffer", BytesMut::from(&payload[..]));
+        frame_data
+    }
+
+    async fn transmit(
+        sender: &mut TCPFrameSender,
+        receiver: TCPFrameReceiver,
+        frame_data: FrameData,
+    ) -> (TCPFrameReceiver, FrameData) {
+        let receiving = tokio::spawn(async move {
+            let mut receiver = receiver;
+            let frame_data = receiver.process(FrameData::default()).await.unwrap();
+            (receiver, frame_data)
+        });
+
+        // Let the receiver start listening before connecting
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let sent = sender.try_process(frame_data).await.unwrap().unwrap();
+        assert_eq!(sent.get_drop_reason(), None);
+
+        receiving.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn selected_buffers_and_stats_are_transmitted() {
+        let port = free_port();
+        let mut sender = caller(port)
+            .sized_buffer("encoded_frame_buffer", "encoded_size")
+            .stat("capture_timestamp");
+
+        let (_, mut received) = transmit(&mut sender, listener(port), frame(7, 64)).await;
+
+        assert_eq!(received.get_drop_reason(), None);
+        assert_eq!(received.get("capture_timestamp"), 7);
+        assert!(!received.has("encoded_size"));
+        assert_eq!(
+            received.extract_writable_buffer("encoded_frame_buffer"),
+            Some(BytesMut::from(&(0..32).collect::<Vec<u8>>()[..]))
+        );
+    }
+
+    #[tokio::test]
+    async fn frames_above_the_limit_are_refused_by_the_sender() {
+        let port = free_port();
+        let mut sender = caller(port).max_frame_size(256);
+
+        let (receiver, received) = transmit(&mut sender, listener(port), frame(1, 64)).await;
+        assert_eq!(received.get("capture_timestamp"), 1);
+
+        let (_, error) = sender.try_process(frame(2, 1024)).await.unwrap_err();
+        assert!(matches!(error, ProcessorError::Other(_)));
+
+        // The connection is still aligned
+        let (_, received) = transmit(&mut sender, receiver, frame(3, 64)).await;
+        assert_eq!(received.get_drop_reason(), None);
+        assert_eq!(received.get("capture_timestamp"), 3);
+    }
+
+    #[tokio::test]
+    async fn frames_above_the_limit_reset_the_receiver() {
+        let port = free_port();
+        let mut sender = caller(port);
+        let receiver = listener(port).max_frame_size(256);
+
+        let (_, received) = transmit(&mut sender, receiver, frame(1, 1024)).await;
+
+        assert_eq!(
+            received.get_drop_reason(),
+            Some(DropReason::InvalidPacketHeader)
+        );
+        assert!(!received.has("capture_timestamp"));
+    }
+
+    #[tokio::test]
+    async fn sender_reconnects_after_the_receiver_restarts() {
+        let port = free_port();
+        let mut sender = caller(port);
+
+        let (receiver, _) = transmit(&mut sender, listener(port), frame(1, 64)).await;
+        drop(receiver);
+
+        // Writes only fail once the peer has reset the connection
+        let mut capture_timestamp = 2;
+        loop {
+            let sent = sender
+                .try_process(frame(capture_timestamp, 64))
+                .await
+                .unwrap()
+                .unwrap();
+            capture_timestamp += 1;
+
+            if sent.get_drop_reason() == Some(DropReason::ConnectionError) {
+                break;
+            }
+
+            assert!(capture_timestamp < 100);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let (_, received) =
+            transmit(&mut sender, listener(port), frame(capture_timestamp, 64)).await;
+        assert_eq!(received.get_drop_reason(), None);
+        assert_eq!(received.get("capture_timestamp"), capture_timestamp);
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn frames_are_transmitted_over_tls() {
+        use openssl::{
+            asn1::Asn1Time,
+            hash::MessageDigest,
+            pkey::PKey,
+            rsa::Rsa,
+            ssl::{SslAcceptor, SslConnector, SslMethod},
+            x509::{extension::SubjectAlternativeName, X509NameBuilder, X509},
+        };
+
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "localhost").unwrap();
+        let name = name.build();
+
+        let mut certificate = X509::builder().unwrap();
+        certificate.set_version(2).unwrap();
+        certificate.set_subject_name(&name).unwrap();
+        certificate.set_issuer_name(&name).unwrap();
+        certificate.set_pubkey(&key).unwrap();
+        certificate
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        certificate
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let subject_alternative_name = SubjectAlternativeName::new()
+            .dns("localhost")
+            .build(&certificate.x509v3_context(None, None))
+            .unwrap();
+        certificate
+            .append_extension(subject_alternative_name)
+            .unwrap();
+        certificate.sign(&key, MessageDigest::sha256()).unwrap();
+        let certificate = certificate.build();
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_certificate(&certificate).unwrap();
+
+        let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+        connector.cert_store_mut().add_cert(certificate).unwrap();
+
+        let port = free_port();
+        let mut sender = caller(port).tls_connector(connector.build(), "localhost");
+        let receiver = listener(port).tls_acceptor(acceptor.build());
+
+        let (_, mut received) = transmit(&mut sender, receiver, frame(5, 2048)).await;
+
+        assert_eq!(received.get_drop_reason(), None);
+        assert_eq!(received.get("capture_timestamp"), 5);
+        assert_eq!(
+            received.extract_writable_buffer("encoded_frame_buffer"),
+            frame(5, 2048).extract_writable_buffer("encoded_frame_buffer")
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::{io, time::Duration};
+
+use async_trait::async_trait;
+use log::debug;
+use tokio::io::AsyncWriteExt;
+
+#[cfg(feature = "tls")]
+use openssl::ssl::{SslAcceptor, SslConnector};
+
+use crate::{
+    common::network::serialized::SerializedFrameData,
+    error::{DropReason, ProcessorError},
+    traits::{FallibleFrameProcessor, ProcessorResult},
+    types::FrameData,
+};
+
+use super::{TCPConnection, TCPEndpoint, TCP_MAX_FRAME_SIZE};
+
+/// Sends the selected buffers and stats of a frame over a TCP stream, each frame being
+/// prefixed by its length. Frames are marked with [`DropReason::ConnectionError`] while the
+/// link is down. Frames whose serialized size exceeds the receiver's limit are refused with a
+/// processor error instead of resetting the connection.
+pub struct TCPFrameSender {
+    connection: TCPConnection,
+
+    buffers: Vec<(String, Option<String>)>,
+    stat_ids: Option<Vec<String>>,
+    max_frame_size: usize,
+}
+
+impl TCPFrameSender {
+    fn new(endpoint: TCPEndpoint) -> Self {
+        Self {
+            connection: TCPConnection::new(endpoint),
+            buffers: Vec::new(),
+            stat_ids: None,
+            max_frame_size: TCP_MAX_FRAME_SIZE,
+        }
+    }
+
+    pub fn caller(remote_address: &str) -> Self {
+        Self::new(TCPEndpoint::Caller(remote_address.to_string()))
+    }
+
+    pub fn listener(port: u16) -> Self {
+        Self::new(TCPEndpoint::Listener(port))
+    }
+
+    pub fn connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection.set_connection_timeout(connection_timeout);
+        self
+    }
+
+    /// Secures the connection established by a caller, verifying the peer against the domain
+    #[cfg(feature = "tls")]
+    pub fn tls_connector(mut self, connector: SslConnector, domain: &str) -> Self {
+        self.connection.set_tls_connector(connector, domain);
+        self
+    }
+
+    /// Secures the connections accepted by a listener
+    #[cfg(feature = "tls")]
+    pub fn tls_acceptor(mut self, acceptor: SslAcceptor) -> Self {
+        self.connection.set_tls_acceptor(acceptor);
+        self
+    }
+
+    /// Adds a buffer to be sent as a whole. If no buffer is selected, the
+    /// "encoded_frame_buffer" one is sent.
+    pub fn buffer(mut self, buffer_id: &str) -> Self {
+        self.buffers.push((buffer_id.to_string(), None));
+        self
+    }
+
+    /// Adds a buffer of which only the amount of bytes held by the size stat is sent
+    pub fn sized_buffer(mut self, buffer_id: &str, size_stat_id: &str) -> Self {
+        self.buffers
+            .push((buffer_id.to_string(), Some(size_stat_id.to_string())));
+        self
+    }
+
+    /// Adds a stat to be sent. All the stats are sent if none is selected.
+    pub fn stat(mut self, stat_id: &str) -> Self {
+        self.stat_ids
+            .get_or_insert_with(Vec::new)
+            .push(stat_id.to_string());
+        self
+    }
+
+    /// Largest serialized frame to be sent, which must match the `max_frame_size` of the
+    /// receiver. Defaults to 64 MiB.
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    fn serialize(&self, frame_data: &mut FrameData) -> Result<Vec<u8>, ProcessorError> {
+        let stats = match &self.stat_ids {
+            Some(stat_ids) => stat_ids
+                .iter()
+                .filter_map(|stat_id| {
+                    let value = frame_data.try_get_value(stat_id).ok()?;
+                    Some((stat_id.clone(), value.clone()))
+                })
+                .collect(),
+            None => frame_data.get_stats().clone(),
+        };
+
+        let mut serialized_frame_data = SerializedFrameData::new(stats);
+
+        let default_buffers = [("encoded_frame_buffer".to_string(), None)];
+        let buffers = if self.buffers.is_empty() {
+            &default_buffers[..]
+        } else {
+            &self.buffers[..]
+        };
+
+        for (buffer_id, size_stat_id) in buffers {
+            let size = match size_stat_id {
+                Some(size_stat_id) => Some(frame_data.try_get(size_stat_id)? as usize),
+                None => None,
+            };
+
+            let buffer = frame_data
+                .get_writable_buffer_ref(buffer_id)
+                .ok_or_else(|| ProcessorError::MissingBuffer(buffer_id.clone()))?;
+            let size = size.unwrap_or(buffer.len());
+            let data = buffer.get(..size).ok_or_else(|| {
+                ProcessorError::Other(format!("Size {} exceeds the '{}' buffer", size, buffer_id))
+            })?;
+
+            serialized_frame_data.insert_buffer(buffer_id, data);
+        }
+
+        Ok(bincode::serialize(&serialized_frame_data).unwrap())
+    }
+
+    /// Returns the length prefix of a serialized frame, if it can be sent
+    fn length_prefix(&self, message: &[u8]) -> Result<u32, ProcessorError> {
+        if message.len() > self.max_frame_size {
+            return Err(ProcessorError::Other(format!(
+                "Frame size {} exceeds the limit of {} bytes",
+                message.len(),
+                self.max_frame_size
+            )));
+        }
+
+        u32::try_from(message.len()).map_err(|_| {
+            ProcessorError::Other(format!(
+                "Frame size {} does not fit the length prefix",
+                message.len()
+            ))
+        })
+    }
+
+    async fn send_message(&mut self, message_size: u32, message: &[u8]) -> io::Result<()> {
+        let stream = self.connection.stream().await?;
+
+        stream.write_u32(message_size).await?;
+        stream.write_all(message).await?;
+        stream.flush().await
+    }
+}
+
+#[async_trait]
+impl FallibleFrameProcessor for TCPFrameSender {
+    async fn try_process(&mut self, mut frame_data: FrameData) -> ProcessorResult {
+        let message = match self.serialize(&mut frame_data) {
+            Ok(message) => message,
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        let message_size = match self.length_prefix(&message) {
+            Ok(message_size) => message_size,
+            Err(error) => return Err((frame_data, error)),
+        };
+
+        if let Err(error) = self.send_message(message_size, &message).await {
+            debug!("Unable to send frame: {}", error);
+            self.connection.disconnect();
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Ok(Some(frame_data))
+    }
+}
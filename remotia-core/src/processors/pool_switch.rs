@@ -5,8 +5,9 @@ use log::debug;
 use rand::prelude::{SliceRandom, ThreadRng};
 
 use crate::{
+    error::ProcessorError,
     pipeline::ascode::{feeder::AscodePipelineFeeder, AscodePipeline},
-    traits::FrameProcessor,
+    traits::{FallibleFrameProcessor, FrameProcessor, ProcessorResult},
     types::FrameData,
 };
 
@@ -41,7 +42,7 @@ impl FrameProcessor for PoolingSwitch {
         debug!("Feeding to pipeline #{}...", key);
 
         frame_data.set("pool_key", *key);
-        feeder.feed(frame_data);
+        feeder.feed(frame_data).await;
 
         None
     }
@@ -71,16 +72,25 @@ impl Default for DepoolingSwitch {
 }
 
 #[async_trait]
-impl FrameProcessor for DepoolingSwitch {
-    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
-        let key = frame_data.get("pool_key");
-        let feeder = self.entries.get(&key).unwrap();
+impl FallibleFrameProcessor for DepoolingSwitch {
+    async fn try_process(&mut self, frame_data: FrameData) -> ProcessorResult {
+        let key = match frame_data.try_get("pool_key") {
+            Ok(key) => key,
+            Err(error) => return Err((frame_data, error.into())),
+        };
+        let feeder = match self.entries.get(&key) {
+            Some(feeder) => feeder,
+            None => {
+                let error = ProcessorError::Other(format!("No pipeline for pool key {}", key));
+                return Err((frame_data, error));
+            }
+        };
 
         debug!("Feeding to pipeline #{}...", key);
 
-        feeder.feed(frame_data);
+        feeder.feed(frame_data).await;
 
-        None
+        Ok(None)
     }
 }
 
@@ -18,7 +18,7 @@ impl Switch {
 #[async_trait]
 impl FrameProcessor for Switch {
     async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
-        self.feeder.feed(frame_data);
+        self.feeder.feed(frame_data).await;
         None
     }
 }
@@ -1,8 +1,97 @@
-use super::types::FrameData;
+use super::{
+    error::{DropReason, ProcessorError},
+    types::FrameData,
+};
 
 use async_trait::async_trait;
+use log::warn;
+
+/// Result of a fallible processor. On failure the frame is handed back together with the error,
+/// so that the component can handle it according to its error policy.
+pub type ProcessorResult = Result<Option<FrameData>, (FrameData, ProcessorError)>;
 
 #[async_trait]
 pub trait FrameProcessor {
     async fn process(&mut self, frame_data: FrameData) -> Option<FrameData>;
-}
\ No newline at end of file
+
+    /// Entry point used by the components. Infallible processors never return an error.
+    async fn try_process(&mut self, frame_data: FrameData) -> ProcessorResult {
+        Ok(self.process(frame_data).await)
+    }
+
+    /// Called once when the end of the stream reaches the component. Any frame which is still
+    /// held by the processor should be returned, in order, to be handled by the next processors.
+    async fn flush(&mut self) -> Vec<FrameData> {
+        Vec::new()
+    }
+}
+
+#[async_trait]
+pub trait FallibleFrameProcessor {
+    async fn try_process(&mut self, frame_data: FrameData) -> ProcessorResult;
+
+    async fn flush(&mut self) -> Vec<FrameData> {
+        Vec::new()
+    }
+}
+
+#[async_trait]
+impl<T: FallibleFrameProcessor + Send> FrameProcessor for T {
+    /// Marks the frame with [`DropReason::ProcessorError`] on error, as the default policy of
+    /// the components does. Components rely on `try_process` to apply their own policy.
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        match FallibleFrameProcessor::try_process(self, frame_data).await {
+            Ok(frame_data) => frame_data,
+            Err((mut frame_data, error)) => {
+                warn!("Processor error: {}", error);
+                frame_data.set_drop_reason(Some(DropReason::ProcessorError));
+                Some(frame_data)
+            }
+        }
+    }
+
+    async fn try_process(&mut self, frame_data: FrameData) -> ProcessorResult {
+        FallibleFrameProcessor::try_process(self, frame_data).await
+    }
+
+    async fn flush(&mut self) -> Vec<FrameData> {
+        FallibleFrameProcessor::flush(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use crate::{
+        error::{DropReason, ProcessorError},
+        types::FrameData,
+    };
+
+    use super::{FallibleFrameProcessor, FrameProcessor, ProcessorResult};
+
+    struct Failing;
+
+    #[async_trait]
+    impl FallibleFrameProcessor for Failing {
+        async fn try_process(&mut self, frame_data: FrameData) -> ProcessorResult {
+            Err((frame_data, ProcessorError::Other("Failure".to_string())))
+        }
+    }
+
+    #[tokio::test]
+    async fn process_marks_failed_frames() {
+        let mut frame_data = FrameData::default();
+        frame_data.set("index", 7);
+
+        let frame_data = FrameProcessor::process(&mut Failing, frame_data)
+            .await
+            .unwrap();
+
+        assert_eq!(frame_data.get("index"), 7);
+        assert_eq!(
+            frame_data.get_drop_reason(),
+            Some(DropReason::ProcessorError)
+        );
+    }
+}
@@ -1,19 +1,138 @@
 use std::{
     collections::{hash_map::Keys, HashMap},
     fmt::Display,
+    time::Duration,
 };
 
 use bytes::{Bytes, BytesMut};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::error::DropReason;
+use crate::error::{DropReason, ProcessorError, StatError};
+
+/// Value of a frame stat
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StatValue {
+    Unsigned(u128),
+    Signed(i128),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+    Duration(Duration),
+
+    /// Pair of unsigned values, such as a resolution
+    Pair(u128, u128),
+}
+
+impl StatValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            StatValue::Unsigned(_) => "unsigned",
+            StatValue::Signed(_) => "signed",
+            StatValue::Float(_) => "float",
+            StatValue::Bool(_) => "bool",
+            StatValue::Text(_) => "text",
+            StatValue::Duration(_) => "duration",
+            StatValue::Pair(..) => "pair",
+        }
+    }
+
+    /// Numeric representation of the value, if any. Durations are expressed in milliseconds
+    /// and booleans as 0 or 1.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            StatValue::Unsigned(value) => Some(*value as f64),
+            StatValue::Signed(value) => Some(*value as f64),
+            StatValue::Float(value) => Some(*value),
+            StatValue::Bool(value) => Some(if *value { 1.0 } else { 0.0 }),
+            StatValue::Text(_) | StatValue::Pair(..) => None,
+            StatValue::Duration(value) => Some(value.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+/// Durations are displayed in milliseconds, as the timestamps used across the framework, and
+/// pairs as resolutions
+impl Display for StatValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatValue::Unsigned(value) => write!(f, "{}", value),
+            StatValue::Signed(value) => write!(f, "{}", value),
+            StatValue::Float(value) => write!(f, "{}", value),
+            StatValue::Bool(value) => write!(f, "{}", value),
+            StatValue::Text(value) => write!(f, "{}", value),
+            StatValue::Duration(value) => write!(f, "{}", value.as_secs_f64() * 1000.0),
+            StatValue::Pair(first, second) => write!(f, "{}x{}", first, second),
+        }
+    }
+}
+
+macro_rules! stat_value_from {
+    ($type:ty, $variant:ident) => {
+        impl From<$type> for StatValue {
+            fn from(value: $type) -> Self {
+                StatValue::$variant(value.into())
+            }
+        }
+    };
+}
+
+stat_value_from!(u128, Unsigned);
+stat_value_from!(u64, Unsigned);
+stat_value_from!(u32, Unsigned);
+stat_value_from!(u16, Unsigned);
+stat_value_from!(u8, Unsigned);
+stat_value_from!(i128, Signed);
+stat_value_from!(i64, Signed);
+stat_value_from!(i32, Signed);
+stat_value_from!(i16, Signed);
+stat_value_from!(i8, Signed);
+stat_value_from!(f64, Float);
+stat_value_from!(f32, Float);
+stat_value_from!(bool, Bool);
+stat_value_from!(String, Text);
+stat_value_from!(&str, Text);
+stat_value_from!(Duration, Duration);
+
+// Pointer-sized integers have no lossless conversion to the wider ones
+impl From<usize> for StatValue {
+    fn from(value: usize) -> Self {
+        StatValue::Unsigned(value as u128)
+    }
+}
+
+impl From<isize> for StatValue {
+    fn from(value: isize) -> Self {
+        StatValue::Signed(value as i128)
+    }
+}
+
+impl<T: Into<u128>> From<(T, T)> for StatValue {
+    fn from((first, second): (T, T)) -> Self {
+        StatValue::Pair(first.into(), second.into())
+    }
+}
+
+macro_rules! typed_stat_getters {
+    ($get:ident, $try_get:ident, $variant:ident, $type:ty, $type_name:literal) => {
+        pub fn $get(&self, key: &str) -> $type {
+            self.$try_get(key).unwrap_or_else(|error| panic!("{}", error))
+        }
+
+        pub fn $try_get(&self, key: &str) -> Result<$type, StatError> {
+            match self.try_get_value(key)? {
+                StatValue::$variant(value) => Ok(*value),
+                value => Err(wrong_type_error(key, $type_name, value)),
+            }
+        }
+    };
+}
 
 #[derive(Default, Clone, Debug)]
 pub struct FrameData {
     readonly_buffers: HashMap<String, Bytes>,
     writable_buffers: HashMap<String, BytesMut>,
 
-    stats: HashMap<String, u128>,
+    stats: HashMap<String, StatValue>,
 
     drop_reason: Option<DropReason>,
 }
@@ -24,25 +143,56 @@ impl FrameData {
     //*******//
 
     pub fn set(&mut self, key: &str, value: u128) {
-        self.stats.insert(key.to_string(), value);
+        self.set_value(key, value);
+    }
+
+    pub fn set_value<T: Into<StatValue>>(&mut self, key: &str, value: T) {
+        self.stats.insert(key.to_string(), value.into());
+    }
+
+    typed_stat_getters!(get, try_get, Unsigned, u128, "unsigned");
+    typed_stat_getters!(get_signed, try_get_signed, Signed, i128, "signed");
+    typed_stat_getters!(get_float, try_get_float, Float, f64, "float");
+    typed_stat_getters!(get_bool, try_get_bool, Bool, bool, "bool");
+    typed_stat_getters!(get_duration, try_get_duration, Duration, Duration, "duration");
+
+    pub fn get_pair(&self, key: &str) -> (u128, u128) {
+        self.try_get_pair(key).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_get_pair(&self, key: &str) -> Result<(u128, u128), StatError> {
+        match self.try_get_value(key)? {
+            StatValue::Pair(first, second) => Ok((*first, *second)),
+            value => Err(wrong_type_error(key, "pair", value)),
+        }
+    }
+
+    pub fn get_text(&self, key: &str) -> &str {
+        self.try_get_text(key).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_get_text(&self, key: &str) -> Result<&str, StatError> {
+        match self.try_get_value(key)? {
+            StatValue::Text(value) => Ok(value),
+            value => Err(wrong_type_error(key, "text", value)),
+        }
     }
 
-    pub fn get(&self, key: &str) -> u128 {
-        *self
-            .stats
+    pub fn try_get_value(&self, key: &str) -> Result<&StatValue, StatError> {
+        self.stats
             .get(key)
-            .unwrap_or_else(|| panic!("{}", missing_key_msg(key)))
+            .ok_or_else(|| StatError::Missing(key.to_string()))
     }
 
     pub fn has(&self, key: &str) -> bool {
         self.stats.contains_key(key)
     }
 
-    pub fn get_stats(&self) -> &HashMap<String, u128> {
+    pub fn get_stats(&self) -> &HashMap<String, StatValue> {
         &self.stats
     }
 
-    pub fn merge_stats(&mut self, other_stats: HashMap<String, u128>) {
+    pub fn merge_stats(&mut self, other_stats: HashMap<String, StatValue>) {
         self.stats.extend(other_stats);
     }
 
@@ -87,11 +237,42 @@ impl FrameData {
     pub fn get_writable_buffers_keys(&self) -> Vec<String> {
         self.writable_buffers
             .keys()
-            .into_iter()
             .map(|key| key.to_string())
             .collect()
     }
 
+    /// Extracts the writable buffers of the given keys, lends them to `f` in order and inserts
+    /// them back. Nothing is extracted if one of them is missing, so that no buffer is lost on
+    /// error.
+    pub fn with_writable_buffers<R>(
+        &mut self,
+        keys: &[&str],
+        f: impl FnOnce(&mut [BytesMut]) -> R,
+    ) -> Result<R, ProcessorError> {
+        for (index, key) in keys.iter().enumerate() {
+            if !self.has_writable_buffer(key) {
+                return Err(ProcessorError::MissingBuffer(key.to_string()));
+            }
+
+            if keys[..index].contains(key) {
+                return Err(ProcessorError::Other(format!("Buffer '{}' is lent twice", key)));
+            }
+        }
+
+        let mut buffers: Vec<BytesMut> = keys
+            .iter()
+            .map(|key| self.extract_writable_buffer(key).unwrap())
+            .collect();
+
+        let result = f(&mut buffers);
+
+        for (key, buffer) in keys.iter().zip(buffers) {
+            self.insert_writable_buffer(key, buffer);
+        }
+
+        Ok(result)
+    }
+
     //*************//
     // Drop reason //
     //*************//
@@ -122,6 +303,14 @@ fn missing_key_msg(key: &str) -> String {
     format!("Missing key '{}'", key)
 }
 
+fn wrong_type_error(key: &str, expected: &'static str, value: &StatValue) -> StatError {
+    StatError::WrongType {
+        key: key.to_string(),
+        expected,
+        found: value.type_name(),
+    }
+}
+
 impl Display for FrameData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -134,3 +323,144 @@ impl Display for FrameData {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::BytesMut;
+
+    use crate::error::{ProcessorError, StatError};
+
+    use super::{FrameData, StatValue};
+
+    #[test]
+    fn typed_stats_are_read_back() {
+        let mut frame_data = FrameData::default();
+        frame_data.set("timestamp", 42);
+        frame_data.set_value("clock_offset", -7i64);
+        frame_data.set_value("psnr", 38.5);
+        frame_data.set_value("keyframe", true);
+        frame_data.set_value("codec", "av1");
+        frame_data.set_value("encoding_time", Duration::from_millis(3));
+        frame_data.set_value("resolution", (1920u32, 1080u32));
+
+        assert_eq!(frame_data.get("timestamp"), 42);
+        assert_eq!(frame_data.get_signed("clock_offset"), -7);
+        assert_eq!(frame_data.get_float("psnr"), 38.5);
+        assert!(frame_data.get_bool("keyframe"));
+        assert_eq!(frame_data.get_text("codec"), "av1");
+        assert_eq!(
+            frame_data.get_duration("encoding_time"),
+            Duration::from_millis(3)
+        );
+        assert_eq!(frame_data.get_pair("resolution"), (1920, 1080));
+    }
+
+    #[test]
+    fn narrower_integers_are_widened() {
+        let mut frame_data = FrameData::default();
+        frame_data.set_value("u8", u8::MAX);
+        frame_data.set_value("u32", u32::MAX);
+        frame_data.set_value("usize", usize::MAX);
+        frame_data.set_value("i16", i16::MIN);
+        frame_data.set_value("isize", -1isize);
+        frame_data.set_value("f32", 0.5f32);
+
+        assert_eq!(frame_data.get("u8"), u8::MAX as u128);
+        assert_eq!(frame_data.get("u32"), u32::MAX as u128);
+        assert_eq!(frame_data.get("usize"), usize::MAX as u128);
+        assert_eq!(frame_data.get_signed("i16"), i16::MIN as i128);
+        assert_eq!(frame_data.get_signed("isize"), -1);
+        assert_eq!(frame_data.get_float("f32"), 0.5);
+    }
+
+    #[test]
+    fn fallible_getters_report_missing_and_mistyped_stats() {
+        let mut frame_data = FrameData::default();
+        frame_data.set_value("codec", "av1");
+
+        assert_eq!(
+            frame_data.try_get("timestamp"),
+            Err(StatError::Missing("timestamp".to_string()))
+        );
+        assert_eq!(
+            frame_data.try_get("codec"),
+            Err(StatError::WrongType {
+                key: "codec".to_string(),
+                expected: "unsigned",
+                found: "text",
+            })
+        );
+        assert_eq!(
+            frame_data.try_get_pair("codec"),
+            Err(StatError::WrongType {
+                key: "codec".to_string(),
+                expected: "pair",
+                found: "text",
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Missing stat 'timestamp'")]
+    fn get_panics_on_missing_stats() {
+        FrameData::default().get("timestamp");
+    }
+
+    #[test]
+    fn values_are_formatted_by_type() {
+        let values = [
+            (StatValue::Unsigned(3), "3", Some(3.0)),
+            (StatValue::Signed(-3), "-3", Some(-3.0)),
+            (StatValue::Float(1.5), "1.5", Some(1.5)),
+            (StatValue::Bool(true), "true", Some(1.0)),
+            (StatValue::Text("av1".to_string()), "av1", None),
+            (
+                StatValue::Duration(Duration::from_micros(2500)),
+                "2.5",
+                Some(2.5),
+            ),
+            (StatValue::Pair(1280, 720), "1280x720", None),
+        ];
+
+        for (value, displayed, numeric) in values {
+            assert_eq!(value.to_string(), displayed);
+            assert_eq!(value.as_f64(), numeric);
+        }
+    }
+
+    #[test]
+    fn lent_buffers_are_inserted_back() {
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer("source", BytesMut::from(&[1, 2, 3][..]));
+        frame_data.insert_writable_buffer("destination", BytesMut::from(&[0, 0, 0][..]));
+
+        let lent = frame_data.with_writable_buffers(&["source", "destination"], |buffers| {
+            let (source, destination) = buffers.split_at_mut(1);
+            destination[0].copy_from_slice(&source[0]);
+            buffers.len()
+        });
+
+        assert_eq!(lent.unwrap(), 2);
+        assert_eq!(
+            frame_data.extract_writable_buffer("destination"),
+            Some(BytesMut::from(&[1, 2, 3][..]))
+        );
+        assert!(frame_data.has_writable_buffer("source"));
+    }
+
+    #[test]
+    fn no_buffer_is_lent_if_one_is_missing_or_repeated() {
+        let mut frame_data = FrameData::default();
+        frame_data.insert_writable_buffer("source", BytesMut::from(&[1][..]));
+
+        let missing = frame_data.with_writable_buffers(&["source", "destination"], |_| ());
+        assert!(matches!(missing, Err(ProcessorError::MissingBuffer(key)) if key == "destination"));
+
+        let repeated = frame_data.with_writable_buffers(&["source", "source"], |_| ());
+        assert!(matches!(repeated, Err(ProcessorError::Other(_))));
+
+        assert!(frame_data.has_writable_buffer("source"));
+    }
+}